@@ -0,0 +1,463 @@
+//! Rust code for an OpenAPI [`Document`].
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use serde_json::Value;
+
+use crate::openapi::{
+    AdditionalProperties, Document, MediaType, Operation, Parameter, ParameterLocation, PathItem,
+    Schema,
+};
+use crate::{CodegenError, Generator};
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+pub(crate) fn generate(generator: &Generator, document: &Document) -> Result<String, CodegenError> {
+    let client = match &generator.client_name {
+        Some(name) => name.clone(),
+        None => format!("{}Client", type_name(&document.info.title)),
+    };
+    let api = generator.api_client_path.as_str();
+    let mut emitter =
+        Emitter { document, api, client: &client, items: Vec::new(), types: BTreeSet::new() };
+
+    for (name, schema) in &document.components.schemas {
+        emitter.named_type(&type_name(name), schema, &format!("schema `{name}`"))?;
+    }
+
+    let mut methods = Vec::new();
+    let mut method_names = BTreeSet::new();
+    for (path, item) in &document.paths {
+        for (method, operation) in item.operations() {
+            let location = format!("operation `{method} {path}`");
+            let (name, code) = emitter.operation(path, item, method, operation, &location)?;
+            if !method_names.insert(name.clone()) {
+                return Err(unsupported(&location, format!("method `{name}` is generated twice")));
+            }
+            methods.push(code);
+        }
+    }
+
+    let title = &document.info.title;
+    let mut out =
+        format!("// @generated by `my_api_client_codegen` from {title:?}, do not edit.\n");
+    for item in &emitter.items {
+        out.push('\n');
+        out.push_str(item);
+    }
+    out.push('\n');
+    doc(&mut out, "", document.info.description.as_deref().unwrap_or(title));
+    writeln!(out, "pub struct {client} {{\n    pub client: {api}::ApiClient,\n}}\n").unwrap();
+    writeln!(out, "impl {client} {{").unwrap();
+    writeln!(out, "    pub fn new(client: {api}::ApiClient) -> Self {{").unwrap();
+    writeln!(out, "        Self {{ client }}\n    }}").unwrap();
+    for method in methods {
+        out.push('\n');
+        out.push_str(&method);
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+fn unsupported(location: &str, reason: impl Into<String>) -> CodegenError {
+    CodegenError::Unsupported { location: location.to_owned(), reason: reason.into() }
+}
+
+fn type_name(name: &str) -> String {
+    name.to_upper_camel_case()
+}
+
+fn field_name(name: &str) -> String {
+    let name = name.to_snake_case();
+    if KEYWORDS.contains(&name.as_str()) { format!("{name}_") } else { name }
+}
+
+fn doc(out: &mut String, indent: &str, text: &str) {
+    for line in text.trim().lines() {
+        match line.trim_end() {
+            "" => writeln!(out, "{indent}///").unwrap(),
+            line => writeln!(out, "{indent}/// {line}").unwrap(),
+        }
+    }
+}
+
+/// `application/json` or any `application/*+json`.
+fn json_content(content: &std::collections::BTreeMap<String, MediaType>) -> Option<&MediaType> {
+    content.iter().find_map(|(media_type, content)| {
+        let is_json = media_type == "application/json"
+            || (media_type.starts_with("application/") && media_type.ends_with("+json"));
+        is_json.then_some(content)
+    })
+}
+
+enum ResponseKind {
+    /// Deserialized as the type.
+    Json(String),
+    /// No content.
+    Empty,
+    /// Non-JSON content, returned as `ApiResponse` to read in any mode.
+    Raw,
+}
+
+struct Emitter<'a> {
+    document: &'a Document,
+    api: &'a str,
+    client: &'a str,
+    /// Type definitions in order of generation.
+    items: Vec<String>,
+    types: BTreeSet<String>,
+}
+
+impl Emitter<'_> {
+    fn define(&mut self, name: &str, code: String, location: &str) -> Result<(), CodegenError> {
+        if !self.types.insert(name.to_owned()) {
+            return Err(unsupported(location, format!("type `{name}` is generated twice")));
+        }
+        self.items.push(code);
+        Ok(())
+    }
+
+    fn named_type(
+        &mut self,
+        name: &str,
+        schema: &Schema,
+        location: &str,
+    ) -> Result<(), CodegenError> {
+        let type_ = self.rust_type(schema, name, location)?;
+        if type_ == name {
+            return Ok(());
+        }
+        let mut code = String::new();
+        doc(&mut code, "", schema.description.as_deref().unwrap_or_default());
+        let type_ = if schema.is_nullable() { format!("Option<{type_}>") } else { type_ };
+        writeln!(code, "pub type {name} = {type_};").unwrap();
+        self.define(name, code, location)
+    }
+
+    /// Returns the Rust type of `schema`, defining a struct or enum named `hint` if it's needed.
+    fn rust_type(
+        &mut self,
+        schema: &Schema,
+        hint: &str,
+        location: &str,
+    ) -> Result<String, CodegenError> {
+        if let Some(reference) = &schema.ref_ {
+            self.resolve_schema(reference, location)?;
+            let name = reference.trim_start_matches("#/components/schemas/");
+            return Ok(type_name(name));
+        }
+        if !schema.all_of.is_empty() || !schema.one_of.is_empty() || !schema.any_of.is_empty() {
+            return Ok("serde_json::Value".into());
+        }
+
+        let type_ = match schema.type_name() {
+            Some("string") if !schema.enum_.is_empty() => {
+                self.string_enum(hint, schema, location)?;
+                hint.to_owned()
+            }
+            Some("string") => "String".into(),
+            Some("integer") if schema.format.as_deref() == Some("int32") => "i32".into(),
+            Some("integer") => "i64".into(),
+            Some("number") if schema.format.as_deref() == Some("float") => "f32".into(),
+            Some("number") => "f64".into(),
+            Some("boolean") => "bool".into(),
+            Some("array") => match &schema.items {
+                Some(items) => {
+                    format!("Vec<{}>", self.rust_type(items, &format!("{hint}Item"), location)?)
+                }
+                None => "Vec<serde_json::Value>".into(),
+            },
+            Some("object") | None if !schema.properties.is_empty() => {
+                self.struct_(hint, schema, location)?;
+                hint.to_owned()
+            }
+            Some("object") | None => match &schema.additional_properties {
+                Some(AdditionalProperties::Schema(values)) => {
+                    let values = self.rust_type(values, &format!("{hint}Value"), location)?;
+                    format!("std::collections::BTreeMap<String, {values}>")
+                }
+                _ => "serde_json::Value".into(),
+            },
+            Some(other) => return Err(unsupported(location, format!("type `{other}`"))),
+        };
+        Ok(type_)
+    }
+
+    fn resolve_schema(&self, reference: &str, location: &str) -> Result<&Schema, CodegenError> {
+        reference
+            .strip_prefix("#/components/schemas/")
+            .and_then(|name| self.document.components.schemas.get(name))
+            .ok_or_else(|| unsupported(location, format!("unknown reference `{reference}`")))
+    }
+
+    fn resolve_parameter(
+        &self,
+        parameter: &Parameter,
+        location: &str,
+    ) -> Result<Parameter, CodegenError> {
+        let Some(reference) = &parameter.ref_ else {
+            return Ok(parameter.clone());
+        };
+        reference
+            .strip_prefix("#/components/parameters/")
+            .and_then(|name| self.document.components.parameters.get(name))
+            .cloned()
+            .ok_or_else(|| unsupported(location, format!("unknown reference `{reference}`")))
+    }
+
+    fn struct_(&mut self, name: &str, schema: &Schema, location: &str) -> Result<(), CodegenError> {
+        let mut code = String::new();
+        doc(&mut code, "", schema.description.as_deref().unwrap_or_default());
+        code.push_str("#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]\n");
+        writeln!(code, "pub struct {name} {{").unwrap();
+        for (key, property) in &schema.properties {
+            let type_ = self.rust_type(property, &format!("{name}{}", type_name(key)), location)?;
+            let required = schema.required.contains(key);
+            let description = property.description.as_deref();
+            field(&mut code, key, description, type_, required, property.is_nullable());
+        }
+        code.push_str("}\n");
+        self.define(name, code, location)
+    }
+
+    fn string_enum(
+        &mut self,
+        name: &str,
+        schema: &Schema,
+        location: &str,
+    ) -> Result<(), CodegenError> {
+        let mut code = String::new();
+        doc(&mut code, "", schema.description.as_deref().unwrap_or_default());
+        code.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]\n");
+        writeln!(code, "pub enum {name} {{").unwrap();
+        let mut variants = BTreeSet::new();
+        for value in &schema.enum_ {
+            let value = match value {
+                Value::String(value) => value,
+                // `null` of nullable enums is `None` of `Option`.
+                Value::Null => continue,
+                other => return Err(unsupported(location, format!("enum value `{other}`"))),
+            };
+            let mut variant = value.to_upper_camel_case();
+            if !variant.starts_with(|char: char| char.is_alphabetic()) {
+                variant.insert(0, 'V');
+            }
+            if !variants.insert(variant.clone()) {
+                return Err(unsupported(
+                    location,
+                    format!("enum variant `{variant}` is generated twice"),
+                ));
+            }
+            if variant != *value {
+                writeln!(code, "    #[serde(rename = {value:?})]").unwrap();
+            }
+            writeln!(code, "    {variant},").unwrap();
+        }
+        code.push_str("}\n");
+        self.define(name, code, location)
+    }
+
+    /// Returns the method name and code.
+    fn operation(
+        &mut self,
+        path: &str,
+        item: &PathItem,
+        method: &str,
+        operation: &Operation,
+        location: &str,
+    ) -> Result<(String, String), CodegenError> {
+        let name = match &operation.operation_id {
+            Some(id) => field_name(id),
+            None => field_name(&format!("{method} {path}")),
+        };
+        let prefix = type_name(&name);
+
+        // Parameters of the operation override ones of the path with the same name and location.
+        let mut parameters: Vec<Parameter> = Vec::new();
+        for parameter in item.parameters.iter().chain(&operation.parameters) {
+            let parameter = self.resolve_parameter(parameter, location)?;
+            parameters.retain(|p| (&p.name, p.location) != (&parameter.name, parameter.location));
+            parameters.push(parameter);
+        }
+        let (mut path_params, mut query_params) = (Vec::new(), Vec::new());
+        for parameter in parameters {
+            match parameter.location {
+                Some(ParameterLocation::Path) => path_params.push(parameter),
+                Some(ParameterLocation::Query) => query_params.push(parameter),
+                Some(other) => {
+                    let other = format!("{other:?}").to_lowercase();
+                    let reason = format!("{other} parameter `{}`", parameter.name);
+                    return Err(unsupported(location, reason));
+                }
+                None => {
+                    return Err(unsupported(
+                        location,
+                        format!("parameter `{}` without `in`", parameter.name),
+                    ));
+                }
+            }
+        }
+        let body = match &operation.request_body {
+            None => None,
+            Some(_) if method == "GET" => {
+                let reason =
+                    "request body of GET operation, as GET requests are sent without a body";
+                return Err(unsupported(location, reason));
+            }
+            Some(body) => {
+                let Some(content) = json_content(&body.content) else {
+                    let media_types =
+                        body.content.keys().map(|key| format!("`{key}`")).collect::<Vec<_>>();
+                    let reason = format!(
+                        "request body of {}, only JSON is supported",
+                        media_types.join(", ")
+                    );
+                    return Err(unsupported(location, reason));
+                };
+                Some((content.schema.clone().unwrap_or_default(), body.required))
+            }
+        };
+
+        let response = match operation.responses.iter().find(|(status, _)| status.starts_with('2'))
+        {
+            None => ResponseKind::Raw,
+            Some((_, response)) if response.content.is_empty() => ResponseKind::Empty,
+            Some((_, response)) => match json_content(&response.content) {
+                Some(content) => {
+                    let schema = content.schema.clone().unwrap_or_default();
+                    ResponseKind::Json(self.rust_type(
+                        &schema,
+                        &format!("{prefix}Response"),
+                        location,
+                    )?)
+                }
+                None => ResponseKind::Raw,
+            },
+        };
+
+        let (argument, request) = if !path_params.is_empty() || !query_params.is_empty() {
+            let request = format!("{prefix}Request");
+            let mut code = format!("/// Request of [`{}::{name}`].\n", self.client);
+            code.push_str("#[derive(Debug, Clone, PartialEq, serde::Serialize)]\n");
+            writeln!(code, "pub struct {request} {{").unwrap();
+            for parameter in path_params.iter().chain(&query_params) {
+                let hint = format!("{prefix}{}", type_name(&parameter.name));
+                let type_ = match &parameter.schema {
+                    Some(schema) => self.rust_type(schema, &hint, location)?,
+                    None => "String".into(),
+                };
+                let required =
+                    parameter.required || parameter.location == Some(ParameterLocation::Path);
+                field(
+                    &mut code,
+                    &parameter.name,
+                    parameter.description.as_deref(),
+                    type_,
+                    required,
+                    false,
+                );
+            }
+            // Serializes to the path and query params, the body is sent on its own.
+            if let Some((schema, required)) = &body {
+                let type_ = self.rust_type(schema, &format!("{prefix}Body"), location)?;
+                let type_ = if *required { type_ } else { format!("Option<{type_}>") };
+                writeln!(code, "    #[serde(skip)]\n    pub body: {type_},").unwrap();
+            }
+            code.push_str("}\n");
+            self.define(&request, code, location)?;
+            (format!(", request: &{request}"), "request")
+        } else if let Some((schema, _)) = &body {
+            let type_ = self.rust_type(schema, &format!("{prefix}Body"), location)?;
+            (format!(", body: &{type_}"), "body")
+        } else {
+            (String::new(), "()")
+        };
+
+        let api = self.api;
+        let mut code = String::new();
+        let summary = [operation.summary.as_deref(), operation.description.as_deref()];
+        for text in summary.into_iter().flatten() {
+            doc(&mut code, "    ", text);
+            code.push_str("    ///\n");
+        }
+        writeln!(code, "    /// `{method} {path}`").unwrap();
+        let returns = match &response {
+            ResponseKind::Json(type_) => type_.clone(),
+            ResponseKind::Empty => "()".into(),
+            ResponseKind::Raw => format!("{api}::body::ApiResponse"),
+        };
+        writeln!(
+            code,
+            "    pub async fn {name}(&self{argument}) -> Result<{returns}, {api}::ApiClientError> {{"
+        )
+        .unwrap();
+
+        let template = path.trim_start_matches('/');
+        let (path, query) = if path_params.is_empty() {
+            (format!("{template:?}"), request)
+        } else {
+            // The rest of the fields are the query params.
+            let query = if query_params.is_empty() { "_" } else { "query" };
+            writeln!(
+                code,
+                "        let (path, {query}) = {api}::endpoint::interpolate_path({template:?}, request)?;"
+            )
+            .unwrap();
+            ("&path".into(), "&query")
+        };
+        let query = (!query_params.is_empty()).then(|| format!("{api}::body::Query({query})"));
+        let body = body.map(|(_, required)| match (request, required) {
+            ("body", _) => format!("{api}::body::Json(body)"),
+            (_, true) => format!("{api}::body::Json(&request.body)"),
+            (_, false) => format!("request.body.as_ref().map({api}::body::Json)"),
+        });
+        let body = match (query, body) {
+            (Some(query), Some(body)) => format!("({query}, {body})"),
+            (Some(encoding), None) | (None, Some(encoding)) => encoding,
+            (None, None) => "()".into(),
+        };
+        let method = format!("reqwest_middleware::reqwest::Method::{method}");
+        let send = format!("self.client.send({method}, {path}, {body}).await");
+        match response {
+            ResponseKind::Json(_) => writeln!(code, "        {send}?.json().await"),
+            ResponseKind::Empty => writeln!(code, "        {send}?;\n        Ok(())"),
+            ResponseKind::Raw => writeln!(code, "        {send}"),
+        }
+        .unwrap();
+        code.push_str("    }\n");
+        Ok((name, code))
+    }
+}
+
+fn field(
+    code: &mut String,
+    key: &str,
+    description: Option<&str>,
+    type_: String,
+    required: bool,
+    nullable: bool,
+) {
+    let name = field_name(key);
+    let mut serde = Vec::new();
+    if name != key {
+        serde.push(format!("rename = {key:?}"));
+    }
+    if !required {
+        serde.push("default, skip_serializing_if = \"Option::is_none\"".to_owned());
+    }
+    let type_ = if required && !nullable { type_ } else { format!("Option<{type_}>") };
+
+    doc(code, "    ", description.unwrap_or_default());
+    if !serde.is_empty() {
+        writeln!(code, "    #[serde({})]", serde.join(", ")).unwrap();
+    }
+    writeln!(code, "    pub {name}: {type_},").unwrap();
+}
@@ -0,0 +1,195 @@
+//! Generates typed `my_practices::api_client` code from OpenAPI 3 documents in JSON or YAML.
+//!
+//! Every schema in `components/schemas` becomes a struct, enum or type alias with serde derives,
+//! and every operation becomes a method of the generated client, which calls `ApiClient::send`
+//! with query params and JSON body. The generated code refers to `reqwest_middleware` and `serde`,
+//! so they must be dependencies of the crate including it.
+//!
+//! Meant to be run from a build script, the same way as `prost_build`:
+//!
+//! ```no_run
+//! // In `fn main` of build.rs.
+//! my_api_client_codegen::Generator::default().compile("api/petstore.yaml").unwrap();
+//! ```
+//!
+//! And then included with `include!(concat!(env!("OUT_DIR"), "/petstore.rs"));`.
+
+use std::path::{Path, PathBuf};
+
+mod emit;
+mod openapi;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CodegenError {
+    #[error("Failed to read '{}': {source}", path.display())]
+    Read { path: PathBuf, source: std::io::Error },
+
+    #[error("Failed to write '{}': {source}", path.display())]
+    Write { path: PathBuf, source: std::io::Error },
+
+    #[error("OUT_DIR is not set, `compile` must be run from a build script")]
+    NoOutDir,
+
+    #[error("Invalid JSON: {0}")]
+    Json(serde_json::Error),
+
+    #[error("Invalid YAML: {0}")]
+    Yaml(serde_yaml_ng::Error),
+
+    #[error("Invalid OpenAPI document at `{path}`: {reason}")]
+    Document { path: String, reason: String },
+
+    #[error("Unsupported {location}: {reason}")]
+    Unsupported { location: String, reason: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct Generator {
+    /// Name of the generated client struct, by default taken from the document title.
+    pub client_name: Option<String>,
+    /// Path of the `api_client` module in the generated code.
+    pub api_client_path: String,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self { client_name: None, api_client_path: "my_practices::api_client".into() }
+    }
+}
+
+impl Generator {
+    pub fn with_client_name(mut self, name: impl Into<String>) -> Self {
+        self.client_name = Some(name.into());
+        self
+    }
+
+    pub fn with_api_client_path(mut self, path: impl Into<String>) -> Self {
+        self.api_client_path = path.into();
+        self
+    }
+
+    /// Generates code from a JSON or YAML `document`.
+    pub fn generate(&self, document: &str) -> Result<String, CodegenError> {
+        let value: serde_json::Value = if document.trim_start().starts_with('{') {
+            serde_json::from_str(document).map_err(CodegenError::Json)?
+        } else {
+            serde_yaml_ng::from_str(document).map_err(CodegenError::Yaml)?
+        };
+        let document = serde_path_to_error::deserialize(value).map_err(|err| {
+            CodegenError::Document { path: err.path().to_string(), reason: err.inner().to_string() }
+        })?;
+        emit::generate(self, &document)
+    }
+
+    /// Generates code from the document at `path` into `$OUT_DIR/<file stem>.rs`
+    /// and returns the path of the generated file.
+    /// Also asks Cargo to rerun the build script when the document changes.
+    pub fn compile(&self, path: impl AsRef<Path>) -> Result<PathBuf, CodegenError> {
+        let path = path.as_ref();
+        let out_dir = std::env::var_os("OUT_DIR").ok_or(CodegenError::NoOutDir)?;
+        let document = std::fs::read_to_string(path)
+            .map_err(|source| CodegenError::Read { path: path.to_owned(), source })?;
+
+        let code = self.generate(&document)?;
+
+        let stem = path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy();
+        let out_path = Path::new(&out_dir).join(format!("{stem}.rs"));
+        std::fs::write(&out_path, code)
+            .map_err(|source| CodegenError::Write { path: out_path.clone(), source })?;
+        println!("cargo::rerun-if-changed={}", path.display());
+        Ok(out_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::{expect, expect_file};
+
+    use super::*;
+
+    const PETSTORE: &str = include_str!("../testdata/petstore.yaml");
+
+    #[test]
+    fn generates_petstore() {
+        let code = Generator::default().generate(PETSTORE).unwrap();
+        expect_file!["../testdata/petstore.rs"].assert_eq(&code);
+    }
+
+    #[test]
+    fn json_and_yaml_documents_are_the_same() {
+        let value: serde_json::Value = serde_yaml_ng::from_str(PETSTORE).unwrap();
+        let json = serde_json::to_string(&value).unwrap();
+        let generator = Generator::default().with_client_name("Pets");
+
+        assert_eq!(generator.generate(&json).unwrap(), generator.generate(PETSTORE).unwrap());
+    }
+
+    #[test]
+    fn reads_yaml_anchors_and_multi_line_scalars() {
+        let json = r#"{
+            "openapi": "3.0.3",
+            "info": {"title": "Pets"},
+            "paths": {},
+            "components": {"schemas": {
+                "Pet": {
+                    "description": "A pet with a long description.",
+                    "type": "object",
+                    "properties": {"name": {"type": "string"}, "age": {"type": "integer"}}
+                },
+                "Owner": {"type": "object", "properties": {"name": {"type": "string"}}}
+            }}
+        }"#;
+        let yaml = r#"
+openapi: 3.0.3
+info: {
+  title: Pets
+}
+paths: {}
+components:
+  schemas:
+    Pet:
+      description: "A pet with
+        a long description."
+      type: object
+      properties:
+        name: &name
+          type: string
+        age: {type:
+          integer}
+    Owner:
+      type: object
+      properties:
+        name: *name
+"#;
+        let generator = Generator::default();
+        assert_eq!(generator.generate(yaml).unwrap(), generator.generate(json).unwrap());
+
+        let err = generator.generate("openapi: [3").unwrap_err();
+        assert!(matches!(err, CodegenError::Yaml(_)), "{err}");
+    }
+
+    #[test]
+    fn reports_unsupported_and_invalid_documents() {
+        let document =
+            |paths: &str| format!("openapi: 3.0.3\ninfo:\n  title: Test\npaths:\n{paths}");
+        let errors = [
+            "  /a:\n    get:\n      parameters:\n        - {name: key, in: header}",
+            "  /a:\n    get:\n      requestBody:\n        content:\n          application/json: {}",
+            "  /a:\n    post:\n      requestBody:\n        content:\n          text/plain: {}",
+            "  /a:\n    get:\n      responses:\n        '200':\n          content:\n            application/json:\n              schema: {$ref: '#/components/schemas/Missing'}",
+            "  /a:\n    get:\n      parameters:\n        - {name: id, in: body}",
+        ]
+        .map(|paths| Generator::default().generate(&document(paths)).unwrap_err().to_string());
+
+        expect![[r#"
+            [
+                "Unsupported operation `GET /a`: header parameter `key`",
+                "Unsupported operation `GET /a`: request body of GET operation, as GET requests are sent without a body",
+                "Unsupported operation `POST /a`: request body of `text/plain`, only JSON is supported",
+                "Unsupported operation `GET /a`: unknown reference `#/components/schemas/Missing`",
+                "Invalid OpenAPI document at `paths./a.get.parameters[0].in`: unknown variant `body`, expected one of `path`, `query`, `header`, `cookie`",
+            ]
+        "#]]
+        .assert_debug_eq(&errors);
+    }
+}
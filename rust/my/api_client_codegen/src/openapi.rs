@@ -0,0 +1,170 @@
+//! Part of the OpenAPI 3 document model used by the generator, unknown fields are ignored.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Document {
+    pub info: Info,
+    #[serde(default)]
+    pub paths: BTreeMap<String, PathItem>,
+    #[serde(default)]
+    pub components: Components,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Info {
+    pub title: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct Components {
+    #[serde(default)]
+    pub schemas: BTreeMap<String, Schema>,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, Parameter>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PathItem {
+    /// Shared by all operations of the path.
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+    pub get: Option<Operation>,
+    pub put: Option<Operation>,
+    pub post: Option<Operation>,
+    pub delete: Option<Operation>,
+    pub patch: Option<Operation>,
+}
+
+impl PathItem {
+    pub fn operations(&self) -> impl Iterator<Item = (&'static str, &Operation)> {
+        [
+            ("GET", &self.get),
+            ("PUT", &self.put),
+            ("POST", &self.post),
+            ("DELETE", &self.delete),
+            ("PATCH", &self.patch),
+        ]
+        .into_iter()
+        .filter_map(|(method, operation)| Some((method, operation.as_ref()?)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Operation {
+    pub operation_id: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+    pub request_body: Option<RequestBody>,
+    #[serde(default)]
+    pub responses: BTreeMap<String, Response>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Parameter {
+    /// `#/components/parameters/...`, then the rest of fields are empty.
+    #[serde(rename = "$ref")]
+    pub ref_: Option<String>,
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "in")]
+    pub location: Option<ParameterLocation>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+    pub schema: Option<Schema>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ParameterLocation {
+    Path,
+    Query,
+    Header,
+    Cookie,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct RequestBody {
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub content: BTreeMap<String, MediaType>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Response {
+    #[serde(default)]
+    pub content: BTreeMap<String, MediaType>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MediaType {
+    pub schema: Option<Schema>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Schema {
+    #[serde(rename = "$ref")]
+    pub ref_: Option<String>,
+    #[serde(rename = "type")]
+    pub type_: Option<SchemaType>,
+    pub format: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub properties: BTreeMap<String, Schema>,
+    #[serde(default)]
+    pub required: Vec<String>,
+    pub items: Option<Box<Schema>>,
+    #[serde(rename = "enum", default)]
+    pub enum_: Vec<Value>,
+    pub additional_properties: Option<AdditionalProperties>,
+    /// OpenAPI 3.0 way, 3.1 uses `type: [..., "null"]` instead.
+    #[serde(default)]
+    pub nullable: bool,
+    #[serde(default)]
+    pub all_of: Vec<Schema>,
+    #[serde(default)]
+    pub one_of: Vec<Schema>,
+    #[serde(default)]
+    pub any_of: Vec<Schema>,
+}
+
+impl Schema {
+    /// Type other than `null`, if any.
+    pub fn type_name(&self) -> Option<&str> {
+        match self.type_.as_ref()? {
+            SchemaType::One(name) => Some(name),
+            SchemaType::Many(names) => {
+                names.iter().map(String::as_str).find(|name| *name != "null")
+            }
+        }
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        self.nullable
+            || matches!(&self.type_, Some(SchemaType::Many(names)) if names.iter().any(|name| name == "null"))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum SchemaType {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum AdditionalProperties {
+    Allowed(#[expect(dead_code, reason = "free-form values are JSON either way")] bool),
+    Schema(Box<Schema>),
+}
@@ -0,0 +1,180 @@
+// @generated by `my_api_client_codegen` from "Petstore", do not edit.
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Error {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NewPet {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// Kind of the pet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum PetType {
+    #[serde(rename = "cat")]
+    Cat,
+    #[serde(rename = "dog")]
+    Dog,
+    #[serde(rename = "1-eyed-fish")]
+    V1EyedFish,
+}
+
+/// A pet in the store.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Pet {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<serde_json::Value>,
+    pub id: i64,
+    pub name: String,
+    /// Kind of the pet.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<PetType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+}
+
+pub type PetIds = Vec<i64>;
+
+/// Combinators are not supported and left as JSON.
+pub type PetOrError = serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CreateOwnerBodyAddress {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zip: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CreateOwnerBody {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<CreateOwnerBodyAddress>,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum ListPetsStatus {
+    #[serde(rename = "available")]
+    Available,
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "sold")]
+    Sold,
+}
+
+/// Request of [`PetstoreClient::list_pets`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ListPetsRequest {
+    /// How many items to return at one time (max 100).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i32>,
+    /// Only pets with all these tags.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ListPetsStatus>,
+}
+
+/// Request of [`PetstoreClient::show_pet_by_id`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ShowPetByIdRequest {
+    /// The id of the pet.
+    #[serde(rename = "petId")]
+    pub pet_id: i64,
+}
+
+/// Request of [`PetstoreClient::update_pet`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct UpdatePetRequest {
+    /// The id of the pet.
+    #[serde(rename = "petId")]
+    pub pet_id: i64,
+    /// Whether to notify the owner.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notify: Option<bool>,
+    #[serde(skip)]
+    pub body: NewPet,
+}
+
+/// Request of [`PetstoreClient::delete_pets_pet_id`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DeletePetsPetIdRequest {
+    /// The id of the pet.
+    #[serde(rename = "petId")]
+    pub pet_id: i64,
+}
+
+/// Request of [`PetstoreClient::get_pet_photo`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct GetPetPhotoRequest {
+    #[serde(rename = "petId")]
+    pub pet_id: i64,
+}
+
+/// Pets and their owners.
+///
+/// Used for golden tests of the generator.
+pub struct PetstoreClient {
+    pub client: my_practices::api_client::ApiClient,
+}
+
+impl PetstoreClient {
+    pub fn new(client: my_practices::api_client::ApiClient) -> Self {
+        Self { client }
+    }
+
+    /// `POST /owners`
+    pub async fn create_owner(&self, body: &CreateOwnerBody) -> Result<std::collections::BTreeMap<String, i32>, my_practices::api_client::ApiClientError> {
+        self.client.send(reqwest_middleware::reqwest::Method::POST, "owners", my_practices::api_client::body::Json(body)).await?.json().await
+    }
+
+    /// List all pets
+    ///
+    /// `GET /pets`
+    pub async fn list_pets(&self, request: &ListPetsRequest) -> Result<Vec<Pet>, my_practices::api_client::ApiClientError> {
+        self.client.send(reqwest_middleware::reqwest::Method::GET, "pets", my_practices::api_client::body::Query(request)).await?.json().await
+    }
+
+    /// Create a pet
+    ///
+    /// `POST /pets`
+    pub async fn create_pet(&self, body: &NewPet) -> Result<Pet, my_practices::api_client::ApiClientError> {
+        self.client.send(reqwest_middleware::reqwest::Method::POST, "pets", my_practices::api_client::body::Json(body)).await?.json().await
+    }
+
+    /// `GET /pets/{petId}`
+    pub async fn show_pet_by_id(&self, request: &ShowPetByIdRequest) -> Result<Pet, my_practices::api_client::ApiClientError> {
+        let (path, _) = my_practices::api_client::endpoint::interpolate_path("pets/{petId}", request)?;
+        self.client.send(reqwest_middleware::reqwest::Method::GET, &path, ()).await?.json().await
+    }
+
+    /// Replaces all fields of the pet.
+    ///
+    /// `PUT /pets/{petId}`
+    pub async fn update_pet(&self, request: &UpdatePetRequest) -> Result<(), my_practices::api_client::ApiClientError> {
+        let (path, query) = my_practices::api_client::endpoint::interpolate_path("pets/{petId}", request)?;
+        self.client.send(reqwest_middleware::reqwest::Method::PUT, &path, (my_practices::api_client::body::Query(&query), my_practices::api_client::body::Json(&request.body))).await?;
+        Ok(())
+    }
+
+    /// `DELETE /pets/{petId}`
+    pub async fn delete_pets_pet_id(&self, request: &DeletePetsPetIdRequest) -> Result<(), my_practices::api_client::ApiClientError> {
+        let (path, _) = my_practices::api_client::endpoint::interpolate_path("pets/{petId}", request)?;
+        self.client.send(reqwest_middleware::reqwest::Method::DELETE, &path, ()).await?;
+        Ok(())
+    }
+
+    /// `GET /pets/{petId}/photo`
+    pub async fn get_pet_photo(&self, request: &GetPetPhotoRequest) -> Result<my_practices::api_client::body::ApiResponse, my_practices::api_client::ApiClientError> {
+        let (path, _) = my_practices::api_client::endpoint::interpolate_path("pets/{petId}/photo", request)?;
+        self.client.send(reqwest_middleware::reqwest::Method::GET, &path, ()).await
+    }
+}
@@ -471,3 +471,109 @@ fn main() {
         // Location: 484
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+    use my_practices::assert_chain;
+
+    const UNSET_ENV: &str = "MY_CRATES_USAGE_UNSET_ENV";
+
+    #[test]
+    fn transparent_anyhow_shows_only_inner_message() {
+        #[derive(thiserror::Error, Debug)]
+        enum MyError {
+            #[error(transparent)]
+            Other(#[from] anyhow::Error),
+        }
+
+        fn read_env_var(key: &str) -> Result<u8, MyError> {
+            let var = std::env::var(key).map_err(anyhow::Error::from)?;
+            Ok(var.parse().map_err(anyhow::Error::from)?)
+        }
+
+        // `VarError` itself is not in the chain, only its message is left.
+        let err = read_env_var(UNSET_ENV).unwrap_err();
+        assert_chain!(err, [is::<MyError>()]);
+        assert_chain!(err, expect!["0: environment variable not found [Other]"]);
+    }
+
+    #[test]
+    fn boxed_source_is_skipped() {
+        #[derive(Debug)]
+        enum MyError {
+            Other(Box<dyn std::error::Error>, std::panic::Location<'static>),
+        }
+
+        impl std::fmt::Display for MyError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    MyError::Other(err, ..) => std::fmt::Display::fmt(err, f),
+                }
+            }
+        }
+
+        impl std::error::Error for MyError {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                match self {
+                    MyError::Other(err, ..) => std::error::Error::source(err.as_ref()),
+                }
+            }
+        }
+
+        impl From<std::io::Error> for MyError {
+            #[track_caller]
+            fn from(value: std::io::Error) -> Self {
+                Self::Other(value.into(), *std::panic::Location::caller())
+            }
+        }
+
+        fn read(path: &str) -> Result<Vec<u8>, MyError> {
+            Ok(std::fs::read(path)?)
+        }
+
+        // Forwarding `source()` of the boxed error skips the boxed error itself,
+        // and the location is not in the chain.
+        let err = read("missing-file").unwrap_err();
+        assert_chain!(err, [contains("No such file or directory")]);
+        let MyError::Other(_, location) = err;
+        assert_eq!(location.file(), file!());
+    }
+
+    #[test]
+    fn anyhow_in_the_middle_forwards_everything() {
+        #[derive(Debug, thiserror::Error)]
+        enum ExternalError {
+            #[error(transparent)]
+            Io(#[from] anyhow::Error),
+
+            #[error("Other error")]
+            Other,
+        }
+
+        #[derive(Debug, thiserror::Error)]
+        enum MyError {
+            #[error(transparent)]
+            External(#[from] anyhow::Error),
+        }
+
+        impl From<ExternalError> for MyError {
+            fn from(value: ExternalError) -> Self {
+                Self::External(value.into())
+            }
+        }
+
+        fn read(path: &str) -> Result<Vec<u8>, MyError> {
+            let buf =
+                std::fs::read(path).map_err(|err| ExternalError::from(anyhow::Error::from(err)))?;
+            Ok(buf)
+        }
+
+        fn other() -> Result<(), MyError> {
+            Err(ExternalError::Other)?
+        }
+
+        assert_chain!(read("missing-file").unwrap_err(), [contains("No such file or directory")]);
+        assert_chain!(other().unwrap_err(), [displays("Other error")]);
+    }
+}
@@ -178,3 +178,117 @@ fn main() {
         print_err!(MissingPasswordSnafu.build());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+    use my_practices::assert_chain;
+    use snafu::prelude::*;
+    use std::{fs, io, path::PathBuf};
+
+    #[test]
+    fn context_selectors_keep_source() {
+        #[derive(Debug, Snafu)]
+        enum MyError {
+            #[snafu(display("Unable to read configuration from '{}'", path.display()))]
+            ReadConfiguration { path: PathBuf, source: io::Error },
+        }
+
+        let path = "missing-config.toml";
+        let err = fs::read_to_string(path).context(ReadConfigurationSnafu { path }).unwrap_err();
+
+        assert_chain!(
+            err,
+            [
+                displays("Unable to read configuration from 'missing-config.toml'"),
+                is::<io::Error>()
+            ]
+        );
+    }
+
+    #[test]
+    fn whatever_keeps_source_but_not_type() {
+        /// Takes the value of `std::env::var`, so the test doesn't have to set env vars.
+        fn read_env_var(var: Result<String, std::env::VarError>) -> Result<u8, snafu::Whatever> {
+            let var = var.whatever_context("cannot read env var")?;
+            let num: u8 =
+                var.parse().with_whatever_context(|e| format!("cannot parse var: {e}"))?;
+            Ok(num)
+        }
+
+        let err = read_env_var(std::env::var("MY_CRATES_USAGE_UNSET_ENV")).unwrap_err();
+        assert_chain!(err, [displays("cannot read env var"), is::<std::env::VarError>()]);
+
+        let err = read_env_var(Ok("256".into())).unwrap_err();
+        assert_chain!(
+            err,
+            expect![[r#"
+                0: cannot parse var: number too large to fit in target type [Whatever]
+                1: number too large to fit in target type [ParseIntError]"#]]
+        );
+    }
+
+    #[test]
+    fn ensure_and_whatever_in_enum() {
+        #[derive(Debug, Snafu)]
+        enum Error {
+            #[snafu(display("ID may not be less than 10, but it was {id}"))]
+            InvalidId { id: u16 },
+
+            #[snafu(whatever, display("{message}"))]
+            Whatever { message: String },
+        }
+
+        fn is_valid_id(id: u16) -> Result<(), Error> {
+            ensure!(id >= 10, InvalidIdSnafu { id });
+            whatever!("Just kidding... this function always fails!");
+        }
+
+        assert_chain!(
+            is_valid_id(5).unwrap_err(),
+            [displays("ID may not be less than 10, but it was 5")]
+        );
+        assert_chain!(is_valid_id(10).unwrap_err(), [contains("Just kidding")]);
+    }
+
+    #[test]
+    fn transparent_hides_itself() {
+        #[derive(Debug, Snafu)]
+        enum MyError {
+            #[snafu(transparent)]
+            Io {
+                source: std::io::Error,
+                #[snafu(implicit)]
+                location: snafu::Location,
+            },
+        }
+
+        fn read(path: &str) -> Result<Vec<u8>, MyError> {
+            Ok(std::fs::read(path)?)
+        }
+
+        // `MyError` has the same `Display` and `source()` as `io::Error`,
+        // so the location is not in the chain.
+        let err = read("missing-file").unwrap_err();
+        assert_chain!(err, [contains("No such file or directory")]);
+        let MyError::Io { location, .. } = err;
+        assert_eq!(location.file, file!());
+    }
+
+    #[test]
+    fn doc_comments_as_display() {
+        #[derive(Debug, Snafu)]
+        enum Error {
+            /// No user available.
+            /// You may need to specify one.
+            MissingUser,
+            MissingPassword,
+        }
+
+        assert_chain!(
+            MissingUserSnafu.build(),
+            [displays("No user available. You may need to specify one.")]
+        );
+        assert_chain!(MissingPasswordSnafu.build(), [displays("MissingPassword")]);
+    }
+}
@@ -0,0 +1,79 @@
+use std::panic::Location;
+
+use my_practices::stack_error::{StackError, StackResultExt, bridge::StackHandler};
+use tracing::instrument;
+use tracing_subscriber::prelude::*;
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .finish()
+        .with(tracing_error::ErrorLayer::default())
+        .try_init()
+        .unwrap();
+
+    // Only one eyre hook can be installed, run with `STACK_HANDLER=1` to use our own.
+    if std::env::var_os("STACK_HANDLER").is_some() {
+        StackHandler::install().unwrap();
+    } else {
+        color_eyre::install().unwrap();
+    }
+
+    //
+
+    #[derive(Debug, thiserror::Error, StackError)]
+    enum InternalError {
+        #[error("IO error")]
+        Io(
+            #[source]
+            #[stack(from)]
+            std::io::Error,
+            #[stack(location)] Location<'static>,
+        ),
+    }
+
+    #[derive(Debug, thiserror::Error, StackError)]
+    enum MyError {
+        #[error("Failed to read config")]
+        Internal(
+            #[source]
+            #[stack(next, from)]
+            InternalError,
+            #[stack(location)] Location<'static>,
+        ),
+    }
+
+    fn read_file(path: &std::path::Path) -> Result<Vec<u8>, InternalError> {
+        Ok(std::fs::read(path)?)
+    }
+
+    fn read_config(path: &std::path::Path) -> Result<Vec<u8>, MyError> {
+        Ok(read_file(path)?)
+    }
+
+    //
+
+    // `StackError` into `eyre::Report`, every layer keeps its location
+    // and the span trace is captured on conversion.
+
+    #[instrument]
+    fn load_eyre(path: &std::path::Path) -> color_eyre::Result<Vec<u8>> {
+        read_config(path).into_eyre()
+    }
+
+    if let Err(err) = load_eyre("config.toml".as_ref()) {
+        eprintln!("Error: {err:?}");
+    }
+
+    //
+
+    // And into `anyhow::Error`, where locations are in the chain of contexts.
+
+    fn load_anyhow(path: &std::path::Path) -> anyhow::Result<Vec<u8>> {
+        read_config(path).into_anyhow()
+    }
+
+    if let Err(err) = load_anyhow("config.toml".as_ref()) {
+        eprintln!("Error: {err:?}");
+    }
+}
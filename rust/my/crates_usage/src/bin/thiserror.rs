@@ -480,3 +480,81 @@ fn main() {
         //              at ./my/crates_usage/src/bin/thiserror.rs:470:19
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+    use my_practices::assert_chain;
+
+    #[derive(Debug, thiserror::Error)]
+    enum Inner {
+        #[error("inner: {0}")]
+        SomeErr(String),
+    }
+
+    #[test]
+    fn from_adds_source_to_chain() {
+        #[derive(Debug, thiserror::Error)]
+        enum Outer {
+            #[error("outer")]
+            SomeErr(#[from] Inner),
+        }
+
+        let err = Outer::from(Inner::SomeErr("some error".into()));
+        assert_chain!(
+            err,
+            expect![[r#"
+                0: outer [SomeErr]
+                1: inner: some error [SomeErr]"#]]
+        );
+    }
+
+    #[test]
+    fn optional_source_is_only_in_chain_when_set() {
+        #[derive(Debug, thiserror::Error)]
+        enum Outer {
+            #[error("outer")]
+            SomeErr(#[source] Option<Inner>),
+        }
+
+        let err = Outer::SomeErr(Some(Inner::SomeErr("some error".into())));
+        assert_chain!(err, [displays("outer"), is::<Inner>()]);
+        assert_chain!(Outer::SomeErr(None), [displays("outer")]);
+    }
+
+    #[test]
+    fn transparent_forwards_display_and_source() {
+        #[derive(Debug, thiserror::Error)]
+        enum Outer {
+            #[error(transparent)]
+            Io(#[from] std::io::Error),
+        }
+
+        // The path of the file is lost.
+        let err = Outer::from(std::fs::read("missing-file").unwrap_err());
+        assert_chain!(err, [contains("No such file or directory")]);
+    }
+
+    #[test]
+    fn backtrace_is_provided_from_source() {
+        #[derive(Debug, thiserror::Error)]
+        enum External {
+            #[error("Io error")]
+            Io(#[from] std::io::Error, std::backtrace::Backtrace),
+        }
+
+        #[derive(Debug, thiserror::Error)]
+        enum Outer {
+            #[error(transparent)]
+            External {
+                #[from]
+                #[backtrace]
+                source: External,
+            },
+        }
+
+        let err = Outer::from(External::from(std::fs::read("missing-file").unwrap_err()));
+        assert_chain!(err, [displays("Io error"), is::<std::io::Error>()]);
+        assert!(std::error::request_ref::<std::backtrace::Backtrace>(&err).is_some());
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    my_api_client_codegen::Generator::default().compile("src/bin/api_client/httpbin.yaml").unwrap();
+}
@@ -0,0 +1,432 @@
+//! Thin typed wrapper around [`ClientWithMiddleware`] for JSON APIs.
+//!
+//! Describe each API call as an [`Endpoint`] and send it with [`ApiClient::call`].
+
+use reqwest_middleware::{ClientWithMiddleware, reqwest};
+use url::Url;
+
+use auth::AuthError;
+use body::{ApiResponse, Json, Query, RequestBody};
+pub use endpoint::Endpoint;
+use error_body::{ErrorBody, ErrorDecoder, RateLimit, StatusClass};
+pub use pagination::Pagination;
+use retry::{RetryAttempt, RetryAttempts, RetryError, RetryMode};
+
+pub mod auth;
+pub mod body;
+pub mod cache;
+pub mod cassette;
+pub mod endpoint;
+pub mod error_body;
+pub mod pagination;
+pub mod rate_limiter;
+pub mod retry;
+
+pub struct ApiClient {
+    client: ClientWithMiddleware,
+    base_url: Url,
+    error_decoders: Vec<(StatusClass, ErrorDecoder)>,
+}
+
+impl ApiClient {
+    pub fn new(client: ClientWithMiddleware, base_url: Url) -> Self {
+        Self { client, base_url, error_decoders: Vec::new() }
+    }
+
+    /// Decodes JSON bodies of error responses with `class` status as `T`,
+    /// available with [`ErrorBody::downcast_ref`].
+    /// Decoders are tried in registration order, before the built-in RFC 7807 one.
+    pub fn with_error_body<T>(self, class: StatusClass) -> Self
+    where
+        T: serde::de::DeserializeOwned + error_body::CustomErrorBody,
+    {
+        self.with_error_decoder(class, error_body::json_decoder::<T>())
+    }
+
+    /// Same as [`ApiClient::with_error_body`], but for non-JSON or otherwise custom formats.
+    pub fn with_error_decoder(mut self, class: StatusClass, decoder: ErrorDecoder) -> Self {
+        self.error_decoders.push((class, decoder));
+        self
+    }
+
+    /// Sends `endpoint` and deserializes its [`Endpoint::Response`].
+    pub async fn call<E: Endpoint>(&self, endpoint: E) -> Result<E::Response, ApiClientError> {
+        // Avoid the `serde_json::Value` round trip if there is nothing to interpolate.
+        if !E::PATH.contains('{') {
+            return self
+                .send_request_with_retry(E::METHOD, E::PATH, &endpoint, E::RETRY_MODE)
+                .await;
+        }
+
+        let (path, request) = endpoint::interpolate_path(E::PATH, &endpoint)?;
+        self.send_request_with_retry(E::METHOD, &path, &request, E::RETRY_MODE).await
+    }
+
+    /// Low-level version of [`ApiClient::call`] with an already built relative `url`.
+    pub async fn send_request<Request, Response>(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        request: &Request,
+    ) -> Result<Response, ApiClientError>
+    where
+        Request: serde::Serialize + ?Sized,
+        Response: serde::de::DeserializeOwned,
+    {
+        self.send_request_with_retry(method, url, request, RetryMode::default()).await
+    }
+
+    /// Same as [`ApiClient::send_request`], but overrides which requests [`retry::RetryMiddleware`] may retry.
+    /// E.g. [`RetryMode::Always`] opts in a non-idempotent POST.
+    pub async fn send_request_with_retry<Request, Response>(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        request: &Request,
+        retry_mode: RetryMode,
+    ) -> Result<Response, ApiClientError>
+    where
+        Request: serde::Serialize + ?Sized,
+        Response: serde::de::DeserializeOwned,
+    {
+        let response = if method == reqwest::Method::GET {
+            // Conditionally attach `request` as query params if GET.
+            self.send_with_retry(method, url, Query(request), retry_mode).await?
+        } else {
+            // For POST/PUT/etc, serialize `request` into the JSON body.
+            self.send_with_retry(method, url, Json(request), retry_mode).await?
+        };
+        response.json().await
+    }
+
+    async fn send_with_retry<B: RequestBody>(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        body: B,
+        retry_mode: RetryMode,
+    ) -> Result<ApiResponse, ApiClientError> {
+        let url = self.request_url(url, &body)?;
+        let response = self.execute(method.clone(), url.clone(), body, retry_mode).await?;
+        Ok(ApiResponse { method, url, response })
+    }
+
+    /// Joins `url` to the base URL and attaches the query of `body`, if any.
+    fn request_url(&self, url: &str, body: &impl RequestBody) -> Result<Url, ApiClientError> {
+        let mut url = self.base_url.join(url)?;
+        if let Some(query_string) = body.query()? {
+            url.set_query(Some(&query_string));
+        }
+        Ok(url)
+    }
+
+    /// Sends the request and turns any error status into [`ApiClientError`].
+    async fn execute(
+        &self,
+        method: reqwest::Method,
+        url: Url,
+        body: impl RequestBody,
+        retry_mode: RetryMode,
+    ) -> Result<reqwest::Response, ApiClientError> {
+        let request_builder =
+            self.client.request(method.clone(), url.clone()).with_extension(retry_mode);
+        let request_builder = body.attach(request_builder)?;
+
+        // Send the request.
+        let mut response = match request_builder.send().await {
+            Ok(response) => response,
+            Err(err) => return Err(ApiClientError::from_network(&method, &url, err)),
+        };
+
+        // Handle any response error before deserialisation.
+        let status = response.status();
+        if status.is_client_error() || status.is_server_error() {
+            let attempts = response.extensions_mut().remove::<RetryAttempts>();
+            let rate_limit = Box::new(RateLimit::from_headers(response.headers()));
+            let headers = response.headers().clone();
+            let body = match response.bytes().await {
+                Ok(body) => ErrorBody::decode(status, &headers, &body, &self.error_decoders),
+                Err(_) => ErrorBody::Text("no response body".into()),
+            };
+            let err = ApiClientError::Api {
+                status,
+                method: method.to_string(),
+                url: url.to_string(),
+                body,
+                rate_limit,
+            };
+            return Err(match attempts {
+                Some(RetryAttempts(attempts)) => ApiClientError::Retry {
+                    method: method.to_string(),
+                    url: url.to_string(),
+                    attempts,
+                    last: Box::new(err),
+                },
+                None => err,
+            });
+        }
+
+        Ok(response)
+    }
+}
+
+/// Deserializes JSON `body_bytes` with path tracking, so the error points to the failed field.
+fn deserialize_body<Response>(
+    method: &reqwest::Method,
+    url: &Url,
+    body_bytes: &[u8],
+) -> Result<Response, ApiClientError>
+where
+    Response: serde::de::DeserializeOwned,
+{
+    // Create a standard JSON deserializer from the bytes.
+    let mut deserializer = serde_json::Deserializer::from_slice(body_bytes);
+
+    // Wrap it with path tracking and deserialize.
+    serde_path_to_error::deserialize(&mut deserializer).map_err(|cause| {
+        // We only convert bytes -> String IF there is an error.
+        // Use 'from_utf8_lossy' to safely handle the body even if it contains invalid characters or binary data.
+        let body_text = String::from_utf8_lossy(body_bytes).to_string();
+
+        // Log error with actual response body text, so we can debug it later.
+        tracing::error!(%method, %url, %body_text, %cause, "Failed to parse response body");
+
+        ApiClientError::Deserialization {
+            method: method.to_string(),
+            url: url.to_string(),
+            err: cause,
+        }
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiClientError {
+    #[error("Failed to parse URL: {0}")]
+    UrlParse(#[from] url::ParseError),
+
+    #[error("Failed to build path from `{template}`: {reason}")]
+    Path { template: &'static str, reason: String },
+
+    #[error("Network error: {0}")]
+    Network(#[from] reqwest_middleware::Error),
+
+    #[error("Failed to authenticate: {0}")]
+    Auth(#[from] AuthError),
+
+    #[error("Failed to serialize query: {0}")]
+    QuerySerialization(#[from] serde_html_form::ser::Error),
+
+    #[error("Failed to serialize form: {0}")]
+    FormSerialization(serde_html_form::ser::Error),
+
+    #[error("API error ({status}) at {method} {url}: {body}")]
+    Api {
+        status: reqwest::StatusCode,
+        method: String,
+        url: String,
+        body: ErrorBody,
+        rate_limit: Box<RateLimit>,
+    },
+
+    #[error("Failed to deserialize response at {method} {url}: {err}")]
+    Deserialization {
+        method: String,
+        url: String,
+        err: serde_path_to_error::Error<serde_json::Error>,
+    },
+
+    #[error("Failed to write response to '{}': {source}", path.display())]
+    Io { path: std::path::PathBuf, source: std::io::Error },
+
+    #[error("Failed to paginate at {url}: {reason}")]
+    Pagination { url: String, reason: String },
+
+    #[error("Request failed after {} attempts at {method} {url}: {last}", attempts.len())]
+    Retry { method: String, url: String, attempts: Vec<RetryAttempt>, last: Box<ApiClientError> },
+}
+
+impl ApiClientError {
+    /// Error response of [`ApiClientError::Api`], also if it was the last of retried attempts.
+    fn api(&self) -> Option<(reqwest::StatusCode, &ErrorBody, &RateLimit)> {
+        match self {
+            ApiClientError::Api { status, body, rate_limit, .. } => {
+                Some((*status, body, rate_limit))
+            }
+            ApiClientError::Retry { last, .. } => last.api(),
+            _ => None,
+        }
+    }
+
+    /// Status of the error response.
+    pub fn status(&self) -> Option<reqwest::StatusCode> {
+        self.api().map(|(status, ..)| status)
+    }
+
+    /// Decoded body of the error response.
+    pub fn error_body(&self) -> Option<&ErrorBody> {
+        self.api().map(|(_, body, _)| body)
+    }
+
+    /// `Retry-After` and rate limit headers of the error response.
+    pub fn rate_limit(&self) -> Option<&RateLimit> {
+        self.api().map(|(.., rate_limit)| rate_limit)
+    }
+
+    /// Unpacks [`RetryError`] and [`AuthError`] from the middleware error, so they are not hidden behind `anyhow`.
+    fn from_network(method: &reqwest::Method, url: &Url, err: reqwest_middleware::Error) -> Self {
+        match err {
+            reqwest_middleware::Error::Middleware(err) if err.is::<RetryError>() => {
+                let RetryError { attempts, source } =
+                    err.downcast().expect("error type was checked above");
+                ApiClientError::Retry {
+                    method: method.to_string(),
+                    url: url.to_string(),
+                    attempts,
+                    last: Box::new(ApiClientError::Network(source)),
+                }
+            }
+            reqwest_middleware::Error::Middleware(err) if err.is::<AuthError>() => {
+                ApiClientError::Auth(err.downcast().expect("error type was checked above"))
+            }
+            err => ApiClientError::Network(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest_middleware::ClientBuilder;
+    use wiremock::matchers::{body_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    #[derive(serde::Serialize)]
+    struct GetUserFiles {
+        user: String,
+        tag: Vec<u32>,
+    }
+
+    impl Endpoint for GetUserFiles {
+        const METHOD: reqwest::Method = reqwest::Method::GET;
+        const PATH: &'static str = "users/{user}/files";
+        type Response = Vec<String>;
+    }
+
+    #[derive(serde::Serialize)]
+    struct CreateFile {
+        user: String,
+        name: String,
+    }
+
+    impl Endpoint for CreateFile {
+        const METHOD: reqwest::Method = reqwest::Method::POST;
+        const PATH: &'static str = "users/{user}/files";
+        type Response = u64;
+    }
+
+    fn client(server: &MockServer) -> ApiClient {
+        let client = ClientBuilder::new(reqwest::Client::new()).build();
+        ApiClient::new(client, format!("{}/api/", server.uri()).parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn call_get_puts_rest_of_fields_into_query() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/users/John%20Doe/files"))
+            .and(query_param("tag", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(["a.txt"]))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let files = client(&server)
+            .call(GetUserFiles { user: "John Doe".into(), tag: vec![1, 2] })
+            .await
+            .unwrap();
+
+        assert_eq!(files, ["a.txt"]);
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[0].url.query(), Some("tag=1&tag=2"));
+    }
+
+    #[tokio::test]
+    async fn call_post_puts_rest_of_fields_into_body() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/users/me/files"))
+            .and(body_json(serde_json::json!({ "name": "a.txt" })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(7))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let id = client(&server).call(CreateFile { user: "me".into(), name: "a.txt".into() }).await;
+
+        assert_eq!(id.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn call_rejects_path_traversal() {
+        let server = MockServer::start().await;
+
+        let err =
+            client(&server).call(CreateFile { user: "..".into(), name: "a.txt".into() }).await;
+
+        assert!(matches!(err, Err(ApiClientError::Path { template: "users/{user}/files", .. })));
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct ValidationError {
+        field: String,
+    }
+
+    impl std::fmt::Display for ValidationError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "invalid `{}`", self.field)
+        }
+    }
+
+    #[tokio::test]
+    async fn decodes_error_body_and_headers() {
+        let server = MockServer::start().await;
+        Mock::given(path("/api/users/me/files"))
+            .and(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(422).set_body_json(serde_json::json!({ "field": "name" })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(path("/api/users/other/files"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("Retry-After", "3")
+                    .insert_header("X-RateLimit-Remaining", "0")
+                    .set_body_raw(r#"{"title": "Slow down"}"#, "application/problem+json"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = client(&server).with_error_body::<ValidationError>(StatusClass::ClientError);
+
+        let err = client.call(CreateFile { user: "me".into(), name: "".into() }).await.unwrap_err();
+        assert_eq!(err.status(), Some(reqwest::StatusCode::UNPROCESSABLE_ENTITY));
+        let body = err.error_body().unwrap().downcast_ref::<ValidationError>().unwrap();
+        assert_eq!(body.field, "name");
+        assert!(err.to_string().ends_with(": invalid `name`"), "{err}");
+
+        // Problem details are not a `ValidationError`, so RFC 7807 decoder takes them.
+        let err =
+            client.call(CreateFile { user: "other".into(), name: "".into() }).await.unwrap_err();
+        assert_eq!(err.status(), Some(reqwest::StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(
+            err.error_body().unwrap().problem().unwrap().title.as_deref(),
+            Some("Slow down")
+        );
+        let rate_limit = err.rate_limit().unwrap();
+        assert_eq!(rate_limit.retry_after, Some(std::time::Duration::from_secs(3)));
+        assert_eq!(rate_limit.remaining, Some(0));
+    }
+}
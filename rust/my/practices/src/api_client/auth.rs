@@ -0,0 +1,484 @@
+//! Authentication middleware for [`reqwest_middleware::ClientWithMiddleware`].
+//!
+//! [`AuthMiddleware`] asks an [`AuthProvider`] to add credentials to each request and,
+//! if the provider can refresh them, resends a request rejected with 401 once.
+//! Register it after [`super::retry::RetryMiddleware`], so every retried attempt is authenticated anew.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use http::Extensions;
+use reqwest_middleware::reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest_middleware::reqwest::{self, Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next};
+use tokio::sync::Mutex;
+use url::Url;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("Invalid credentials header name: {0}")]
+    InvalidHeaderName(#[from] header::InvalidHeaderName),
+
+    #[error("Invalid credentials header value: {0}")]
+    InvalidHeaderValue(#[from] header::InvalidHeaderValue),
+
+    #[error("Failed to request token from {url}: {source}")]
+    TokenRequest { url: String, source: reqwest::Error },
+
+    #[error("Token endpoint {url} responded with {status}: {body}")]
+    TokenResponse { url: String, status: StatusCode, body: String },
+
+    #[error("Failed to parse token response from {url}: {source}")]
+    TokenParse { url: String, source: serde_json::Error },
+}
+
+/// Source of credentials for [`AuthMiddleware`].
+#[async_trait::async_trait]
+pub trait AuthProvider: Send + Sync {
+    /// Adds credentials to `req`.
+    async fn authenticate(&self, req: &mut Request) -> Result<(), AuthError>;
+
+    /// Called when the request sent with `rejected` headers got 401.
+    /// Returns whether credentials were refreshed and the request is worth resending.
+    async fn unauthorized(&self, _rejected: &HeaderMap) -> bool {
+        false
+    }
+}
+
+/// `Authorization: Bearer <token>` with a static token.
+pub struct BearerToken(pub String);
+
+#[async_trait::async_trait]
+impl AuthProvider for BearerToken {
+    async fn authenticate(&self, req: &mut Request) -> Result<(), AuthError> {
+        set_authorization(req, &format!("Bearer {}", self.0))
+    }
+}
+
+/// `Authorization: Basic <base64(username:password)>`.
+pub struct BasicAuth {
+    pub username: String,
+    pub password: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl AuthProvider for BasicAuth {
+    async fn authenticate(&self, req: &mut Request) -> Result<(), AuthError> {
+        set_authorization(req, &basic_credentials(&self.username, self.password.as_deref()))
+    }
+}
+
+/// Where [`ApiKey`] is put.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiKeyLocation {
+    Header,
+    Query,
+}
+
+/// API key in a header or query param `name`.
+pub struct ApiKey {
+    pub name: String,
+    pub value: String,
+    pub location: ApiKeyLocation,
+}
+
+#[async_trait::async_trait]
+impl AuthProvider for ApiKey {
+    async fn authenticate(&self, req: &mut Request) -> Result<(), AuthError> {
+        match self.location {
+            ApiKeyLocation::Header => {
+                let name = header::HeaderName::from_bytes(self.name.as_bytes())?;
+                let mut value = HeaderValue::from_str(&self.value)?;
+                value.set_sensitive(true);
+                req.headers_mut().insert(name, value);
+            }
+            ApiKeyLocation::Query => {
+                req.url_mut().query_pairs_mut().append_pair(&self.name, &self.value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// OAuth2 client credentials grant (RFC 6749, section 4.4).
+///
+/// The token is cached until it expires or the API rejects it with 401.
+/// Concurrent requests wait for a single token request instead of sending their own.
+pub struct ClientCredentials {
+    pub token_url: Url,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scopes: Vec<String>,
+    /// Token is refreshed this long before it expires, to not send an almost expired one.
+    pub expiry_leeway: Duration,
+    client: reqwest::Client,
+    token: Mutex<Option<CachedToken>>,
+}
+
+struct CachedToken {
+    access_token: String,
+    /// `None` if the token endpoint didn't send `expires_in`, then the token is used until 401.
+    expires_at: Option<Instant>,
+}
+
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+impl ClientCredentials {
+    pub fn new(token_url: Url, client_id: String, client_secret: String) -> Self {
+        Self {
+            token_url,
+            client_id,
+            client_secret,
+            scopes: Vec::new(),
+            expiry_leeway: Duration::from_secs(30),
+            client: reqwest::Client::new(),
+            token: Mutex::new(None),
+        }
+    }
+
+    pub fn with_scopes(mut self, scopes: Vec<String>) -> Self {
+        self.scopes = scopes;
+        self
+    }
+
+    pub fn with_expiry_leeway(mut self, leeway: Duration) -> Self {
+        self.expiry_leeway = leeway;
+        self
+    }
+
+    /// Client for the token endpoint, it shouldn't have [`AuthMiddleware`] itself.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Returns the cached token or requests a new one.
+    async fn access_token(&self) -> Result<String, AuthError> {
+        // The lock is held during the token request, so concurrent callers reuse its result.
+        let mut token = self.token.lock().await;
+        if let Some(cached) = &*token {
+            let fresh = cached
+                .expires_at
+                .is_none_or(|expires_at| Instant::now() + self.expiry_leeway < expires_at);
+            if fresh {
+                return Ok(cached.access_token.clone());
+            }
+        }
+
+        let cached = self.request_token().await?;
+        let access_token = cached.access_token.clone();
+        *token = Some(cached);
+        Ok(access_token)
+    }
+
+    async fn request_token(&self) -> Result<CachedToken, AuthError> {
+        let url = self.token_url.to_string();
+        let scope = self.scopes.join(" ");
+        let mut form = vec![("grant_type", "client_credentials")];
+        if !scope.is_empty() {
+            form.push(("scope", &scope));
+        }
+
+        let requested_at = Instant::now();
+        let response = self
+            .client
+            .post(self.token_url.clone())
+            .header(
+                header::AUTHORIZATION,
+                client_secret_basic(&self.client_id, &self.client_secret),
+            )
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(serde_html_form::to_string(&form).expect("pairs of strings are serializable"))
+            .send()
+            .await
+            .map_err(|source| AuthError::TokenRequest { url: url.clone(), source })?;
+
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|source| AuthError::TokenRequest { url: url.clone(), source })?;
+        if !status.is_success() {
+            let body = String::from_utf8_lossy(&body).into_owned();
+            return Err(AuthError::TokenResponse { url, status, body });
+        }
+        let token: TokenResponse = serde_json::from_slice(&body)
+            .map_err(|source| AuthError::TokenParse { url: url.clone(), source })?;
+
+        tracing::debug!(%url, expires_in = ?token.expires_in, "Received access token");
+        Ok(CachedToken {
+            access_token: token.access_token,
+            expires_at: token.expires_in.map(|secs| requested_at + Duration::from_secs(secs)),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthProvider for ClientCredentials {
+    async fn authenticate(&self, req: &mut Request) -> Result<(), AuthError> {
+        let token = self.access_token().await?;
+        set_authorization(req, &format!("Bearer {token}"))
+    }
+
+    async fn unauthorized(&self, rejected: &HeaderMap) -> bool {
+        let mut token = self.token.lock().await;
+        // Drop the token only if it's the rejected one, it may be already refreshed
+        // by another request that got 401 at the same time.
+        let rejected = rejected.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+        if let Some(cached) = &*token
+            && rejected == Some(&format!("Bearer {}", cached.access_token))
+        {
+            *token = None;
+        }
+        true
+    }
+}
+
+fn basic_credentials(username: &str, password: Option<&str>) -> String {
+    let credentials = format!("{username}:{}", password.unwrap_or_default());
+    format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(credentials))
+}
+
+/// RFC 6749 §2.3.1 form-urlencodes the client credentials before encoding them like [`BasicAuth`].
+fn client_secret_basic(client_id: &str, client_secret: &str) -> String {
+    let encode =
+        |value: &str| url::form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>();
+    basic_credentials(&encode(client_id), Some(&encode(client_secret)))
+}
+
+fn set_authorization(req: &mut Request, credentials: &str) -> Result<(), AuthError> {
+    let mut value = HeaderValue::from_str(credentials)?;
+    value.set_sensitive(true);
+    req.headers_mut().insert(header::AUTHORIZATION, value);
+    Ok(())
+}
+
+pub struct AuthMiddleware {
+    provider: Arc<dyn AuthProvider>,
+}
+
+impl AuthMiddleware {
+    pub fn new(provider: impl AuthProvider + 'static) -> Self {
+        Self { provider: Arc::new(provider) }
+    }
+
+    /// Shares `provider`, and so its cached token, with other clients.
+    pub fn shared(provider: Arc<dyn AuthProvider>) -> Self {
+        Self { provider }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for AuthMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        // Streamed bodies cannot be cloned, so such requests are not resent on 401.
+        let resend = req.try_clone();
+        self.provider
+            .authenticate(&mut req)
+            .await
+            .map_err(reqwest_middleware::Error::middleware)?;
+        let headers = req.headers().clone();
+
+        let response = next.clone().run(req, extensions).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let Some(mut resend) = resend else {
+            return Ok(response);
+        };
+        if !self.provider.unauthorized(&headers).await {
+            return Ok(response);
+        }
+
+        tracing::debug!(url = %resend.url(), "Resending request with refreshed credentials");
+        self.provider
+            .authenticate(&mut resend)
+            .await
+            .map_err(reqwest_middleware::Error::middleware)?;
+        next.run(resend, extensions).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use reqwest_middleware::ClientBuilder;
+    use wiremock::matchers::{body_string, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, Request as MockRequest, ResponseTemplate};
+
+    use super::*;
+    use crate::api_client::{ApiClient, ApiClientError};
+
+    fn client(server: &MockServer, provider: impl AuthProvider + 'static) -> ApiClient {
+        let client =
+            ClientBuilder::new(reqwest::Client::new()).with(AuthMiddleware::new(provider)).build();
+        ApiClient::new(client, server.uri().parse().unwrap())
+    }
+
+    fn credentials(server: &MockServer) -> ClientCredentials {
+        let token_url = format!("{}/token", server.uri()).parse().unwrap();
+        ClientCredentials::new(token_url, "id".into(), "secret".into())
+    }
+
+    /// Token endpoint issuing `token-1`, `token-2`, ... valid for `expires_in` seconds.
+    async fn mount_token_endpoint(server: &MockServer, expires_in: u64) -> Arc<AtomicUsize> {
+        let issued = Arc::new(AtomicUsize::new(0));
+        let counter = issued.clone();
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(header("authorization", "Basic aWQ6c2VjcmV0"))
+            .and(body_string("grant_type=client_credentials&scope=read+write"))
+            .respond_with(move |_: &MockRequest| {
+                let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "access_token": format!("token-{n}"),
+                        "token_type": "Bearer",
+                        "expires_in": expires_in,
+                    }))
+                    // Lets concurrent requests pile up while the token is being issued.
+                    .set_delay(Duration::from_millis(50))
+            })
+            .mount(server)
+            .await;
+        issued
+    }
+
+    async fn mount_api(server: &MockServer, token: &str) {
+        Mock::given(path("/me"))
+            .and(header("authorization", format!("Bearer {token}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(token))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn static_credentials() {
+        let server = MockServer::start().await;
+        Mock::given(path("/bearer"))
+            .and(header("authorization", "Bearer abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(1))
+            .mount(&server)
+            .await;
+        Mock::given(path("/basic"))
+            .and(header("authorization", "Basic dXNlcjpwYXNz"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(2))
+            .mount(&server)
+            .await;
+        Mock::given(path("/header"))
+            .and(header("x-api-key", "k"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(3))
+            .mount(&server)
+            .await;
+        Mock::given(path("/query"))
+            .and(query_param("api_key", "k"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(4))
+            .mount(&server)
+            .await;
+
+        let get = |client: ApiClient, url: &'static str| async move {
+            client.send_request::<_, u32>(reqwest::Method::GET, url, &[("page", 2)]).await.unwrap()
+        };
+        let api_key = |location| ApiKey { name: "x-api-key".into(), value: "k".into(), location };
+
+        assert_eq!(get(client(&server, BearerToken("abc".into())), "bearer").await, 1);
+        let basic = BasicAuth { username: "user".into(), password: Some("pass".into()) };
+        assert_eq!(get(client(&server, basic), "basic").await, 2);
+        assert_eq!(get(client(&server, api_key(ApiKeyLocation::Header)), "header").await, 3);
+        let query = ApiKey { name: "api_key".into(), ..api_key(ApiKeyLocation::Query) };
+        assert_eq!(get(client(&server, query), "query").await, 4);
+    }
+
+    #[tokio::test]
+    async fn client_credentials_token_is_requested_once_for_concurrent_requests() {
+        let server = MockServer::start().await;
+        let issued = mount_token_endpoint(&server, 3600).await;
+        mount_api(&server, "token-1").await;
+        let scopes = vec!["read".into(), "write".into()];
+        let client = client(&server, credentials(&server).with_scopes(scopes));
+
+        let requests =
+            (0..5).map(|_| client.send_request::<_, String>(reqwest::Method::GET, "me", &()));
+        let tokens = futures::future::try_join_all(requests).await.unwrap();
+
+        assert_eq!(tokens, ["token-1"; 5]);
+        assert_eq!(issued.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn client_credentials_token_is_refreshed_on_expiry() {
+        let server = MockServer::start().await;
+        // Already expired with the default leeway.
+        let issued = mount_token_endpoint(&server, 10).await;
+        mount_api(&server, "token-1").await;
+        mount_api(&server, "token-2").await;
+        let scopes = vec!["read".into(), "write".into()];
+        let client = client(&server, credentials(&server).with_scopes(scopes));
+
+        let me = || client.send_request::<_, String>(reqwest::Method::GET, "me", &());
+        assert_eq!(me().await.unwrap(), "token-1");
+        assert_eq!(me().await.unwrap(), "token-2");
+        assert_eq!(issued.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn client_credentials_token_is_refreshed_once_on_401() {
+        let server = MockServer::start().await;
+        let issued = mount_token_endpoint(&server, 3600).await;
+        Mock::given(path("/me"))
+            .and(header("authorization", "Bearer token-1"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        mount_api(&server, "token-2").await;
+        let scopes = vec!["read".into(), "write".into()];
+        let client = client(&server, credentials(&server).with_scopes(scopes));
+
+        let requests =
+            (0..3).map(|_| client.send_request::<_, String>(reqwest::Method::GET, "me", &()));
+        let tokens = futures::future::try_join_all(requests).await.unwrap();
+
+        assert_eq!(tokens, ["token-2"; 3]);
+        assert_eq!(issued.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn client_credentials_are_form_urlencoded() {
+        assert_eq!(
+            client_secret_basic("my id", "a:b%c+d"),
+            basic_credentials("my+id", Some("a%3Ab%25c%2Bd"))
+        );
+        assert_eq!(client_secret_basic("id", "secret"), "Basic aWQ6c2VjcmV0");
+        assert_eq!(basic_credentials("a:b", Some("c")), "Basic YTpiOmM=");
+    }
+
+    #[tokio::test]
+    async fn client_credentials_token_endpoint_error() {
+        let server = MockServer::start().await;
+        Mock::given(path("/token"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("invalid_client"))
+            .mount(&server)
+            .await;
+        let client = client(&server, credentials(&server));
+
+        let err = client.send_request::<_, String>(reqwest::Method::GET, "me", &()).await;
+
+        let Err(ApiClientError::Auth(AuthError::TokenResponse { status, body, .. })) = err else {
+            panic!("unexpected result: {err:?}");
+        };
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, "invalid_client");
+    }
+}
@@ -0,0 +1,419 @@
+//! Request body encodings and response body modes, see [`ApiClient::send`].
+
+use std::path::{Path, PathBuf};
+
+use reqwest_middleware::RequestBuilder;
+use reqwest_middleware::reqwest::{self, header};
+use tokio::io::{AsyncRead, AsyncWriteExt};
+use url::Url;
+
+pub use reqwest::multipart;
+
+use super::retry::RetryMode;
+use super::{ApiClient, ApiClientError, deserialize_body};
+
+/// How a request is put into the HTTP request.
+pub trait RequestBody {
+    /// Query string for the URL, if the request is sent as query params.
+    fn query(&self) -> Result<Option<String>, ApiClientError> {
+        Ok(None)
+    }
+
+    /// Attaches the body with its `Content-Type` to the request.
+    fn attach(self, builder: RequestBuilder) -> Result<RequestBuilder, ApiClientError>;
+}
+
+/// No body.
+impl RequestBody for () {
+    fn attach(self, builder: RequestBuilder) -> Result<RequestBuilder, ApiClientError> {
+        Ok(builder)
+    }
+}
+
+/// Query params, encoded with `serde_html_form`.
+pub struct Query<'a, T: ?Sized>(pub &'a T);
+
+impl<T: serde::Serialize + ?Sized> RequestBody for Query<'_, T> {
+    fn query(&self) -> Result<Option<String>, ApiClientError> {
+        // Use `serde_html_form` crate instead of `serde_urlencoded` (baked in `reqwest`),
+        // as it doesn't handle vec (`id=1&id=2`) and gives `unsupported value` error.
+        let query_string = serde_html_form::to_string(self.0)?;
+        Ok((!query_string.is_empty()).then_some(query_string))
+    }
+
+    fn attach(self, builder: RequestBuilder) -> Result<RequestBuilder, ApiClientError> {
+        Ok(builder)
+    }
+}
+
+/// Query params of the first with the body of the second, e.g. `(Query(&page), Json(&pet))`.
+impl<Q: serde::Serialize + ?Sized, B: RequestBody> RequestBody for (Query<'_, Q>, B) {
+    fn query(&self) -> Result<Option<String>, ApiClientError> {
+        self.0.query()
+    }
+
+    fn attach(self, builder: RequestBuilder) -> Result<RequestBuilder, ApiClientError> {
+        self.1.attach(builder)
+    }
+}
+
+/// No body when `None`.
+impl<B: RequestBody> RequestBody for Option<B> {
+    fn query(&self) -> Result<Option<String>, ApiClientError> {
+        self.as_ref().map_or(Ok(None), B::query)
+    }
+
+    fn attach(self, builder: RequestBuilder) -> Result<RequestBuilder, ApiClientError> {
+        match self {
+            Some(body) => body.attach(builder),
+            None => Ok(builder),
+        }
+    }
+}
+
+/// `application/json` body.
+pub struct Json<'a, T: ?Sized>(pub &'a T);
+
+impl<T: serde::Serialize + ?Sized> RequestBody for Json<'_, T> {
+    fn attach(self, builder: RequestBuilder) -> Result<RequestBuilder, ApiClientError> {
+        Ok(builder.json(self.0))
+    }
+}
+
+/// `application/x-www-form-urlencoded` body, encoded with `serde_html_form` same as [`Query`].
+pub struct Form<'a, T: ?Sized>(pub &'a T);
+
+impl<T: serde::Serialize + ?Sized> RequestBody for Form<'_, T> {
+    fn attach(self, builder: RequestBuilder) -> Result<RequestBuilder, ApiClientError> {
+        let form = serde_html_form::to_string(self.0).map_err(ApiClientError::FormSerialization)?;
+        Ok(builder.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded").body(form))
+    }
+}
+
+/// `multipart/form-data` body, file parts can be added with [`multipart::Form::file`].
+pub struct Multipart(pub multipart::Form);
+
+impl RequestBody for Multipart {
+    fn attach(self, builder: RequestBuilder) -> Result<RequestBuilder, ApiClientError> {
+        Ok(builder.multipart(self.0))
+    }
+}
+
+/// Body as is.
+pub struct Raw {
+    pub bytes: bytes::Bytes,
+    pub content_type: String,
+}
+
+impl RequestBody for Raw {
+    fn attach(self, builder: RequestBuilder) -> Result<RequestBuilder, ApiClientError> {
+        Ok(builder.header(header::CONTENT_TYPE, self.content_type).body(self.bytes))
+    }
+}
+
+/// Body streamed from `reader` without buffering it in memory.
+/// Such requests cannot be cloned, so they are never retried.
+pub struct Upload<R> {
+    pub reader: R,
+    pub content_type: String,
+    /// Sent as `Content-Length`, otherwise the body is chunked.
+    pub length: Option<u64>,
+}
+
+impl<R: AsyncRead + Send + 'static> RequestBody for Upload<R> {
+    fn attach(self, builder: RequestBuilder) -> Result<RequestBuilder, ApiClientError> {
+        let body = reqwest::Body::wrap_stream(tokio_util::io::ReaderStream::new(self.reader));
+        let builder = builder.header(header::CONTENT_TYPE, self.content_type);
+        let builder = match self.length {
+            Some(length) => builder.header(header::CONTENT_LENGTH, length),
+            None => builder,
+        };
+        Ok(builder.body(body))
+    }
+}
+
+/// Successful response, which body can be read in one of the supported modes.
+pub struct ApiResponse {
+    pub(super) method: reqwest::Method,
+    pub(super) url: Url,
+    pub(super) response: reqwest::Response,
+}
+
+impl ApiResponse {
+    pub fn status(&self) -> reqwest::StatusCode {
+        self.response.status()
+    }
+
+    pub fn headers(&self) -> &reqwest::header::HeaderMap {
+        self.response.headers()
+    }
+
+    /// Deserializes JSON with path tracking, same as [`ApiClient::send_request`].
+    pub async fn json<T: serde::de::DeserializeOwned>(self) -> Result<T, ApiClientError> {
+        let (method, url) = (self.method.clone(), self.url.clone());
+        deserialize_body(&method, &url, &self.bytes().await?)
+    }
+
+    pub async fn bytes(self) -> Result<bytes::Bytes, ApiClientError> {
+        Ok(self.response.bytes().await.map_err(reqwest_middleware::Error::Reqwest)?)
+    }
+
+    pub async fn text(self) -> Result<String, ApiClientError> {
+        Ok(self.response.text().await.map_err(reqwest_middleware::Error::Reqwest)?)
+    }
+
+    /// Streams the body into a new file at `path` chunk by chunk and returns the number of written bytes.
+    /// The body is written to `<path>.part` first, so a failed download leaves no truncated file at `path`.
+    pub async fn download(mut self, path: &Path) -> Result<u64, ApiClientError> {
+        let mut part = path.as_os_str().to_owned();
+        part.push(".part");
+        let part = PathBuf::from(part);
+
+        let written = match self.write_to(&part).await {
+            Ok(written) => written,
+            Err(err) => {
+                if let Err(err) = tokio::fs::remove_file(&part).await {
+                    tracing::warn!(path = %part.display(), %err, "Failed to remove partial download");
+                }
+                return Err(err);
+            }
+        };
+        let io_err = |source| ApiClientError::Io { path: path.to_owned(), source };
+        tokio::fs::rename(&part, path).await.map_err(io_err)?;
+
+        tracing::debug!(method = %self.method, url = %self.url, path = %path.display(), written, "Downloaded response body");
+        Ok(written)
+    }
+
+    async fn write_to(&mut self, path: &Path) -> Result<u64, ApiClientError> {
+        let io_err = |source| ApiClientError::Io { path: path.to_owned(), source };
+
+        let mut file = tokio::fs::File::create(path).await.map_err(io_err)?;
+        let mut written = 0;
+        while let Some(chunk) =
+            self.response.chunk().await.map_err(reqwest_middleware::Error::Reqwest)?
+        {
+            file.write_all(&chunk).await.map_err(io_err)?;
+            written += chunk.len() as u64;
+        }
+        file.flush().await.map_err(io_err)?;
+        Ok(written)
+    }
+}
+
+impl ApiClient {
+    /// Sends `body` in any of [`RequestBody`] encodings.
+    /// The response body is not read yet, so it can be taken as JSON, bytes, text or downloaded to a file.
+    pub async fn send<B: RequestBody>(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        body: B,
+    ) -> Result<ApiResponse, ApiClientError> {
+        self.send_with_retry(method, url, body, RetryMode::default()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest_middleware::ClientBuilder;
+    use wiremock::matchers::{body_bytes, body_string, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    use super::*;
+
+    fn client(server: &MockServer) -> ApiClient {
+        let client = ClientBuilder::new(reqwest::Client::new()).build();
+        ApiClient::new(client, server.uri().parse().unwrap())
+    }
+
+    #[derive(serde::Serialize)]
+    struct Search {
+        q: &'static str,
+        tag: Vec<&'static str>,
+    }
+
+    #[tokio::test]
+    async fn form_body() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("content-type", "application/x-www-form-urlencoded"))
+            .and(body_string("q=a+b&tag=x&tag=y"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let search = Search { q: "a b", tag: vec!["x", "y"] };
+        let text = client(&server)
+            .send(reqwest::Method::POST, "search", Form(&search))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+
+        assert_eq!(text, "ok");
+    }
+
+    #[tokio::test]
+    async fn query_with_json_body() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/search"))
+            .and(query_param("q", "a b"))
+            .and(body_string(r#"{"limit":2}"#))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let search = Search { q: "a b", tag: vec![] };
+        let limit = serde_json::json!({"limit": 2});
+
+        let response = client(&server)
+            .send(reqwest::Method::POST, "search", (Query(&search), Some(Json(&limit))))
+            .await
+            .unwrap();
+
+        assert_eq!(response.text().await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn multipart_body_with_file() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(|req: &Request| {
+                ResponseTemplate::new(200).set_body_bytes(req.body.clone()).insert_header(
+                    "content-type",
+                    req.headers.get("content-type").unwrap().to_str().unwrap(),
+                )
+            })
+            .mount(&server)
+            .await;
+        let file = temp_file::with_contents(b"file contents");
+
+        let form = multipart::Form::new().text("name", "report").file("file", file.path()).await;
+        let response = client(&server)
+            .send(reqwest::Method::POST, "upload", Multipart(form.unwrap()))
+            .await
+            .unwrap();
+
+        let content_type = response.headers()["content-type"].to_str().unwrap().to_owned();
+        assert!(content_type.starts_with("multipart/form-data; boundary="), "{content_type}");
+        let echoed = response.text().await.unwrap();
+        assert!(echoed.contains("name=\"name\"\r\n\r\nreport\r\n"), "{echoed}");
+        assert!(echoed.contains("name=\"file\"; filename="), "{echoed}");
+        assert!(echoed.contains("\r\n\r\nfile contents\r\n"), "{echoed}");
+    }
+
+    #[tokio::test]
+    async fn raw_and_streamed_bodies() {
+        let server = MockServer::start().await;
+        Mock::given(path("/raw"))
+            .and(header("content-type", "application/octet-stream"))
+            .and(body_bytes([0, 159, 146, 150]))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes([1, 2, 3]))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/upload"))
+            .and(header("content-type", "text/plain"))
+            .and(header("content-length", "11"))
+            .and(body_string("hello world"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/chunked"))
+            .and(header("transfer-encoding", "chunked"))
+            .and(body_string("hello world"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = client(&server);
+
+        let raw = Raw {
+            bytes: bytes::Bytes::from_static(&[0, 159, 146, 150]),
+            content_type: "application/octet-stream".into(),
+        };
+        let bytes =
+            client.send(reqwest::Method::PUT, "raw", raw).await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes.as_ref(), [1, 2, 3]);
+
+        let upload = Upload {
+            reader: &b"hello world"[..],
+            content_type: "text/plain".into(),
+            length: Some(11),
+        };
+        let response = client.send(reqwest::Method::PUT, "upload", upload).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let upload =
+            Upload { reader: &b"hello world"[..], content_type: "text/plain".into(), length: None };
+        let response = client.send(reqwest::Method::PUT, "chunked", upload).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn query_and_json_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/search"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(["found"]))
+            .mount(&server)
+            .await;
+
+        let search = Search { q: "a", tag: vec!["x"] };
+        let response = client(&server).send(reqwest::Method::GET, "search", Query(&search)).await;
+        let found: Vec<String> = response.unwrap().json().await.unwrap();
+
+        assert_eq!(found, ["found"]);
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[0].url.query(), Some("q=a&tag=x"));
+    }
+
+    #[tokio::test]
+    async fn downloads_to_file() {
+        let server = MockServer::start().await;
+        let contents = vec![7; 100_000];
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(contents.clone()))
+            .mount(&server)
+            .await;
+        let file = temp_file::empty();
+
+        let response = client(&server).send(reqwest::Method::GET, "file", ()).await.unwrap();
+        let written = response.download(file.path()).await.unwrap();
+
+        assert_eq!(written, 100_000);
+        assert_eq!(std::fs::read(file.path()).unwrap(), contents);
+    }
+
+    #[tokio::test]
+    async fn failed_download_leaves_no_file() {
+        use tokio::io::AsyncReadExt;
+
+        // Closes the connection before the whole body promised by `Content-Length` is sent.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await;
+            let response = "HTTP/1.1 200 OK\r\ncontent-length: 100\r\n\r\n0123456789";
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        let client = ApiClient::new(
+            ClientBuilder::new(reqwest::Client::new()).build(),
+            base_url.parse().unwrap(),
+        );
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.child("file.bin");
+
+        let response = client.send(reqwest::Method::GET, "file", ()).await.unwrap();
+        response.download(&path).await.unwrap_err();
+
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,803 @@
+//! HTTP cache middleware for [`reqwest_middleware::ClientWithMiddleware`].
+//!
+//! [`ResponseCache`] keeps successful GET responses keyed on the final URL, including the query,
+//! and the request headers named by `Vary`, in an in-memory LRU and optionally on disk.
+//! Responses are served as is while fresh by `Cache-Control: max-age`, and revalidated with
+//! `If-None-Match`/`If-Modified-Since` after that.
+//!
+//! As clones share entries, responses for one user aren't stored by default: `private` ones and
+//! ones to requests with `Authorization`. Register the cache after [`super::auth::AuthMiddleware`],
+//! so it sees `Authorization`, and before retries and rate limiting, so cache hits skip them.
+//! Default headers of [`reqwest::Client`] are added after
+//! all middleware, so the cache doesn't see them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use http::Extensions;
+use reqwest_middleware::reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest_middleware::reqwest::{Method, Request, Response, ResponseBuilderExt, StatusCode};
+use reqwest_middleware::{Middleware, Next};
+use url::Url;
+
+/// Cached response with its validators.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct CachedResponse {
+    /// Cache key, kept to detect hash collisions of disk entries.
+    url: String,
+    headers: Vec<(String, String)>,
+    #[serde(with = "base64_body")]
+    body: bytes::Bytes,
+    stored_at: SystemTime,
+    /// `None` if the response must be revalidated on every use.
+    max_age: Option<Duration>,
+    /// Request headers named by `Vary` with their values, which must be the same to use the entry.
+    #[serde(default)]
+    vary: Vec<(String, Option<String>)>,
+}
+
+impl CachedResponse {
+    fn header(&self, name: &header::HeaderName) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name.as_str()).map(|(_, value)| value.as_str())
+    }
+
+    fn matches(&self, request_headers: &HeaderMap) -> bool {
+        self.vary.iter().all(|(name, value)| header_value(request_headers, name) == *value)
+    }
+
+    fn is_fresh(&self, now: SystemTime) -> bool {
+        let age = now.duration_since(self.stored_at).unwrap_or_default();
+        self.max_age.is_some_and(|max_age| age < max_age)
+    }
+
+    fn to_response(&self, url: Url) -> Response {
+        let mut response = http::Response::builder().status(StatusCode::OK).url(url);
+        for (name, value) in &self.headers {
+            response = response.header(name, value);
+        }
+        let response =
+            response.body(self.body.clone()).expect("headers were taken from a valid response");
+        Response::from(response)
+    }
+}
+
+mod base64_body {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(body: &bytes::Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(body))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<bytes::Bytes, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map(Into::into).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Directives of `Cache-Control` response header the cache cares about.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    max_age: Option<Duration>,
+}
+
+impl CacheControl {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let mut cache_control = Self::default();
+        let directives = headers
+            .get_all(header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for directive in directives {
+            let (name, value) = directive.split_once('=').unwrap_or((directive, ""));
+            match name.trim().to_ascii_lowercase().as_str() {
+                "no-store" => cache_control.no_store = true,
+                "no-cache" => cache_control.no_cache = true,
+                "private" => cache_control.private = true,
+                "max-age" => {
+                    cache_control.max_age =
+                        value.trim().trim_matches('"').parse().ok().map(Duration::from_secs)
+                }
+                _ => {}
+            }
+        }
+        cache_control
+    }
+
+    /// How long a response can be used without revalidation.
+    fn max_age(&self) -> Option<Duration> {
+        if self.no_cache { None } else { self.max_age }
+    }
+}
+
+/// Names of request headers in `Vary`, `None` for `Vary: *`, which no request matches.
+fn vary(headers: &HeaderMap) -> Option<Vec<String>> {
+    let mut names = vec![];
+    let values = headers.get_all(header::VARY).iter().filter_map(|value| value.to_str().ok());
+    for name in values.flat_map(|value| value.split(',')) {
+        match name.trim().to_ascii_lowercase() {
+            name if name == "*" => return None,
+            name if !name.is_empty() && !names.contains(&name) => names.push(name),
+            _ => {}
+        }
+    }
+    Some(names)
+}
+
+/// All values of a header, joined like in a single header.
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values: Vec<_> = headers
+        .get_all(name)
+        .iter()
+        .map(|value| String::from_utf8_lossy(value.as_bytes()))
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+/// Least recently used entries are evicted first.
+#[derive(Debug)]
+struct Lru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, (u64, Arc<CachedResponse>)>,
+    /// Keys by the tick they were last used at.
+    order: BTreeMap<u64, String>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, tick: 0, entries: HashMap::new(), order: BTreeMap::new() }
+    }
+
+    fn get(&mut self, key: &str) -> Option<Arc<CachedResponse>> {
+        self.tick += 1;
+        let (tick, entry) = self.entries.get_mut(key)?;
+        let key = self.order.remove(tick).expect("order has every entry");
+        *tick = self.tick;
+        self.order.insert(self.tick, key);
+        Some(entry.clone())
+    }
+
+    /// Returns keys of the evicted entries.
+    fn insert(&mut self, key: String, entry: Arc<CachedResponse>) -> Vec<String> {
+        self.tick += 1;
+        if let Some((tick, _)) = self.entries.insert(key.clone(), (self.tick, entry)) {
+            self.order.remove(&tick);
+        }
+        self.order.insert(self.tick, key);
+        let mut evicted = vec![];
+        while self.entries.len() > self.capacity {
+            let (_, oldest) = self.order.pop_first().expect("order has every entry");
+            self.entries.remove(&oldest);
+            evicted.push(oldest);
+        }
+        evicted
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((tick, _)) = self.entries.remove(key) {
+            self.order.remove(&tick);
+        }
+    }
+}
+
+/// Snapshot of [`ResponseCache`] counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Responses served from the cache, including revalidated ones.
+    pub hits: u64,
+    /// Hits which were confirmed by the server with 304 Not Modified.
+    pub revalidated: u64,
+    /// Cacheable requests which were sent to the server and not answered with 304.
+    pub misses: u64,
+}
+
+struct Inner {
+    memory: Mutex<Lru>,
+    disk: Option<PathBuf>,
+    /// Whether responses for one user are stored too.
+    private: AtomicBool,
+    hits: AtomicU64,
+    revalidated: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Cache is opt-in: only clients built with this middleware use it.
+/// Clones share the same entries and counters.
+#[derive(Clone)]
+pub struct ResponseCache {
+    inner: Arc<Inner>,
+}
+
+impl ResponseCache {
+    /// In-memory cache of at most `capacity` responses.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_store(capacity, None)
+    }
+
+    /// Same as [`ResponseCache::new`], but also keeps every response as a file in `dir`,
+    /// so the cache outlives the process. Files are removed when their entries are evicted.
+    /// Disk errors are logged and otherwise ignored, as the cache is best effort.
+    pub fn on_disk(capacity: usize, dir: impl Into<PathBuf>) -> Self {
+        Self::with_store(capacity, Some(dir.into()))
+    }
+
+    fn with_store(capacity: usize, disk: Option<PathBuf>) -> Self {
+        let inner = Inner {
+            memory: Mutex::new(Lru::new(capacity)),
+            disk,
+            private: AtomicBool::new(false),
+            hits: AtomicU64::new(0),
+            revalidated: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        };
+        Self { inner: Arc::new(inner) }
+    }
+
+    /// Stores `Cache-Control: private` responses and responses to requests with `Authorization`
+    /// too, which is only safe if all clients sharing the cache act for the same user.
+    /// Clones share the setting.
+    pub fn with_private_responses(self, private: bool) -> Self {
+        self.inner.private.store(private, Ordering::Relaxed);
+        self
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.inner.hits.load(Ordering::Relaxed),
+            revalidated: self.inner.revalidated.load(Ordering::Relaxed),
+            misses: self.inner.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn get(&self, key: &str) -> Option<Arc<CachedResponse>> {
+        let cached = self.inner.memory.lock().unwrap().get(key);
+        if let Some(entry) = cached {
+            return Some(entry);
+        }
+        let entry = Arc::new(self.read_disk(key).await?);
+        self.remember(key.to_owned(), entry.clone()).await;
+        Some(entry)
+    }
+
+    async fn insert(&self, entry: CachedResponse) -> Arc<CachedResponse> {
+        self.write_disk(&entry).await;
+        let entry = Arc::new(entry);
+        self.remember(entry.url.clone(), entry.clone()).await;
+        entry
+    }
+
+    /// Puts the entry in memory, evicted entries are removed from disk too.
+    async fn remember(&self, key: String, entry: Arc<CachedResponse>) {
+        let evicted = self.inner.memory.lock().unwrap().insert(key, entry);
+        for key in evicted {
+            self.remove_disk(&key).await;
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        self.inner.memory.lock().unwrap().remove(key);
+        self.remove_disk(key).await;
+    }
+
+    async fn remove_disk(&self, key: &str) {
+        if let Some(path) = self.disk_path(key)
+            && let Err(err) = tokio::fs::remove_file(&path).await
+            && err.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::warn!(path = %path.display(), %err, "Failed to remove cached response");
+        }
+    }
+
+    fn disk_path(&self, key: &str) -> Option<PathBuf> {
+        // FNV-1a, as it's stable between Rust versions unlike `DefaultHasher`.
+        let hash = key.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+        Some(self.inner.disk.as_ref()?.join(format!("{hash:016x}.json")))
+    }
+
+    async fn read_disk(&self, key: &str) -> Option<CachedResponse> {
+        let path = self.disk_path(key)?;
+        let json = tokio::fs::read(&path).await.ok()?;
+        match serde_json::from_slice::<CachedResponse>(&json) {
+            Ok(entry) if entry.url == key => Some(entry),
+            Ok(_) => None,
+            Err(err) => {
+                tracing::warn!(path = %path.display(), %err, "Ignoring corrupted cached response");
+                None
+            }
+        }
+    }
+
+    async fn write_disk(&self, entry: &CachedResponse) {
+        let Some(path) = self.disk_path(&entry.url) else {
+            return;
+        };
+        // Write to a temporary file first, so readers never see a partially written entry.
+        let tmp = path.with_extension("json.tmp");
+        let write = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&tmp, serde_json::to_vec(entry)?).await?;
+            tokio::fs::rename(&tmp, &path).await
+        };
+        if let Err(err) = write.await {
+            tracing::warn!(path = %path.display(), %err, "Failed to write cached response");
+        }
+    }
+
+    fn hit(&self, entry: &CachedResponse, url: Url, revalidated: bool) -> Response {
+        self.inner.hits.fetch_add(1, Ordering::Relaxed);
+        if revalidated {
+            self.inner.revalidated.fetch_add(1, Ordering::Relaxed);
+        }
+        tracing::debug!(%url, revalidated, "Serving response from cache");
+        entry.to_response(url)
+    }
+
+    /// Stores a 200 response if it is allowed and can be validated or is fresh for some time.
+    async fn store(
+        &self,
+        request_headers: &HeaderMap,
+        response: Response,
+    ) -> reqwest_middleware::Result<Response> {
+        let url = response.url().clone();
+        let cache_control = CacheControl::from_headers(response.headers());
+        let has_validators = response.headers().contains_key(header::ETAG)
+            || response.headers().contains_key(header::LAST_MODIFIED);
+        let private = cache_control.private || request_headers.contains_key(header::AUTHORIZATION);
+        let vary = vary(response.headers());
+        let forbidden = cache_control.no_store
+            || vary.is_none()
+            || (private && !self.inner.private.load(Ordering::Relaxed));
+        let (StatusCode::OK, false, Some(vary)) = (response.status(), forbidden, vary) else {
+            if forbidden {
+                self.remove(url.as_str()).await;
+            }
+            return Ok(response);
+        };
+        if !has_validators && cache_control.max_age().is_none() {
+            return Ok(response);
+        }
+
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+            .collect();
+        let body = response.bytes().await?;
+        let entry = self
+            .insert(CachedResponse {
+                url: url.to_string(),
+                headers,
+                body,
+                stored_at: SystemTime::now(),
+                max_age: cache_control.max_age(),
+                vary: vary
+                    .into_iter()
+                    .map(|name| {
+                        let value = header_value(request_headers, &name);
+                        (name, value)
+                    })
+                    .collect(),
+            })
+            .await;
+        // Body was consumed, so serve it from the stored entry.
+        Ok(entry.to_response(url))
+    }
+
+    /// Updates the entry with the headers of a 304 response, which may extend its freshness.
+    async fn refresh(
+        &self,
+        entry: &CachedResponse,
+        not_modified: &HeaderMap,
+    ) -> Arc<CachedResponse> {
+        let mut entry = entry.clone();
+        for (name, value) in not_modified {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            entry.headers.retain(|(key, _)| key != name.as_str());
+            entry.headers.push((name.to_string(), value.to_owned()));
+        }
+        entry.max_age = CacheControl::from_headers(not_modified).max_age().or(entry.max_age);
+        entry.stored_at = SystemTime::now();
+        self.insert(entry).await
+    }
+}
+
+fn set_header(req: &mut Request, name: header::HeaderName, value: Option<&str>) {
+    if let Some(value) = value.and_then(|value| HeaderValue::from_str(value).ok()) {
+        req.headers_mut().insert(name, value);
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for ResponseCache {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        if req.method() != Method::GET {
+            return next.run(req, extensions).await;
+        }
+
+        let url = req.url().clone();
+        let request_headers = req.headers().clone();
+        let cached = match self.get(url.as_str()).await {
+            // Only one variant is kept, so the other one replaces it.
+            Some(entry) if entry.matches(&request_headers) => Some(entry),
+            _ => None,
+        };
+        if let Some(entry) = &cached {
+            if entry.is_fresh(SystemTime::now()) {
+                return Ok(self.hit(entry, url, false));
+            }
+            set_header(&mut req, header::IF_NONE_MATCH, entry.header(&header::ETAG));
+            set_header(&mut req, header::IF_MODIFIED_SINCE, entry.header(&header::LAST_MODIFIED));
+        }
+
+        let response = next.run(req, extensions).await?;
+        if let Some(entry) = &cached
+            && response.status() == StatusCode::NOT_MODIFIED
+        {
+            let entry = self.refresh(entry, response.headers()).await;
+            return Ok(self.hit(&entry, url, true));
+        }
+
+        self.inner.misses.fetch_add(1, Ordering::Relaxed);
+        self.store(&request_headers, response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest_middleware::ClientBuilder;
+    use reqwest_middleware::reqwest;
+    use wiremock::matchers::{header as header_eq, header_exists, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::api_client::ApiClient;
+
+    fn client(server: &MockServer, cache: &ResponseCache) -> ApiClient {
+        let client = ClientBuilder::new(reqwest::Client::new()).with(cache.clone()).build();
+        ApiClient::new(client, server.uri().parse().unwrap())
+    }
+
+    /// Sets headers before the cache, like `AuthMiddleware`.
+    struct SetHeaders(HeaderMap);
+
+    #[async_trait::async_trait]
+    impl Middleware for SetHeaders {
+        async fn handle(
+            &self,
+            mut req: Request,
+            extensions: &mut Extensions,
+            next: Next<'_>,
+        ) -> reqwest_middleware::Result<Response> {
+            req.headers_mut().extend(self.0.clone());
+            next.run(req, extensions).await
+        }
+    }
+
+    fn client_with(
+        server: &MockServer,
+        cache: &ResponseCache,
+        headers: &[(&str, &str)],
+    ) -> ApiClient {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+            .collect();
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(SetHeaders(headers))
+            .with(cache.clone())
+            .build();
+        ApiClient::new(client, server.uri().parse().unwrap())
+    }
+
+    async fn get(client: &ApiClient, url: &str) -> String {
+        client.send_request(reqwest::Method::GET, url, &()).await.unwrap()
+    }
+
+    fn stats(hits: u64, revalidated: u64, misses: u64) -> CacheStats {
+        CacheStats { hits, revalidated, misses }
+    }
+
+    #[test]
+    fn parses_cache_control() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::CACHE_CONTROL, HeaderValue::from_static("public, Max-Age=60"));
+        headers.append(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+        let cache_control = CacheControl::from_headers(&headers);
+
+        let max_age = Some(Duration::from_secs(60));
+        let expected = CacheControl { no_store: false, no_cache: true, private: false, max_age };
+        assert_eq!(cache_control, expected);
+        assert_eq!(cache_control.max_age(), None);
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let entry = |url: &str| {
+            let body = bytes::Bytes::new();
+            let stored_at = SystemTime::now();
+            Arc::new(CachedResponse {
+                url: url.into(),
+                headers: vec![],
+                body,
+                stored_at,
+                max_age: None,
+                vary: vec![],
+            })
+        };
+        let mut lru = Lru::new(2);
+        lru.insert("a".into(), entry("a"));
+        lru.insert("b".into(), entry("b"));
+        lru.get("a");
+
+        lru.insert("c".into(), entry("c"));
+
+        assert!(lru.get("b").is_none());
+        assert!(lru.get("a").is_some());
+        assert!(lru.get("c").is_some());
+    }
+
+    #[tokio::test]
+    async fn serves_fresh_response_from_cache_by_full_url() {
+        let server = MockServer::start().await;
+        for tag in ["a", "b"] {
+            Mock::given(path("/items"))
+                .and(query_param("tag", tag))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("cache-control", "max-age=60")
+                        .set_body_json(tag),
+                )
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        let cache = ResponseCache::new(10);
+        let client = client(&server, &cache);
+
+        let get = async |tag: &str| -> String {
+            let query = [("tag", tag), ("tag", "x")];
+            client.send_request(reqwest::Method::GET, "items", &query).await.unwrap()
+        };
+        assert_eq!(get("a").await, "a");
+        assert_eq!(get("a").await, "a");
+        assert_eq!(get("b").await, "b");
+
+        assert_eq!(cache.stats(), stats(1, 0, 2));
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[0].url.query(), Some("tag=a&tag=x"));
+    }
+
+    #[tokio::test]
+    async fn revalidates_with_etag_and_last_modified() {
+        let server = MockServer::start().await;
+        Mock::given(path("/etag"))
+            .and(header_eq("if-none-match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304).insert_header("etag", "\"v1\""))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/etag"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("etag", "\"v1\"")
+                    .insert_header("cache-control", "no-cache")
+                    .set_body_json("etag"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let last_modified = "Wed, 21 Oct 2015 07:28:00 GMT";
+        Mock::given(path("/modified"))
+            // `header` matcher would split the date on comma.
+            .and(move |req: &wiremock::Request| {
+                req.headers.get("if-modified-since").is_some_and(|value| value == last_modified)
+            })
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/modified"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("last-modified", last_modified)
+                    .set_body_json("modified"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let cache = ResponseCache::new(10);
+        let client = client(&server, &cache);
+
+        for _ in 0..2 {
+            assert_eq!(get(&client, "etag").await, "etag");
+            assert_eq!(get(&client, "modified").await, "modified");
+        }
+
+        assert_eq!(cache.stats(), stats(2, 2, 2));
+    }
+
+    #[tokio::test]
+    async fn does_not_cache_no_store_and_errors() {
+        let server = MockServer::start().await;
+        Mock::given(path("/no-store"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("cache-control", "no-store")
+                    .insert_header("etag", "\"v1\"")
+                    .set_body_json("no-store"),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(path("/error"))
+            .respond_with(ResponseTemplate::new(500).insert_header("cache-control", "max-age=60"))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("cache-control", "max-age=60")
+                    .set_body_json(1),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+        let cache = ResponseCache::new(10);
+        let client = client(&server, &cache);
+
+        for _ in 0..2 {
+            assert_eq!(get(&client, "no-store").await, "no-store");
+            let err = client.send_request::<_, String>(reqwest::Method::GET, "error", &()).await;
+            assert_eq!(err.unwrap_err().status(), Some(StatusCode::INTERNAL_SERVER_ERROR));
+            let posted: u32 =
+                client.send_request(reqwest::Method::POST, "post", &()).await.unwrap();
+            assert_eq!(posted, 1);
+        }
+
+        assert_eq!(cache.stats(), stats(0, 0, 4));
+    }
+
+    #[tokio::test]
+    async fn does_not_share_responses_for_one_user() {
+        let server = MockServer::start().await;
+        for (path, cache_control) in [("/private", "private, max-age=60"), ("/me", "max-age=60")] {
+            Mock::given(wiremock::matchers::path(path))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("cache-control", cache_control)
+                        .set_body_json(path),
+                )
+                .mount(&server)
+                .await;
+        }
+        Mock::given(path("/any"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("cache-control", "max-age=60")
+                    .insert_header("vary", "accept, *")
+                    .set_body_json("/any"),
+            )
+            .mount(&server)
+            .await;
+
+        let shared = ResponseCache::new(10);
+        let anonymous = client(&server, &shared);
+        let authorized = client_with(&server, &shared, &[("authorization", "Bearer ann")]);
+        for _ in 0..2 {
+            assert_eq!(get(&anonymous, "private").await, "/private");
+            assert_eq!(get(&authorized, "me").await, "/me");
+            assert_eq!(get(&anonymous, "any").await, "/any");
+        }
+        assert_eq!(shared.stats(), stats(0, 0, 6));
+
+        let own = ResponseCache::new(10);
+        let authorized = client_with(&server, &own, &[("authorization", "Bearer ann")]);
+        // Applies to the clone in the client too.
+        let own = own.with_private_responses(true);
+        for _ in 0..2 {
+            assert_eq!(get(&authorized, "private").await, "/private");
+            assert_eq!(get(&authorized, "me").await, "/me");
+            assert_eq!(get(&authorized, "any").await, "/any");
+        }
+        assert_eq!(own.stats(), stats(2, 0, 4));
+    }
+
+    #[tokio::test]
+    async fn varies_by_request_headers() {
+        let server = MockServer::start().await;
+        for language in ["en", "de"] {
+            Mock::given(path("/greeting"))
+                .and(header_eq("accept-language", language))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("cache-control", "max-age=60")
+                        .insert_header("vary", "Accept-Language")
+                        .set_body_json(language),
+                )
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        let cache = ResponseCache::new(10);
+        let english = client_with(&server, &cache, &[("accept-language", "en")]);
+        let german = client_with(&server, &cache, &[("accept-language", "de")]);
+
+        assert_eq!(get(&english, "greeting").await, "en");
+        assert_eq!(get(&english, "greeting").await, "en");
+        assert_eq!(get(&german, "greeting").await, "de");
+        assert_eq!(get(&german, "greeting").await, "de");
+        assert_eq!(cache.stats(), stats(2, 0, 2));
+    }
+
+    #[tokio::test]
+    async fn disk_store_outlives_memory() {
+        let server = MockServer::start().await;
+        Mock::given(path("/items"))
+            .and(header_exists("if-none-match"))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/items"))
+            .respond_with(
+                ResponseTemplate::new(200).insert_header("etag", "\"v1\"").set_body_json("items"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let dir = temp_dir::TempDir::new().unwrap();
+
+        let cache = ResponseCache::on_disk(10, dir.path().join("cache"));
+        assert_eq!(get(&client(&server, &cache), "items").await, "items");
+
+        let restarted = ResponseCache::on_disk(10, dir.path().join("cache"));
+        assert_eq!(get(&client(&server, &restarted), "items").await, "items");
+        assert_eq!(restarted.stats(), stats(1, 1, 0));
+    }
+
+    #[tokio::test]
+    async fn disk_store_evicts_with_memory() {
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(
+                ResponseTemplate::new(200).insert_header("etag", "\"v1\"").set_body_json("item"),
+            )
+            .mount(&server)
+            .await;
+        let dir = temp_dir::TempDir::new().unwrap();
+        let cache = ResponseCache::on_disk(1, dir.path().join("cache"));
+        let client = client(&server, &cache);
+
+        get(&client, "a").await;
+        get(&client, "b").await;
+
+        let files = std::fs::read_dir(dir.path().join("cache")).unwrap().count();
+        assert_eq!(files, 1);
+        assert!(cache.read_disk(&format!("{}/a", server.uri())).await.is_none());
+        assert!(cache.read_disk(&format!("{}/b", server.uri())).await.is_some());
+    }
+}
@@ -0,0 +1,475 @@
+//! Record and replay of HTTP interactions, so code using [`super::ApiClient`] can be tested without network.
+//!
+//! In [`CassetteMode::Record`] every request is sent as usual and written with its response to a JSON
+//! cassette file. In [`CassetteMode::Replay`] nothing is sent and responses are served from that file.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use http::Extensions;
+use reqwest_middleware::reqwest::{Request, Response, ResponseBuilderExt};
+use reqwest_middleware::{Middleware, Next};
+use serde_json::Value;
+use url::Url;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Full URL, including the query.
+    pub url: String,
+    pub query: Vec<(String, String)>,
+    /// JSON body, or string if the body is not JSON.
+    pub body: Option<Value>,
+}
+
+impl RecordedRequest {
+    fn new(req: &Request) -> Self {
+        let body = req.body().and_then(|body| body.as_bytes()).map(|bytes| {
+            serde_json::from_slice(bytes)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(bytes).into_owned()))
+        });
+        Self {
+            method: req.method().to_string(),
+            url: req.url().to_string(),
+            query: req.url().query_pairs().into_owned().collect(),
+            body,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, RecordedBytes)>,
+    pub body: RecordedBytes,
+}
+
+/// UTF-8 as a string, so cassettes stay readable, and anything else as `{ "base64": ... }`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum RecordedBytes {
+    Text(String),
+    Base64 { base64: String },
+}
+
+impl RecordedBytes {
+    pub fn new(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Self::Text(text.to_owned()),
+            Err(_) => Self::Base64 { base64: STANDARD.encode(bytes) },
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, base64::DecodeError> {
+        match self {
+            Self::Text(text) => Ok(text.as_bytes().to_vec()),
+            Self::Base64 { base64 } => STANDARD.decode(base64),
+        }
+    }
+}
+
+impl From<&str> for RecordedBytes {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_owned())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// Which parts of a request must be equal to the recorded one to be replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchRules {
+    pub method: bool,
+    /// URL without the query.
+    pub path: bool,
+    /// Query params in any order.
+    pub query: bool,
+    pub body: bool,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self { method: true, path: true, query: true, body: true }
+    }
+}
+
+impl MatchRules {
+    fn matches(&self, recorded: &RecordedRequest, actual: &RecordedRequest) -> bool {
+        let without_query = |url: &str| {
+            let mut url = Url::parse(url).ok()?;
+            url.set_query(None);
+            Some(url)
+        };
+        let sorted = |query: &[(String, String)]| {
+            let mut query = query.to_vec();
+            query.sort();
+            query
+        };
+
+        (!self.method || recorded.method == actual.method)
+            && (!self.path || without_query(&recorded.url) == without_query(&actual.url))
+            && (!self.query || sorted(&recorded.query) == sorted(&actual.query))
+            && (!self.body || recorded.body == actual.body)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CassetteError {
+    #[error("Failed to access cassette '{}': {source}", path.display())]
+    Io { path: PathBuf, source: std::io::Error },
+
+    #[error("Failed to parse cassette '{}': {source}", path.display())]
+    Parse { path: PathBuf, source: serde_json::Error },
+
+    #[error("Failed to serialize an interaction for cassette '{}': {source}", path.display())]
+    Serialize { path: PathBuf, source: serde_json::Error },
+
+    #[error("Invalid recorded response in '{}': {source}", path.display())]
+    InvalidResponse { path: PathBuf, source: http::Error },
+
+    #[error("Invalid base64 of a recorded response in '{}': {source}", path.display())]
+    InvalidBase64 { path: PathBuf, source: base64::DecodeError },
+
+    #[error("No recorded interaction in '{}' matches {} {}", path.display(), request.method, request.url)]
+    Unmatched { path: PathBuf, request: Box<RecordedRequest> },
+}
+
+struct Cassette {
+    interactions: Vec<Interaction>,
+    /// Replayed interactions are not served again, so repeated requests get successive responses.
+    used: Vec<bool>,
+    /// Recorded interactions, serialized once and joined on each save.
+    recorded: Vec<String>,
+}
+
+pub struct CassetteMiddleware {
+    mode: CassetteMode,
+    path: PathBuf,
+    rules: MatchRules,
+    cassette: Mutex<Cassette>,
+    /// Number of recorded interactions in the file. Saves are done one at a time,
+    /// so an older cassette never replaces a newer one.
+    saved: tokio::sync::Mutex<usize>,
+}
+
+impl CassetteMiddleware {
+    /// Starts an empty cassette, `path` is overwritten with the first recorded interaction.
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self::new(CassetteMode::Record, path.into(), Vec::new())
+    }
+
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self, CassetteError> {
+        let path = path.into();
+        let json = std::fs::read(&path)
+            .map_err(|source| CassetteError::Io { path: path.clone(), source })?;
+        let interactions = serde_json::from_slice(&json)
+            .map_err(|source| CassetteError::Parse { path: path.clone(), source })?;
+        Ok(Self::new(CassetteMode::Replay, path, interactions))
+    }
+
+    pub fn with_match_rules(mut self, rules: MatchRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    fn new(mode: CassetteMode, path: PathBuf, interactions: Vec<Interaction>) -> Self {
+        let used = vec![false; interactions.len()];
+        let cassette = Mutex::new(Cassette { interactions, used, recorded: Vec::new() });
+        let saved = tokio::sync::Mutex::new(0);
+        Self { mode, path, rules: MatchRules::default(), cassette, saved }
+    }
+
+    fn replay_response(&self, req: &Request) -> Result<Response, CassetteError> {
+        let request = RecordedRequest::new(req);
+        let mut cassette = self.cassette.lock().unwrap();
+        let Cassette { interactions, used, .. } = &mut *cassette;
+        let Some(index) = interactions
+            .iter()
+            .zip(used.iter())
+            .position(|(recorded, used)| !used && self.rules.matches(&recorded.request, &request))
+        else {
+            return Err(CassetteError::Unmatched {
+                path: self.path.clone(),
+                request: Box::new(request),
+            });
+        };
+        used[index] = true;
+
+        let recorded = &interactions[index].response;
+        let decode = |bytes: &RecordedBytes| {
+            bytes
+                .to_bytes()
+                .map_err(|source| CassetteError::InvalidBase64 { path: self.path.clone(), source })
+        };
+        let mut response = http::Response::builder().status(recorded.status).url(req.url().clone());
+        for (name, value) in &recorded.headers {
+            response = response.header(name, decode(value)?);
+        }
+        let response = response
+            .body(decode(&recorded.body)?)
+            .map_err(|source| CassetteError::InvalidResponse { path: self.path.clone(), source })?;
+        Ok(Response::from(response))
+    }
+
+    async fn record_response(
+        &self,
+        request: RecordedRequest,
+        response: Response,
+    ) -> reqwest_middleware::Result<Response> {
+        let status = response.status();
+        let url = response.url().clone();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?;
+
+        let recorded = RecordedResponse {
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), RecordedBytes::new(value.as_bytes())))
+                .collect(),
+            body: RecordedBytes::new(&body),
+        };
+        self.save(Interaction { request, response: recorded })
+            .await
+            .map_err(reqwest_middleware::Error::middleware)?;
+
+        // Body was consumed, so build the same response again.
+        let mut rebuilt = http::Response::builder().status(status).url(url);
+        if let Some(rebuilt_headers) = rebuilt.headers_mut() {
+            *rebuilt_headers = headers;
+        }
+        let rebuilt = rebuilt.body(body).expect("parts are taken from a valid response");
+        Ok(Response::from(rebuilt))
+    }
+
+    /// Rewrites the whole cassette on the blocking pool, so it is usable even if the process
+    /// is killed. Interactions recorded meanwhile are saved together.
+    async fn save(&self, interaction: Interaction) -> Result<(), CassetteError> {
+        let json = serde_json::to_string_pretty(&interaction)
+            .map_err(|source| CassetteError::Serialize { path: self.path.clone(), source })?;
+        {
+            let mut cassette = self.cassette.lock().unwrap();
+            cassette.interactions.push(interaction);
+            cassette.used.push(true);
+            // Indented like elements of `to_vec_pretty`, JSON strings have no raw newlines.
+            cassette.recorded.push(format!("  {}", json.replace('\n', "\n  ")));
+        }
+
+        let mut saved = self.saved.lock().await;
+        let (count, json) = {
+            let cassette = self.cassette.lock().unwrap();
+            let count = cassette.recorded.len();
+            if count == *saved {
+                return Ok(());
+            }
+            (count, format!("[\n{}\n]", cassette.recorded.join(",\n")))
+        };
+        let path = self.path.clone();
+        match tokio::task::spawn_blocking(move || write_atomically(&path, json.as_bytes())).await {
+            Ok(result) => {
+                result.map_err(|source| CassetteError::Io { path: self.path.clone(), source })?
+            }
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        }
+        *saved = count;
+        Ok(())
+    }
+}
+
+/// Writes a temporary file next to `path` and renames it over `path`, so a killed process
+/// leaves either the previous or the new contents.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, path)
+}
+
+#[async_trait::async_trait]
+impl Middleware for CassetteMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        match self.mode {
+            CassetteMode::Replay => {
+                self.replay_response(&req).map_err(reqwest_middleware::Error::middleware)
+            }
+            CassetteMode::Record => {
+                let request = RecordedRequest::new(&req);
+                let response = next.run(req, extensions).await?;
+                self.record_response(request, response).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest_middleware::{ClientBuilder, reqwest};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::api_client::{ApiClient, ApiClientError};
+
+    fn client(base_url: &str, cassette: CassetteMiddleware) -> ApiClient {
+        let client = ClientBuilder::new(reqwest::Client::new()).with(cassette).build();
+        ApiClient::new(client, base_url.parse().unwrap())
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct User {
+        name: String,
+    }
+
+    #[tokio::test]
+    async fn records_and_replays() {
+        let file = temp_file::empty();
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/users"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({ "id": 1 })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/users/1"))
+            .respond_with(
+                ResponseTemplate::new(404)
+                    .insert_header("x-request-id", "abc")
+                    .set_body_string("not found"),
+            )
+            .mount(&server)
+            .await;
+
+        let base_url = server.uri();
+        let user = User { name: "Ann".into() };
+        let recorder = client(&base_url, CassetteMiddleware::record(file.path()));
+        let created: Value =
+            recorder.send_request(reqwest::Method::POST, "users", &user).await.unwrap();
+        let err = recorder
+            .send_request::<_, Value>(reqwest::Method::GET, "users/1", &[("full", true)])
+            .await
+            .unwrap_err();
+        drop(server);
+
+        let json = std::fs::read(file.path()).unwrap();
+        let interactions: Vec<Interaction> = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json, serde_json::to_vec_pretty(&interactions).unwrap());
+        assert_eq!(interactions.len(), 2);
+        assert_eq!(interactions[0].request.body, Some(serde_json::json!({ "name": "Ann" })));
+        assert_eq!(interactions[1].request.query, [("full".into(), "true".into())]);
+        assert!(interactions[1].response.headers.contains(&("x-request-id".into(), "abc".into())));
+
+        let player = client(&base_url, CassetteMiddleware::replay(file.path()).unwrap());
+        let replayed: Value =
+            player.send_request(reqwest::Method::POST, "users", &user).await.unwrap();
+        assert_eq!(replayed, created);
+        let replayed_err = player
+            .send_request::<_, Value>(reqwest::Method::GET, "users/1", &[("full", true)])
+            .await
+            .unwrap_err();
+        assert_eq!(replayed_err.to_string(), err.to_string());
+    }
+
+    #[tokio::test]
+    async fn replays_binary_bodies_and_headers() {
+        let file = temp_file::empty();
+        let server = MockServer::start().await;
+        let (body, header) = (vec![0x89, b'P', b'N', b'G', 0xff, 0], b"caf\xe9".to_vec());
+        Mock::given(method("GET"))
+            .and(path("/image"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-name", http::HeaderValue::from_bytes(&header).unwrap())
+                    .set_body_bytes(body.clone()),
+            )
+            .mount(&server)
+            .await;
+
+        let recorder = client(&server.uri(), CassetteMiddleware::record(file.path()));
+        let recorded = recorder.send(reqwest::Method::GET, "image", ()).await.unwrap();
+        assert_eq!(recorded.bytes().await.unwrap(), body);
+        let base_url = server.uri();
+        drop(server);
+
+        let interactions: Vec<Interaction> =
+            serde_json::from_slice(&std::fs::read(file.path()).unwrap()).unwrap();
+        assert!(matches!(interactions[0].response.body, RecordedBytes::Base64 { .. }));
+
+        let player = client(&base_url, CassetteMiddleware::replay(file.path()).unwrap());
+        let replayed = player.send(reqwest::Method::GET, "image", ()).await.unwrap();
+        assert_eq!(replayed.headers()["x-name"].as_bytes(), header);
+        assert_eq!(replayed.bytes().await.unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn unmatched_request_is_clear_error() {
+        let request = RecordedRequest {
+            method: "GET".into(),
+            url: "http://localhost/users?a=1&b=2".into(),
+            query: vec![("a".into(), "1".into()), ("b".into(), "2".into())],
+            body: None,
+        };
+        let response = RecordedResponse { status: 200, headers: vec![], body: "[]".into() };
+        let interaction = Interaction { request, response };
+        let file = temp_file::with_contents(&serde_json::to_vec(&[interaction]).unwrap());
+
+        let player = client("http://localhost", CassetteMiddleware::replay(file.path()).unwrap());
+        let users: Vec<User> = player
+            .send_request(reqwest::Method::GET, "users", &[("b", 2), ("a", 1)])
+            .await
+            .unwrap();
+        assert!(users.is_empty());
+
+        // The only interaction was already used.
+        let err = player
+            .send_request::<_, Vec<User>>(reqwest::Method::GET, "users", &[("a", 1), ("b", 2)])
+            .await
+            .unwrap_err();
+        let ApiClientError::Network(reqwest_middleware::Error::Middleware(err)) = err else {
+            panic!("expected middleware error, got {err:?}");
+        };
+        assert!(matches!(err.downcast_ref(), Some(CassetteError::Unmatched { .. })));
+        assert!(err.to_string().ends_with("matches GET http://localhost/users?a=1&b=2"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn match_rules_can_ignore_body() {
+        let request = RecordedRequest {
+            method: "POST".into(),
+            url: "http://localhost/users".into(),
+            query: vec![],
+            body: Some(serde_json::json!({ "name": "Ann" })),
+        };
+        let response = RecordedResponse { status: 201, headers: vec![], body: "1".into() };
+        let interaction = Interaction { request, response };
+        let file = temp_file::with_contents(&serde_json::to_vec(&[interaction]).unwrap());
+        let user = User { name: "Bob".into() };
+
+        let strict = client("http://localhost", CassetteMiddleware::replay(file.path()).unwrap());
+        let err = strict.send_request::<_, u32>(reqwest::Method::POST, "users", &user).await;
+        assert!(err.is_err());
+
+        let rules = MatchRules { body: false, ..Default::default() };
+        let cassette = CassetteMiddleware::replay(file.path()).unwrap().with_match_rules(rules);
+        let lenient = client("http://localhost", cassette);
+        let id: u32 = lenient.send_request(reqwest::Method::POST, "users", &user).await.unwrap();
+        assert_eq!(id, 1);
+    }
+}
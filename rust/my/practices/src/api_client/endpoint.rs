@@ -0,0 +1,164 @@
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use reqwest_middleware::reqwest::Method;
+use serde_json::{Map, Value};
+
+use super::ApiClientError;
+use super::retry::RetryMode;
+
+/// Ties a request type to its HTTP method, path template and response type.
+///
+/// Placeholders in [`Endpoint::PATH`] like `users/{id}` are filled from the request fields
+/// with the same names, and these fields are not sent in the query string or JSON body.
+///
+/// ```
+/// use my_practices::api_client::Endpoint;
+/// use reqwest_middleware::reqwest::Method;
+///
+/// #[derive(serde::Serialize)]
+/// struct GetUser {
+///     id: u64,
+/// }
+///
+/// impl Endpoint for GetUser {
+///     const METHOD: Method = Method::GET;
+///     const PATH: &'static str = "users/{id}";
+///     type Response = serde_json::Value;
+/// }
+/// ```
+pub trait Endpoint: serde::Serialize {
+    const METHOD: Method;
+
+    /// Relative to the client base URL, joined the same way as in [`super::ApiClient::send_request`].
+    const PATH: &'static str;
+
+    const RETRY_MODE: RetryMode = RetryMode::Idempotent;
+
+    type Response: serde::de::DeserializeOwned;
+}
+
+/// Everything except unreserved characters and sub-delimiters, so a value always stays one segment.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'\\')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Returns `template` with placeholders replaced by percent-encoded `request` fields,
+/// and the rest of the `request` fields.
+/// Used by [`super::ApiClient::call`] and the code generated by `my_api_client_codegen`.
+pub fn interpolate_path(
+    template: &'static str,
+    request: &impl serde::Serialize,
+) -> Result<(String, Value), ApiClientError> {
+    let err = |reason: String| ApiClientError::Path { template, reason };
+
+    let mut fields = match serde_json::to_value(request) {
+        Ok(Value::Object(fields)) => fields,
+        Ok(other) => return Err(err(format!("request must be a struct or map, got `{other}`"))),
+        Err(cause) => return Err(err(cause.to_string())),
+    };
+
+    let mut path = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            return Err(err("unclosed `{`".into()));
+        };
+        let name = &rest[start + 1..start + len];
+        let value = take_param(&mut fields, name).map_err(err)?;
+
+        path.push_str(&rest[..start]);
+        path.extend(utf8_percent_encode(&value, PATH_SEGMENT));
+        rest = &rest[start + len + 1..];
+    }
+    path.push_str(rest);
+
+    Ok((path, Value::Object(fields)))
+}
+
+fn take_param(fields: &mut Map<String, Value>, name: &str) -> Result<String, String> {
+    let value = match fields.remove(name) {
+        Some(Value::String(value)) => value,
+        Some(Value::Number(value)) => value.to_string(),
+        Some(Value::Bool(value)) => value.to_string(),
+        Some(other) => {
+            return Err(format!("`{name}` must be a string, number or bool, got `{other}`"));
+        }
+        None => return Err(format!("no `{name}` field in request")),
+    };
+
+    // Dot segments are normalized by URL parsing even when percent-encoded,
+    // so they would silently change the path.
+    if value.is_empty() || value == "." || value == ".." {
+        return Err(format!("`{name}` has invalid value `{value}`"));
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Serialize)]
+    struct GetFile {
+        owner: String,
+        name: String,
+        version: u32,
+        raw: bool,
+    }
+
+    fn get_file(name: &str) -> GetFile {
+        GetFile { owner: "me".into(), name: name.into(), version: 2, raw: true }
+    }
+
+    #[test]
+    fn interpolates_and_leaves_other_fields() {
+        let (path, rest) =
+            interpolate_path("users/{owner}/files/{name}/v{version}", &get_file("a.txt")).unwrap();
+        assert_eq!(path, "users/me/files/a.txt/v2");
+        assert_eq!(rest, serde_json::json!({ "raw": true }));
+    }
+
+    #[test]
+    fn percent_encodes_values() {
+        let (path, _) = interpolate_path("files/{name}", &get_file("../a b/c?d#e%f")).unwrap();
+        assert_eq!(path, "files/..%2Fa%20b%2Fc%3Fd%23e%25f");
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for name in ["", ".", ".."] {
+            let err = interpolate_path("files/{name}", &get_file(name)).unwrap_err();
+            assert!(matches!(err, ApiClientError::Path { .. }), "{err}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        let err = interpolate_path("files/{missing}", &get_file("a")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to build path from `files/{missing}`: no `missing` field in request"
+        );
+
+        let err = interpolate_path("files/{name", &get_file("a")).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to build path from `files/{name`: unclosed `{`");
+
+        let err = interpolate_path("files/{id}", &42).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to build path from `files/{id}`: request must be a struct or map, got `42`"
+        );
+    }
+}
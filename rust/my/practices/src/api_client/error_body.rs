@@ -0,0 +1,271 @@
+//! Typed bodies and headers of error responses, see [`super::ApiClientError::Api`].
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use reqwest_middleware::reqwest::StatusCode;
+use reqwest_middleware::reqwest::header::{self, HeaderMap, HeaderName};
+
+/// Class of error statuses a decoder is registered for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusClass {
+    /// 4xx
+    ClientError,
+    /// 5xx
+    ServerError,
+    /// 4xx and 5xx
+    Any,
+}
+
+impl StatusClass {
+    pub fn matches(self, status: StatusCode) -> bool {
+        match self {
+            StatusClass::ClientError => status.is_client_error(),
+            StatusClass::ServerError => status.is_server_error(),
+            StatusClass::Any => status.is_client_error() || status.is_server_error(),
+        }
+    }
+}
+
+/// Error body type produced by a custom decoder.
+/// Implemented for every type that can be printed and sent between threads.
+pub trait CustomErrorBody: Any + Debug + Display + Send + Sync {}
+
+impl<T: Any + Debug + Display + Send + Sync> CustomErrorBody for T {}
+
+/// Returns `None` if the body is not of the expected format.
+pub type ErrorDecoder = Arc<dyn Fn(&[u8]) -> Option<Box<dyn CustomErrorBody>> + Send + Sync>;
+
+/// Decodes error bodies of JSON type `T`, see [`super::ApiClient::with_error_body`].
+pub fn json_decoder<T>() -> ErrorDecoder
+where
+    T: serde::de::DeserializeOwned + CustomErrorBody,
+{
+    Arc::new(|body| {
+        let body: T = serde_json::from_slice(body).ok()?;
+        Some(Box::new(body))
+    })
+}
+
+/// Body of an error response.
+#[derive(Debug)]
+pub enum ErrorBody {
+    /// Decoded by a decoder registered for the response status class.
+    Custom(Box<dyn CustomErrorBody>),
+    /// RFC 7807 `application/problem+json`.
+    Problem(Box<ProblemDetails>),
+    /// Body as is, if nothing above matched.
+    Text(String),
+}
+
+impl ErrorBody {
+    /// Tries registered `decoders` in order, then RFC 7807, then falls back to text.
+    pub(crate) fn decode(
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: &[u8],
+        decoders: &[(StatusClass, ErrorDecoder)],
+    ) -> Self {
+        let custom = decoders
+            .iter()
+            .filter(|(class, _)| class.matches(status))
+            .find_map(|(_, decoder)| decoder(body));
+        if let Some(custom) = custom {
+            return ErrorBody::Custom(custom);
+        }
+
+        let is_problem = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/problem+json"));
+        if is_problem && let Ok(problem) = serde_json::from_slice(body) {
+            return ErrorBody::Problem(problem);
+        }
+
+        ErrorBody::Text(String::from_utf8_lossy(body).into_owned())
+    }
+
+    pub fn problem(&self) -> Option<&ProblemDetails> {
+        match self {
+            ErrorBody::Problem(problem) => Some(problem),
+            _ => None,
+        }
+    }
+
+    /// Returns the custom body if it was decoded as `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        match self {
+            ErrorBody::Custom(custom) => (&**custom as &dyn Any).downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ErrorBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorBody::Custom(custom) => Display::fmt(custom, f),
+            ErrorBody::Problem(problem) => Display::fmt(problem, f),
+            ErrorBody::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+/// RFC 7807 problem details.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type", default = "ProblemDetails::default_type")]
+    pub type_: String,
+    pub title: Option<String>,
+    pub status: Option<u16>,
+    pub detail: Option<String>,
+    pub instance: Option<String>,
+    /// Extension members.
+    #[serde(flatten)]
+    pub extensions: BTreeMap<String, serde_json::Value>,
+}
+
+impl ProblemDetails {
+    fn default_type() -> String {
+        "about:blank".into()
+    }
+}
+
+impl Display for ProblemDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.title.as_deref().unwrap_or(&self.type_))?;
+        if let Some(detail) = &self.detail {
+            write!(f, ": {detail}")?;
+        }
+        Ok(())
+    }
+}
+
+/// `Retry-After` and rate limit headers of a response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// From `Retry-After`, either delay in seconds or HTTP date.
+    pub retry_after: Option<Duration>,
+    /// From `X-RateLimit-Limit` or `RateLimit-Limit`.
+    pub limit: Option<u64>,
+    /// From `X-RateLimit-Remaining` or `RateLimit-Remaining`.
+    pub remaining: Option<u64>,
+    /// From `X-RateLimit-Reset` or `RateLimit-Reset`, as is, because APIs use
+    /// both seconds until reset and Unix timestamps.
+    pub reset: Option<u64>,
+}
+
+impl RateLimit {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let number = |name: &'static str| {
+            let value = headers.get(format!("x-{name}")).or_else(|| headers.get(name))?;
+            value.to_str().ok()?.trim().parse().ok()
+        };
+        Self {
+            retry_after: retry_after(headers, SystemTime::now()),
+            limit: number("ratelimit-limit"),
+            remaining: number("ratelimit-remaining"),
+            reset: number("ratelimit-reset"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+fn retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get(HeaderName::from_static("retry-after"))?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest_middleware::reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct MyError {
+        code: u32,
+    }
+
+    impl Display for MyError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "code {}", self.code)
+        }
+    }
+
+    fn header_map(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (HeaderName::from_static(name), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn decodes_custom_body_for_matching_class_only() {
+        let decoders = [(StatusClass::ClientError, json_decoder::<MyError>())];
+        let body = br#"{"code": 7}"#;
+
+        let decoded = ErrorBody::decode(StatusCode::CONFLICT, &HeaderMap::new(), body, &decoders);
+        assert_eq!(decoded.downcast_ref::<MyError>().unwrap().code, 7);
+        assert_eq!(decoded.to_string(), "code 7");
+
+        let decoded =
+            ErrorBody::decode(StatusCode::BAD_GATEWAY, &HeaderMap::new(), body, &decoders);
+        assert!(matches!(decoded, ErrorBody::Text(ref text) if text == r#"{"code": 7}"#));
+    }
+
+    #[test]
+    fn decodes_problem_json() {
+        let headers = header_map(&[("content-type", "application/problem+json; charset=utf-8")]);
+        let body = br#"{"title": "Out of credit", "detail": "Balance is 30", "balance": 30}"#;
+
+        let decoded = ErrorBody::decode(StatusCode::FORBIDDEN, &headers, body, &[]);
+
+        let problem = decoded.problem().unwrap();
+        assert_eq!(problem.type_, "about:blank");
+        assert_eq!(problem.extensions["balance"], 30);
+        assert_eq!(decoded.to_string(), "Out of credit: Balance is 30");
+    }
+
+    #[test]
+    fn parses_rate_limit_headers() {
+        let headers = header_map(&[
+            ("retry-after", "120"),
+            ("x-ratelimit-limit", "100"),
+            ("ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1700000000"),
+        ]);
+
+        let rate_limit = RateLimit::from_headers(&headers);
+
+        assert_eq!(
+            rate_limit,
+            RateLimit {
+                retry_after: Some(Duration::from_secs(120)),
+                limit: Some(100),
+                remaining: Some(0),
+                reset: Some(1700000000),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_retry_after_date() {
+        let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        let headers = header_map(&[("retry-after", "Wed, 21 Oct 2015 07:29:30 GMT")]);
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(90)));
+
+        let headers = header_map(&[("retry-after", "Wed, 21 Oct 2015 07:00:00 GMT")]);
+        assert_eq!(retry_after(&headers, now), Some(Duration::ZERO));
+    }
+}
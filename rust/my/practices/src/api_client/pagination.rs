@@ -0,0 +1,496 @@
+//! Streams of items from paginated list endpoints, see [`ApiClient::paginate`].
+
+use futures::{Stream, TryStreamExt, stream};
+use reqwest_middleware::reqwest::{self, header::HeaderMap};
+use serde_json::Value;
+use url::Url;
+
+use super::body::Query;
+use super::retry::RetryMode;
+use super::{ApiClient, ApiClientError, deserialize_body};
+
+/// Page that was just fetched, used by [`PaginationStrategy`] to find the next one.
+pub struct Page<'a> {
+    pub url: &'a Url,
+    pub headers: &'a HeaderMap,
+    pub body: &'a Value,
+    /// Number of items on this page.
+    pub items: usize,
+}
+
+/// Decides which page to fetch next.
+pub trait PaginationStrategy: Send {
+    /// Returns the URL of the next page, or `None` if `page` was the last one.
+    fn next_page(&mut self, page: &Page<'_>) -> Result<Option<Url>, String>;
+}
+
+/// Cursor is taken from the JSON body and sent back in the `param` query param.
+/// A missing or `null` cursor ends the pagination.
+pub struct CursorPagination {
+    /// JSON pointer to the cursor, e.g. `/meta/next_cursor`.
+    pub cursor_pointer: String,
+    pub param: String,
+}
+
+impl PaginationStrategy for CursorPagination {
+    fn next_page(&mut self, page: &Page<'_>) -> Result<Option<Url>, String> {
+        let cursor = match page.body.pointer(&self.cursor_pointer) {
+            None | Some(Value::Null) => return Ok(None),
+            Some(Value::String(cursor)) if cursor.is_empty() => return Ok(None),
+            Some(Value::String(cursor)) => cursor.clone(),
+            Some(Value::Number(cursor)) => cursor.to_string(),
+            Some(other) => return Err(format!("unexpected cursor `{other}`")),
+        };
+        with_query_param(page.url, &self.param, &cursor).map(Some)
+    }
+}
+
+/// Page number is incremented in the `param` query param until an empty page.
+pub struct PagePagination {
+    pub param: String,
+    /// Used if the first request has no `param`.
+    pub first_page: u64,
+}
+
+impl PaginationStrategy for PagePagination {
+    fn next_page(&mut self, page: &Page<'_>) -> Result<Option<Url>, String> {
+        if page.items == 0 {
+            return Ok(None);
+        }
+        let current = query_param(page.url, &self.param)?.unwrap_or(self.first_page);
+        with_query_param(page.url, &self.param, &(current + 1).to_string()).map(Some)
+    }
+}
+
+/// Offset in the `param` query param is advanced by the number of received items until an empty page.
+pub struct OffsetPagination {
+    pub param: String,
+}
+
+impl PaginationStrategy for OffsetPagination {
+    fn next_page(&mut self, page: &Page<'_>) -> Result<Option<Url>, String> {
+        if page.items == 0 {
+            return Ok(None);
+        }
+        let current = query_param(page.url, &self.param)?.unwrap_or(0);
+        with_query_param(page.url, &self.param, &(current + page.items as u64).to_string())
+            .map(Some)
+    }
+}
+
+/// Follows RFC 5988 `Link: <url>; rel="next"` response header.
+/// Links to another origin are errors, as their requests would get the client's credentials too.
+#[derive(Debug, Default)]
+pub struct LinkPagination {
+    /// Follows links to other origins as well, for APIs which page through another host.
+    pub cross_origin: bool,
+}
+
+impl PaginationStrategy for LinkPagination {
+    fn next_page(&mut self, page: &Page<'_>) -> Result<Option<Url>, String> {
+        for value in page.headers.get_all(reqwest::header::LINK) {
+            let value = value.to_str().map_err(|err| format!("invalid `Link` header: {err}"))?;
+            if let Some(next) = parse_link_next(value) {
+                let url = page.url.join(next).map_err(|err| format!("invalid next link: {err}"))?;
+                if !self.cross_origin && url.origin() != page.url.origin() {
+                    return Err(format!("next link {url} is on another origin than {}", page.url));
+                }
+                return Ok(Some(url));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Returns the target of the first link with `next` relation type.
+fn parse_link_next(value: &str) -> Option<&str> {
+    split_unquoted(value, ',').find_map(|link| {
+        let (target, params) = link.trim().strip_prefix('<')?.split_once('>')?;
+        let is_next = split_unquoted(params, ';').any(|param| {
+            let Some((name, value)) = param.split_once('=') else { return false };
+            name.trim().eq_ignore_ascii_case("rel")
+                && value
+                    .trim()
+                    .trim_matches('"')
+                    .split_ascii_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("next"))
+        });
+        is_next.then_some(target)
+    })
+}
+
+/// Splits on `separator` outside of `<url>` and quoted strings, like `title="a, b"`.
+fn split_unquoted(value: &str, separator: char) -> impl Iterator<Item = &str> {
+    let (mut in_url, mut in_quotes, mut escaped) = (false, false, false);
+    value.split(move |c| {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' if !in_url => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_url = true,
+            '>' if !in_quotes => in_url = false,
+            _ => return c == separator && !in_url && !in_quotes,
+        }
+        false
+    })
+}
+
+type QueryPairs = Vec<(String, String)>;
+
+fn query_param(url: &Url, param: &str) -> Result<Option<u64>, String> {
+    let pairs: QueryPairs = serde_html_form::from_str(url.query().unwrap_or_default())
+        .map_err(|err| format!("invalid query: {err}"))?;
+    let Some((_, value)) = pairs.into_iter().find(|(name, _)| name == param) else {
+        return Ok(None);
+    };
+    value.parse().map(Some).map_err(|err| format!("invalid `{param}` query param: {err}"))
+}
+
+/// Replaces `param` in the query of `url`, keeping all other params as is.
+fn with_query_param(url: &Url, param: &str, value: &str) -> Result<Url, String> {
+    let mut pairs: QueryPairs = serde_html_form::from_str(url.query().unwrap_or_default())
+        .map_err(|err| format!("invalid query: {err}"))?;
+    pairs.retain(|(name, _)| name != param);
+    pairs.push((param.to_owned(), value.to_owned()));
+
+    let query = serde_html_form::to_string(&pairs).map_err(|err| err.to_string())?;
+    let mut url = url.clone();
+    url.set_query(Some(&query));
+    Ok(url)
+}
+
+/// Options of [`ApiClient::paginate`].
+pub struct Pagination<S> {
+    pub strategy: S,
+    /// JSON pointer to the items array in the page body, empty if the whole body is the array.
+    pub items_pointer: String,
+    /// Safety cap against endless pagination, reaching it ends the stream with an error.
+    pub max_pages: usize,
+}
+
+impl<S: PaginationStrategy> Pagination<S> {
+    pub fn new(strategy: S) -> Self {
+        Self { strategy, items_pointer: String::new(), max_pages: 100 }
+    }
+}
+
+struct State<S> {
+    pagination: Pagination<S>,
+    /// Error if the first URL could not be built.
+    next_url: Result<Option<Url>, ApiClientError>,
+    pages: usize,
+}
+
+impl ApiClient {
+    /// Streams items of all pages, starting with GET `url` with `request` as query params.
+    /// Each page is deserialized with path tracking, same as in [`ApiClient::send_request`].
+    pub fn paginate<'a, Request, Item, S>(
+        &'a self,
+        url: &str,
+        request: &Request,
+        pagination: Pagination<S>,
+    ) -> impl Stream<Item = Result<Item, ApiClientError>> + 'a
+    where
+        Request: serde::Serialize + ?Sized,
+        Item: serde::de::DeserializeOwned + 'a,
+        S: PaginationStrategy + 'a,
+    {
+        let first_url = self.request_url(url, &Query(request)).map(Some);
+        let state = State { pagination, next_url: first_url, pages: 0 };
+
+        let pages = stream::try_unfold(state, move |mut state| async move {
+            let url = match std::mem::replace(&mut state.next_url, Ok(None)) {
+                Ok(Some(url)) => url,
+                Ok(None) => return Ok(None),
+                Err(err) => return Err(err),
+            };
+            if state.pages == state.pagination.max_pages {
+                return Err(ApiClientError::Pagination {
+                    url: url.to_string(),
+                    reason: format!("reached max pages ({})", state.pagination.max_pages),
+                });
+            }
+            state.pages += 1;
+
+            let (items, next_url) = self.fetch_page(url, &mut state.pagination).await?;
+            state.next_url = Ok(next_url);
+            Ok(Some((items, state)))
+        });
+
+        pages.map_ok(|items: Vec<Item>| stream::iter(items.into_iter().map(Ok))).try_flatten()
+    }
+
+    async fn fetch_page<Item, S>(
+        &self,
+        url: Url,
+        pagination: &mut Pagination<S>,
+    ) -> Result<(Vec<Item>, Option<Url>), ApiClientError>
+    where
+        Item: serde::de::DeserializeOwned,
+        S: PaginationStrategy,
+    {
+        let method = reqwest::Method::GET;
+        let response = self.execute(method.clone(), url.clone(), (), RetryMode::default()).await?;
+        let headers = response.headers().clone();
+        let body_bytes = response.bytes().await.map_err(reqwest_middleware::Error::Reqwest)?;
+        let body: Value = deserialize_body(&method, &url, &body_bytes)?;
+
+        let Some(items) = body.pointer(&pagination.items_pointer) else {
+            return Err(ApiClientError::Pagination {
+                url: url.to_string(),
+                reason: format!("no items at `{}`", pagination.items_pointer),
+            });
+        };
+        let items: Vec<Item> = serde_path_to_error::deserialize(items).map_err(|err| {
+            tracing::error!(%method, %url, %err, "Failed to parse page items");
+            ApiClientError::Deserialization {
+                method: method.to_string(),
+                url: url.to_string(),
+                err,
+            }
+        })?;
+
+        let page = Page { url: &url, headers: &headers, body: &body, items: items.len() };
+        let next_url = pagination
+            .strategy
+            .next_page(&page)
+            .map_err(|reason| ApiClientError::Pagination { url: url.to_string(), reason })?;
+
+        Ok((items, next_url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use reqwest_middleware::ClientBuilder;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    fn client(server: &MockServer) -> ApiClient {
+        let client = ClientBuilder::new(reqwest::Client::new()).build();
+        ApiClient::new(client, server.uri().parse().unwrap())
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Item {
+        id: u32,
+    }
+
+    #[derive(serde::Serialize)]
+    struct ListItems {
+        kind: &'static str,
+    }
+
+    fn ids(items: &[Item]) -> Vec<u32> {
+        items.iter().map(|item| item.id).collect()
+    }
+
+    #[tokio::test]
+    async fn cursor_in_body() {
+        let server = MockServer::start().await;
+        Mock::given(query_param("cursor", "c1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [{ "id": 3 }],
+                "meta": { "next": null },
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(path("/items"))
+            .and(query_param("kind", "a"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [{ "id": 1 }, { "id": 2 }],
+                "meta": { "next": "c1" },
+            })))
+            .mount(&server)
+            .await;
+
+        let pagination = Pagination {
+            items_pointer: "/data".into(),
+            ..Pagination::new(CursorPagination {
+                cursor_pointer: "/meta/next".into(),
+                param: "cursor".into(),
+            })
+        };
+        let client = client(&server);
+        let items: Vec<Item> = client
+            .paginate("items", &ListItems { kind: "a" }, pagination)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(ids(&items), [1, 2, 3]);
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[1].url.query(), Some("kind=a&cursor=c1"));
+    }
+
+    #[tokio::test]
+    async fn page_number_in_query() {
+        let server = MockServer::start().await;
+        for (page, body) in
+            [("1", serde_json::json!([{ "id": 1 }])), ("2", serde_json::json!([{ "id": 2 }]))]
+        {
+            Mock::given(query_param("page", page))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(query_param("page", "3"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .mount(&server)
+            .await;
+
+        let pagination = Pagination::new(PagePagination { param: "page".into(), first_page: 1 });
+        let client = client(&server);
+        let items: Vec<Item> =
+            client.paginate("items?page=1", &(), pagination).try_collect().await.unwrap();
+
+        assert_eq!(ids(&items), [1, 2]);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn offset_in_query() {
+        let server = MockServer::start().await;
+        Mock::given(query_param("offset", "2"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!([{ "id": 3 }])),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(query_param("offset", "3"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .mount(&server)
+            .await;
+        Mock::given(path("/items"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!([{ "id": 1 }, { "id": 2 }])),
+            )
+            .mount(&server)
+            .await;
+
+        let pagination = Pagination::new(OffsetPagination { param: "offset".into() });
+        let client = client(&server);
+        let items: Vec<Item> =
+            client.paginate("items", &(), pagination).try_collect().await.unwrap();
+
+        assert_eq!(ids(&items), [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn link_header() {
+        let server = MockServer::start().await;
+        Mock::given(path("/items/2"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!([{ "id": 2 }])),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/items"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(
+                        "Link",
+                        r#"<https://example.com/last>; rel="last", </items/2>; rel="next""#,
+                    )
+                    .set_body_json(serde_json::json!([{ "id": 1 }])),
+            )
+            .mount(&server)
+            .await;
+
+        let client = client(&server);
+        let items: Vec<Item> = client
+            .paginate("items", &(), Pagination::new(LinkPagination::default()))
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(ids(&items), [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn max_pages_ends_with_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Link", "</items>; rel=next")
+                    .set_body_json(serde_json::json!([{ "id": 1 }])),
+            )
+            .mount(&server)
+            .await;
+
+        let pagination = Pagination { max_pages: 3, ..Pagination::new(LinkPagination::default()) };
+        let client = client(&server);
+        let results: Vec<Result<Item, _>> =
+            client.paginate("items", &(), pagination).collect().await;
+
+        assert_eq!(results.len(), 4);
+        assert!(results[..3].iter().all(Result::is_ok));
+        assert!(matches!(results[3], Err(ApiClientError::Pagination { .. })));
+    }
+
+    #[tokio::test]
+    async fn deserialization_error_has_path() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "data": [{ "id": 1 }, { "id": "2" }] })),
+            )
+            .mount(&server)
+            .await;
+
+        let pagination = Pagination {
+            items_pointer: "/data".into(),
+            ..Pagination::new(LinkPagination::default())
+        };
+        let client = client(&server);
+        let err =
+            client.paginate::<_, Item, _>("items", &(), pagination).try_collect::<Vec<_>>().await;
+
+        let Err(ApiClientError::Deserialization { err, .. }) = err else {
+            panic!("expected deserialization error, got {err:?}");
+        };
+        assert_eq!(err.path().to_string(), "[1].id");
+    }
+
+    #[test]
+    fn rejects_links_to_other_origins() {
+        let url: Url = "https://api.example.com/items".parse().unwrap();
+        let next = |link: &str, cross_origin: bool| {
+            let mut headers = HeaderMap::new();
+            headers.insert(reqwest::header::LINK, link.parse().unwrap());
+            let page = Page { url: &url, headers: &headers, body: &Value::Null, items: 1 };
+            LinkPagination { cross_origin }.next_page(&page).map(|url| url.unwrap().to_string())
+        };
+
+        let err = next("<https://evil.example/items>; rel=next", false).unwrap_err();
+        assert!(err.contains("another origin"), "{err}");
+        let cross_origin = next("<https://evil.example/items>; rel=next", true);
+        assert_eq!(cross_origin.unwrap(), "https://evil.example/items");
+        let same_origin = next("<//api.example.com/items?page=2>; rel=next", false);
+        assert_eq!(same_origin.unwrap(), "https://api.example.com/items?page=2");
+    }
+
+    #[test]
+    fn parses_link_header() {
+        assert_eq!(parse_link_next(r#"<a>; rel="next""#), Some("a"));
+        assert_eq!(
+            parse_link_next(r#"<a>; rel="prev", <b>; title="x"; rel="last next""#),
+            Some("b")
+        );
+        assert_eq!(parse_link_next("<a?x=1,2>; rel=NEXT"), Some("a?x=1,2"));
+        assert_eq!(parse_link_next(r#"<a>; title="a, b"; rel="next""#), Some("a"));
+        assert_eq!(
+            parse_link_next(r#"<a>; title="x\"; rel=next, y"; rel="prev", <b>; rel="next""#),
+            Some("b")
+        );
+        assert_eq!(parse_link_next(r#"<a>; rel="prev""#), None);
+        assert_eq!(parse_link_next(""), None);
+    }
+}
@@ -0,0 +1,361 @@
+//! Client-side rate and concurrency limiting middleware for [`reqwest_middleware::ClientWithMiddleware`].
+//!
+//! [`RateLimiter`] spaces requests out with a token bucket and caps the number of requests in flight.
+//! It's cheap to clone and clones share the same limits, so one limiter can guard several clients
+//! of the same upstream API.
+//! Register it after [`super::retry::RetryMiddleware`], so retried attempts are limited too.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use http::Extensions;
+use reqwest_middleware::reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use tokio::sync::Semaphore;
+
+use super::error_body::RateLimit;
+
+#[derive(Debug, Clone)]
+pub struct RateLimitPolicy {
+    /// Number of requests allowed per [`RateLimitPolicy::interval`] on average.
+    pub requests: u32,
+    pub interval: Duration,
+    /// Number of requests that can be sent at once after a quiet period.
+    pub burst: u32,
+    /// Maximum number of requests waiting for response headers at the same time.
+    pub max_in_flight: Option<usize>,
+    /// Pause all requests when a response says the quota is used up, with `Retry-After`
+    /// or `X-RateLimit-Remaining: 0` and `X-RateLimit-Reset`.
+    pub adaptive: bool,
+    /// Longer pauses asked for by responses are cut to this, so a server can't stall the client
+    /// for days.
+    pub max_pause: Duration,
+}
+
+impl Default for RateLimitPolicy {
+    fn default() -> Self {
+        Self {
+            requests: 10,
+            interval: Duration::from_secs(1),
+            burst: 10,
+            max_in_flight: None,
+            adaptive: true,
+            max_pause: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Token bucket, which tokens are reserved in advance, so waiting requests are served in order.
+#[derive(Debug)]
+struct Bucket {
+    /// Negative if tokens are reserved by waiting requests.
+    tokens: f64,
+    /// Time `tokens` were refilled at, in the future if all requests are paused.
+    updated: Instant,
+    /// Tokens per second.
+    rate: f64,
+    burst: f64,
+}
+
+impl Bucket {
+    fn new(policy: &RateLimitPolicy, now: Instant) -> Self {
+        let burst = f64::from(policy.burst.max(1));
+        let rate = f64::from(policy.requests) / policy.interval.as_secs_f64();
+        Self { tokens: burst, updated: now, rate, burst }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if now > self.updated {
+            let elapsed = (now - self.updated).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+            self.updated = now;
+        }
+    }
+
+    /// Takes a token and returns how long to wait before it can be used.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= 1.0;
+        let paused = self.updated.saturating_duration_since(now);
+        if self.tokens >= 0.0 {
+            return paused;
+        }
+        // A very low rate can give a wait longer than `Duration` holds.
+        let wait = Duration::try_from_secs_f64(-self.tokens / self.rate).unwrap_or(Duration::MAX);
+        paused.saturating_add(wait)
+    }
+
+    /// Gives back a token of a request that was never sent.
+    fn unreserve(&mut self, now: Instant) {
+        self.refill(now);
+        self.tokens = (self.tokens + 1.0).min(self.burst);
+    }
+
+    /// Drains the bucket and pauses all requests until `until`.
+    fn pause(&mut self, now: Instant, until: Instant) {
+        self.refill(now);
+        if until > self.updated {
+            self.tokens = self.tokens.min(0.0);
+            self.updated = until;
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RateLimitPolicyError {
+    #[error("Rate limit policy allows no requests, at least 1 per interval is needed")]
+    NoRequests,
+    #[error("Rate limit policy allows no requests in flight, at least 1 is needed")]
+    NoRequestsInFlight,
+}
+
+/// Token reserved for a request, which is given back if the request is cancelled before it is sent.
+struct Reservation<'a> {
+    bucket: &'a Mutex<Bucket>,
+    sent: bool,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if !self.sent {
+            self.bucket.lock().unwrap().unreserve(Instant::now());
+        }
+    }
+}
+
+struct Inner {
+    policy: RateLimitPolicy,
+    bucket: Mutex<Bucket>,
+    in_flight: Option<Semaphore>,
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    inner: Arc<Inner>,
+}
+
+impl RateLimiter {
+    pub fn new(policy: RateLimitPolicy) -> Result<Self, RateLimitPolicyError> {
+        if policy.requests == 0 {
+            return Err(RateLimitPolicyError::NoRequests);
+        }
+        if policy.max_in_flight == Some(0) {
+            return Err(RateLimitPolicyError::NoRequestsInFlight);
+        }
+        let inner = Inner {
+            bucket: Mutex::new(Bucket::new(&policy, Instant::now())),
+            in_flight: policy.max_in_flight.map(Semaphore::new),
+            policy,
+        };
+        Ok(Self { inner: Arc::new(inner) })
+    }
+
+    /// Reserves a token and returns how long to wait before it can be used.
+    fn reserve(&self, now: Instant) -> (Reservation<'_>, Duration) {
+        let wait = self.inner.bucket.lock().unwrap().reserve(now);
+        (Reservation { bucket: &self.inner.bucket, sent: false }, wait)
+    }
+
+    /// Pauses requests according to rate limit headers of a response.
+    fn observe(&self, rate_limit: &RateLimit, now: Instant, wall_now: SystemTime) {
+        let pause = match (rate_limit.retry_after, rate_limit.remaining, rate_limit.reset) {
+            (Some(retry_after), ..) => retry_after,
+            (None, Some(0), Some(reset)) => reset_delay(reset, wall_now),
+            _ => return,
+        };
+        let pause = pause.min(self.inner.policy.max_pause);
+        // Only with a huge `max_pause`, which is as good as no limit.
+        let Some(until) = now.checked_add(pause) else { return };
+        tracing::warn!(?pause, "Upstream rate limit reached, pausing requests");
+        self.inner.bucket.lock().unwrap().pause(now, until);
+    }
+}
+
+/// `X-RateLimit-Reset` is seconds until reset for some APIs and a Unix timestamp for others,
+/// timestamps are told apart by being after 2001-09-09.
+fn reset_delay(reset: u64, now: SystemTime) -> Duration {
+    if reset < 1_000_000_000 {
+        return Duration::from_secs(reset);
+    }
+    (UNIX_EPOCH + Duration::from_secs(reset)).duration_since(now).unwrap_or_default()
+}
+
+#[async_trait::async_trait]
+impl Middleware for RateLimiter {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        // A permit isn't held while waiting for a token, so other requests can use it meanwhile.
+        let (mut reservation, rate_wait) = self.reserve(Instant::now());
+        if !rate_wait.is_zero() {
+            tokio::time::sleep(rate_wait).await;
+        }
+
+        let start = Instant::now();
+        let _permit = match &self.inner.in_flight {
+            Some(in_flight) => Some(in_flight.acquire().await.expect("semaphore is never closed")),
+            None => None,
+        };
+        let in_flight_wait = start.elapsed();
+        reservation.sent = true;
+
+        if !(in_flight_wait + rate_wait).is_zero() {
+            let (method, url) = (req.method(), req.url());
+            tracing::debug!(%method, %url, ?in_flight_wait, ?rate_wait, "Request was rate limited");
+        }
+
+        let response = next.run(req, extensions).await?;
+        if self.inner.policy.adaptive {
+            let rate_limit = RateLimit::from_headers(response.headers());
+            self.observe(&rate_limit, Instant::now(), SystemTime::now());
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest_middleware::ClientBuilder;
+    use reqwest_middleware::reqwest;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::api_client::ApiClient;
+
+    fn policy(requests: u32, interval_ms: u64, burst: u32) -> RateLimitPolicy {
+        let interval = Duration::from_millis(interval_ms);
+        RateLimitPolicy { requests, interval, burst, ..Default::default() }
+    }
+
+    #[test]
+    fn bucket_allows_burst_then_spaces_requests() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(&policy(10, 1000, 2), now);
+
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::from_millis(100));
+        assert_eq!(bucket.reserve(now), Duration::from_millis(200));
+
+        // Reserved tokens are refilled first, the rest up to the burst size.
+        let later = now + Duration::from_millis(500);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn bucket_saturates_very_long_waits() {
+        let now = Instant::now();
+        let policy = RateLimitPolicy { interval: Duration::MAX, ..policy(1, 0, 1) };
+        let mut bucket = Bucket::new(&policy, now);
+
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::MAX);
+    }
+
+    #[test]
+    fn rejects_policies_without_requests() {
+        let err = RateLimiter::new(policy(0, 1000, 5)).err();
+        assert!(matches!(err, Some(RateLimitPolicyError::NoRequests)), "{err:?}");
+        let err =
+            RateLimiter::new(RateLimitPolicy { max_in_flight: Some(0), ..policy(1, 1000, 5) });
+        assert!(matches!(err.err(), Some(RateLimitPolicyError::NoRequestsInFlight)));
+    }
+
+    #[test]
+    fn cancelled_request_gives_token_back() {
+        let limiter = RateLimiter::new(policy(10, 1000, 1)).unwrap();
+        let now = Instant::now();
+
+        let (mut sent, wait) = limiter.reserve(now);
+        assert_eq!(wait, Duration::ZERO);
+        sent.sent = true;
+        drop(sent);
+        let (cancelled, wait) = limiter.reserve(now);
+        assert_eq!(wait, Duration::from_millis(100));
+        drop(cancelled);
+        // Still behind the sent request only.
+        let (_next, wait) = limiter.reserve(now);
+        assert!(wait <= Duration::from_millis(100), "{wait:?}");
+    }
+
+    #[test]
+    fn adapts_to_rate_limit_headers() {
+        let limiter = RateLimiter::new(policy(10, 1000, 5)).unwrap();
+        let now = Instant::now();
+        let wall_now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let reserve = || limiter.inner.bucket.lock().unwrap().reserve(now);
+
+        limiter.observe(&RateLimit { remaining: Some(3), ..Default::default() }, now, wall_now);
+        assert_eq!(reserve(), Duration::ZERO);
+
+        let exhausted =
+            RateLimit { remaining: Some(0), reset: Some(1_700_000_002), ..Default::default() };
+        limiter.observe(&exhausted, now, wall_now);
+        assert_eq!(reserve(), Duration::from_millis(2100));
+
+        let retry_after =
+            RateLimit { retry_after: Some(Duration::from_secs(5)), ..Default::default() };
+        limiter.observe(&retry_after, now, wall_now);
+        // Still behind the token reserved during the previous pause.
+        assert_eq!(reserve(), Duration::from_millis(5200));
+
+        let forever =
+            RateLimit { retry_after: Some(Duration::from_secs(u64::MAX)), ..Default::default() };
+        limiter.observe(&forever, now, wall_now);
+        // Cut to `max_pause`, behind all tokens reserved before.
+        assert_eq!(reserve(), Duration::from_millis(300_300));
+    }
+
+    #[tokio::test]
+    async fn limits_in_flight_requests_across_clients() {
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(1).set_delay(Duration::from_millis(100)),
+            )
+            .expect(4)
+            .mount(&server)
+            .await;
+        let limiter =
+            RateLimiter::new(RateLimitPolicy { max_in_flight: Some(2), ..policy(100, 1000, 100) })
+                .unwrap();
+        let client = || {
+            let client = ClientBuilder::new(reqwest::Client::new()).with(limiter.clone()).build();
+            ApiClient::new(client, server.uri().parse().unwrap())
+        };
+        let (first, second) = (client(), client());
+
+        let start = Instant::now();
+        let requests = [&first, &second, &first, &second]
+            .map(|client| client.send_request::<_, u32>(reqwest::Method::GET, "get", &()));
+        futures::future::try_join_all(requests).await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(200), "{:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn spaces_requests_by_rate() {
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_body_json(1))
+            .expect(4)
+            .mount(&server)
+            .await;
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(RateLimiter::new(policy(1, 50, 2)).unwrap())
+            .build();
+        let client = ApiClient::new(client, server.uri().parse().unwrap());
+
+        let start = Instant::now();
+        for _ in 0..4 {
+            client.send_request::<_, u32>(reqwest::Method::GET, "get", &()).await.unwrap();
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(100), "{:?}", start.elapsed());
+    }
+}
@@ -0,0 +1,415 @@
+//! Retry middleware for [`reqwest_middleware::ClientWithMiddleware`].
+//!
+//! Transient failures (5xx, 408, 429 and connection/timeout errors) are retried with
+//! exponential backoff and full jitter, bounded by [`RetryPolicy::max_attempts`] and
+//! an optional overall [`RetryPolicy::deadline`]. A `Retry-After` of a response is used
+//! instead, up to [`RetryPolicy::max_backoff`].
+
+use std::time::{Duration, Instant};
+
+use http::Extensions;
+use reqwest_middleware::reqwest::{Method, Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next};
+
+use super::error_body::RateLimit;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Upper bound of the backoff before the first retry, doubled on each next retry.
+    pub initial_backoff: Duration,
+    /// Backoff will never grow above this value, even if `Retry-After` asks for more.
+    pub max_backoff: Duration,
+    /// Timeout for a single attempt.
+    pub attempt_timeout: Option<Duration>,
+    /// Timeout for all attempts together, including backoffs.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            attempt_timeout: None,
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with "full jitter": random duration in `[0, min(max, initial * 2^retry))`.
+    fn backoff(&self, retry: u32) -> Duration {
+        let upper =
+            self.initial_backoff.saturating_mul(2u32.saturating_pow(retry)).min(self.max_backoff);
+        if upper.is_zero() {
+            return upper;
+        }
+        rand::random_range(Duration::ZERO..upper)
+    }
+}
+
+/// Per-call override of which requests can be retried.
+/// Can be attached with [`reqwest_middleware::RequestBuilder::with_extension`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RetryMode {
+    /// Retry only idempotent methods.
+    #[default]
+    Idempotent,
+    /// Retry regardless of the method.
+    Always,
+    /// Never retry.
+    Never,
+}
+
+impl RetryMode {
+    fn allows(self, method: &Method) -> bool {
+        match self {
+            RetryMode::Idempotent => is_idempotent(method),
+            RetryMode::Always => true,
+            RetryMode::Never => false,
+        }
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS | Method::TRACE
+    )
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+fn is_retryable_error(err: &reqwest_middleware::Error) -> bool {
+    match err {
+        reqwest_middleware::Error::Reqwest(err) => err.is_connect() || err.is_timeout(),
+        reqwest_middleware::Error::Middleware(_) => false,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AttemptOutcome {
+    Status(StatusCode),
+    Error(String),
+}
+
+impl std::fmt::Display for AttemptOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttemptOutcome::Status(status) => write!(f, "{status}"),
+            AttemptOutcome::Error(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+/// Single attempt made by [`RetryMiddleware`].
+#[derive(Debug, Clone)]
+pub struct RetryAttempt {
+    /// Starting from 1.
+    pub attempt: u32,
+    pub outcome: AttemptOutcome,
+    /// Time since the first attempt was started.
+    pub elapsed: Duration,
+    /// Backoff before the next attempt, `None` for the last one.
+    pub backoff: Option<Duration>,
+}
+
+/// All attempts of a retried request.
+/// Attached to the [`Response`] extensions when there was more than one attempt.
+#[derive(Debug, Clone, Default)]
+pub struct RetryAttempts(pub Vec<RetryAttempt>);
+
+/// Returned as [`reqwest_middleware::Error::Middleware`] when all attempts failed with an error.
+#[derive(Debug, thiserror::Error)]
+#[error("Retries exhausted after {} attempts: {source}", attempts.len())]
+pub struct RetryError {
+    pub attempts: Vec<RetryAttempt>,
+    pub source: reqwest_middleware::Error,
+}
+
+pub struct RetryMiddleware {
+    policy: RetryPolicy,
+}
+
+impl RetryMiddleware {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self { policy }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RetryMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let mode = extensions.get::<RetryMode>().copied().unwrap_or_default();
+        if !mode.allows(req.method()) {
+            return next.run(req, extensions).await;
+        }
+
+        let start = Instant::now();
+        let mut attempts = Vec::new();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let remaining =
+                self.policy.deadline.map(|deadline| deadline.saturating_sub(start.elapsed()));
+
+            // Streamed bodies cannot be cloned, so such requests are sent only once.
+            let Some(mut attempt_req) = req.try_clone() else {
+                return next.run(req, extensions).await;
+            };
+            *attempt_req.timeout_mut() = match (self.policy.attempt_timeout, remaining) {
+                (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+                (timeout, remaining) => timeout.or(remaining),
+            };
+
+            let result = next.clone().run(attempt_req, extensions).await;
+            let (outcome, retryable) = match &result {
+                Ok(response) => (
+                    AttemptOutcome::Status(response.status()),
+                    is_retryable_status(response.status()),
+                ),
+                Err(err) => (AttemptOutcome::Error(err.to_string()), is_retryable_error(err)),
+            };
+
+            // The server knows better when it is ready again, like after 429 or 503.
+            let retry_after = match &result {
+                Ok(response) => RateLimit::from_headers(response.headers()).retry_after,
+                Err(_) => None,
+            };
+            let backoff = match retry_after {
+                Some(retry_after) => retry_after.min(self.policy.max_backoff),
+                None => self.policy.backoff(attempt - 1),
+            };
+            let out_of_time =
+                self.policy.deadline.is_some_and(|deadline| start.elapsed() + backoff >= deadline);
+            let done = !retryable || attempt >= self.policy.max_attempts || out_of_time;
+            attempts.push(RetryAttempt {
+                attempt,
+                outcome,
+                elapsed: start.elapsed(),
+                backoff: (!done).then_some(backoff),
+            });
+
+            if done {
+                return match result {
+                    Ok(mut response) => {
+                        if attempts.len() > 1 {
+                            response.extensions_mut().insert(RetryAttempts(attempts));
+                        }
+                        Ok(response)
+                    }
+                    Err(err) if attempts.len() > 1 => {
+                        Err(reqwest_middleware::Error::middleware(RetryError {
+                            attempts,
+                            source: err,
+                        }))
+                    }
+                    Err(err) => Err(err),
+                };
+            }
+
+            tracing::warn!(
+                method = %req.method(),
+                url = %req.url(),
+                attempt,
+                outcome = %attempts[attempts.len() - 1].outcome,
+                ?backoff,
+                "Retrying request"
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use reqwest_middleware::ClientBuilder;
+    use reqwest_middleware::reqwest;
+
+    use super::*;
+    use crate::api_client::{ApiClient, ApiClientError};
+
+    fn client(base_url: &str, policy: RetryPolicy) -> ApiClient {
+        let client =
+            ClientBuilder::new(reqwest::Client::new()).with(RetryMiddleware::new(policy)).build();
+        ApiClient::new(client, base_url.parse().unwrap())
+    }
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy { max_attempts, initial_backoff: Duration::ZERO, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn retries_server_error_until_success() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/get"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/get"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(42))
+            .mount(&server)
+            .await;
+
+        let client = client(&server.uri(), policy(3));
+        let response: i32 = client.send_request(reqwest::Method::GET, "get", &()).await.unwrap();
+
+        assert_eq!(response, 42);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn records_attempts_when_retries_exhausted() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("boom"))
+            .mount(&server)
+            .await;
+
+        let client = client(&server.uri(), policy(3));
+        let err =
+            client.send_request::<_, serde_json::Value>(reqwest::Method::GET, "get", &()).await;
+
+        let Err(ApiClientError::Retry { attempts, last, .. }) = err else {
+            panic!("expected retry error, got {err:?}");
+        };
+        assert_eq!(attempts.len(), 3);
+        assert_eq!(attempts.iter().map(|a| a.attempt).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(attempts[..2].iter().all(|a| a.backoff.is_some()));
+        assert!(attempts[2].backoff.is_none());
+        assert!(
+            matches!(*last, ApiClientError::Api { ref body, .. } if body.to_string() == "boom")
+        );
+    }
+
+    #[tokio::test]
+    async fn waits_for_retry_after() {
+        let server = MockServer::start().await;
+        Mock::given(path("/now"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/later"))
+            .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "3600"))
+            .mount(&server)
+            .await;
+
+        // Jitter would take up to 10 seconds.
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let client = client(&server.uri(), policy);
+        for (path, backoff) in [("now", Duration::ZERO), ("later", Duration::from_millis(50))] {
+            let err =
+                client.send_request::<_, serde_json::Value>(reqwest::Method::GET, path, &()).await;
+            let Err(ApiClientError::Retry { attempts, .. }) = err else {
+                panic!("expected retry error, got {err:?}");
+            };
+            assert_eq!(attempts[0].backoff, Some(backoff), "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_non_idempotent_unless_opted_in() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(502)).mount(&server).await;
+
+        let client = client(&server.uri(), policy(3));
+        let err =
+            client.send_request::<_, serde_json::Value>(reqwest::Method::POST, "post", &42).await;
+        assert!(matches!(err, Err(ApiClientError::Api { .. })));
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        let err = client
+            .send_request_with_retry::<_, serde_json::Value>(
+                reqwest::Method::POST,
+                "post",
+                &42,
+                RetryMode::Always,
+            )
+            .await;
+        assert!(
+            matches!(err, Err(ApiClientError::Retry { ref attempts, .. }) if attempts.len() == 3)
+        );
+        assert_eq!(server.received_requests().await.unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(ResponseTemplate::new(404)).mount(&server).await;
+
+        let client = client(&server.uri(), policy(3));
+        let err =
+            client.send_request::<_, serde_json::Value>(reqwest::Method::GET, "get", &()).await;
+
+        assert!(matches!(err, Err(ApiClientError::Api { .. })));
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_connection_error() {
+        // Take a free port and release it, so nobody listens there.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = client(&base_url, policy(2));
+        let err =
+            client.send_request::<_, serde_json::Value>(reqwest::Method::GET, "get", &()).await;
+
+        let Err(ApiClientError::Retry { attempts, last, .. }) = err else {
+            panic!("expected retry error, got {err:?}");
+        };
+        assert_eq!(attempts.len(), 2);
+        assert!(matches!(*last, ApiClientError::Network(_)));
+    }
+
+    #[tokio::test]
+    async fn times_out_each_attempt_and_respects_deadline() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let policy = RetryPolicy {
+            max_attempts: 100,
+            initial_backoff: Duration::ZERO,
+            attempt_timeout: Some(Duration::from_millis(50)),
+            deadline: Some(Duration::from_millis(300)),
+            ..Default::default()
+        };
+        let client = client(&server.uri(), policy);
+        let started = std::time::Instant::now();
+        let err =
+            client.send_request::<_, serde_json::Value>(reqwest::Method::GET, "get", &()).await;
+
+        let Err(ApiClientError::Retry { attempts, .. }) = err else {
+            panic!("expected retry error, got {err:?}");
+        };
+        assert!(attempts.len() > 1 && attempts.len() < 100);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
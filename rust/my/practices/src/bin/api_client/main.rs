@@ -0,0 +1,46 @@
+use my_practices::api_client::cassette::CassetteMiddleware;
+use my_practices::api_client::retry::{RetryMiddleware, RetryPolicy};
+use my_practices::api_client::{ApiClient, Endpoint};
+use reqwest_middleware::{ClientBuilder, reqwest};
+
+#[allow(dead_code)]
+mod httpbin {
+    include!(concat!(env!("OUT_DIR"), "/httpbin.rs"));
+}
+
+#[derive(serde::Serialize)]
+struct GetAnything {
+    id: u32,
+    tags: Vec<String>,
+}
+
+impl Endpoint for GetAnything {
+    const METHOD: reqwest::Method = reqwest::Method::GET;
+    const PATH: &'static str = "anything/{id}";
+    type Response = serde_json::Value;
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let client = reqwest::Client::new();
+    let mut client = ClientBuilder::new(client).with(RetryMiddleware::new(RetryPolicy::default()));
+
+    // Run with `RECORD=cassette.json` once, and then with `REPLAY=cassette.json` to work offline.
+    if let Ok(path) = std::env::var("RECORD") {
+        client = client.with(CassetteMiddleware::record(path));
+    } else if let Ok(path) = std::env::var("REPLAY") {
+        client = client.with(CassetteMiddleware::replay(path).unwrap());
+    }
+
+    let client = client.build();
+    let client = ApiClient::new(client, "http://httpbin.org".try_into().unwrap());
+    let response =
+        client.call(GetAnything { id: 1, tags: vec!["a".into(), "b".into()] }).await.unwrap();
+    println!("{response}");
+
+    // The same endpoint through the client generated from `httpbin.yaml` by `build.rs`.
+    let client = httpbin::HttpbinClient::new(client);
+    let request = httpbin::GetAnythingRequest { id: 1, tags: Some(vec!["a".into(), "b".into()]) };
+    let response = client.get_anything(&request).await.unwrap();
+    println!("{} {} {:?}", response.method, response.url, response.args);
+}
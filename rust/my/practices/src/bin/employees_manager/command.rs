@@ -0,0 +1,522 @@
+//! Commands of the manager. Keywords are case-insensitive, names with spaces are quoted:
+//! `add "Sally Ann" to "Research and Development" as "Data Engineer" since 2024-01-15`.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+
+use super::company::Department;
+use super::exchange::Format;
+
+/// Employees are referred by names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Add {
+        employee: String,
+        department: Department,
+        title: Option<String>,
+        since: Option<NaiveDate>,
+    },
+    Move {
+        employee: String,
+        department: Department,
+        on: Option<NaiveDate>,
+    },
+    SetManager {
+        employee: String,
+        manager: Option<String>,
+    },
+    SetTitle {
+        employee: String,
+        title: Option<String>,
+    },
+    RemoveDepartment(Department),
+    RemoveEmployee(String),
+    ListDepartment(Department),
+    ListEmployee(String),
+    ListDepartments,
+    ListEmployees,
+    ListDepartmentsWithEmployees,
+    ListEmployeesWithDepartments,
+    ListReports(String),
+    WhoManages(String),
+    ShowOrgChart,
+    ShowHistory(String),
+    /// To stdout if `path` is `-`.
+    Export {
+        format: Format,
+        path: PathBuf,
+    },
+    Import {
+        format: Format,
+        path: PathBuf,
+    },
+    Undo(usize),
+    Redo(usize),
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    #[error("Missing closing quote")]
+    UnterminatedQuote,
+
+    #[error("Expected {expected}, found {}{}", Found(found), DidYouMean(suggestion))]
+    Unexpected { expected: String, found: Option<String>, suggestion: Option<&'static str> },
+
+    #[error("{0:?} is not a number of changes")]
+    InvalidCount(String),
+
+    #[error("{0:?} is not a date like 2024-01-31")]
+    InvalidDate(String),
+}
+
+struct Found<'a>(&'a Option<String>);
+
+impl fmt::Display for Found<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(found) => write!(f, "{found:?}"),
+            None => f.write_str("nothing"),
+        }
+    }
+}
+
+struct DidYouMean<'a>(&'a Option<&'static str>);
+
+impl fmt::Display for DidYouMean<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(suggestion) => write!(f, ", did you mean {suggestion:?}?"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    /// Quoted tokens are always names, even if they look like keywords.
+    quoted: bool,
+}
+
+/// Splits by whitespace, except inside `"..."`, where `\"` and `\\` are escapes.
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut text = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => text.push(chars.next().ok_or(ParseError::UnterminatedQuote)?),
+                    Some(c) => text.push(c),
+                    None => return Err(ParseError::UnterminatedQuote),
+                }
+            }
+            tokens.push(Token { text, quoted: true });
+        } else {
+            text.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '"') {
+                text.push(c);
+            }
+            tokens.push(Token { text, quoted: false });
+        }
+    }
+    Ok(tokens)
+}
+
+impl std::str::FromStr for Command {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { tokens: tokenize(input)?.into_iter().collect() };
+        let keywords = [
+            "Add", "Move", "Set", "Clear", "Remove", "List", "Who", "Show", "Export", "Import",
+            "Undo", "Redo", "Quit",
+        ];
+        let command = match parser.keyword(&keywords)? {
+            "Add" => {
+                let employee = parser.name("employee name")?;
+                parser.keyword(&["to"])?;
+                let department = parser.name("department name")?;
+                let (mut title, mut since) = (None, None);
+                while let Some(option) = parser.option(&["as", "since"])? {
+                    match option {
+                        "as" => title = Some(parser.name("title")?),
+                        _ => since = Some(parser.date()?),
+                    }
+                }
+                Command::Add { employee, department, title, since }
+            }
+            "Move" => {
+                let employee = parser.name("employee name")?;
+                parser.keyword(&["to"])?;
+                let department = parser.name("department name")?;
+                let on = match parser.option(&["on"])? {
+                    Some(_) => Some(parser.date()?),
+                    None => None,
+                };
+                Command::Move { employee, department, on }
+            }
+            "Set" => {
+                let field = parser.keyword(&["manager", "title"])?;
+                parser.keyword(&["of"])?;
+                let employee = parser.name("employee name")?;
+                parser.keyword(&["to"])?;
+                match field {
+                    "manager" => Command::SetManager {
+                        employee,
+                        manager: Some(parser.name("manager name")?),
+                    },
+                    _ => Command::SetTitle { employee, title: Some(parser.name("title")?) },
+                }
+            }
+            "Clear" => {
+                let field = parser.keyword(&["manager", "title"])?;
+                parser.keyword(&["of"])?;
+                let employee = parser.name("employee name")?;
+                match field {
+                    "manager" => Command::SetManager { employee, manager: None },
+                    _ => Command::SetTitle { employee, title: None },
+                }
+            }
+            "Remove" => {
+                let name = parser.name("department or employee name")?;
+                match parser.keyword(&["department", "employee"])? {
+                    "department" => Command::RemoveDepartment(name),
+                    _ => Command::RemoveEmployee(name),
+                }
+            }
+            "List" => parser.list()?,
+            "Who" => {
+                parser.keyword(&["manages"])?;
+                Command::WhoManages(parser.name("employee name")?)
+            }
+            "Show" => match parser.keyword(&["org", "history"])? {
+                "org" => {
+                    parser.keyword(&["chart"])?;
+                    Command::ShowOrgChart
+                }
+                _ => {
+                    parser.keyword(&["of"])?;
+                    Command::ShowHistory(parser.name("employee name")?)
+                }
+            },
+            "Export" => {
+                let format = parser.format()?;
+                Command::Export { format, path: parser.name("file path")?.into() }
+            }
+            "Import" => {
+                let format = parser.format()?;
+                Command::Import { format, path: parser.name("file path")?.into() }
+            }
+            "Undo" => Command::Undo(parser.count()?),
+            "Redo" => Command::Redo(parser.count()?),
+            _ => Command::Quit,
+        };
+        parser.end()?;
+        Ok(command)
+    }
+}
+
+const END: &str = "end of command (quote names with spaces)";
+
+struct Parser {
+    tokens: std::collections::VecDeque<Token>,
+}
+
+impl Parser {
+    fn keyword(&mut self, keywords: &[&'static str]) -> Result<&'static str, ParseError> {
+        self.keyword_or_end(keywords, false)
+    }
+
+    /// Optional keyword, `None` at the end of the command.
+    fn option(&mut self, keywords: &[&'static str]) -> Result<Option<&'static str>, ParseError> {
+        match self.tokens.is_empty() {
+            true => Ok(None),
+            false => self.keyword_or_end(keywords, true).map(Some),
+        }
+    }
+
+    fn keyword_or_end(
+        &mut self,
+        keywords: &[&'static str],
+        or_end: bool,
+    ) -> Result<&'static str, ParseError> {
+        let token = self.tokens.pop_front();
+        let found = token.as_ref().filter(|token| !token.quoted).and_then(|token| {
+            keywords.iter().find(|keyword| keyword.eq_ignore_ascii_case(&token.text))
+        });
+        if let Some(keyword) = found {
+            return Ok(keyword);
+        }
+
+        let suggestion = token.as_ref().and_then(|token| {
+            let text = token.text.to_lowercase();
+            keywords
+                .iter()
+                .map(|keyword| (distance(&keyword.to_lowercase(), &text), *keyword))
+                // Short words need closer matches, so that "and" is not taken for "as".
+                .filter(|(distance, _)| *distance <= (text.chars().count() / 3).clamp(1, 2))
+                .min()
+                .map(|(_, keyword)| keyword)
+        });
+        let mut expected: Vec<_> = keywords.iter().map(|keyword| format!("{keyword:?}")).collect();
+        if or_end {
+            expected.push(END.into());
+        }
+        let expected = match expected.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, init)) => format!("{} or {last}", init.join(", ")),
+            None => unreachable!("there is always a keyword to expect"),
+        };
+        Err(ParseError::Unexpected { expected, found: token.map(|token| token.text), suggestion })
+    }
+
+    fn name(&mut self, what: &str) -> Result<String, ParseError> {
+        self.tokens.pop_front().map(|token| token.text).ok_or_else(|| ParseError::Unexpected {
+            expected: what.into(),
+            found: None,
+            suggestion: None,
+        })
+    }
+
+    /// `List departments [with employees]`, `List employees [with departments]`,
+    /// `List reports of <name>` or `List <name> department|employee`.
+    fn list(&mut self) -> Result<Command, ParseError> {
+        let is_name = self.tokens.front().is_some_and(|token| token.quoted)
+            || (self.tokens.len() == 2 && !self.tokens[1].text.eq_ignore_ascii_case("with"));
+        if is_name {
+            let name = self.name("department or employee name")?;
+            return match self.keyword(&["department", "employee"])? {
+                "department" => Ok(Command::ListDepartment(name)),
+                _ => Ok(Command::ListEmployee(name)),
+            };
+        }
+
+        let with_all = self.tokens.len() > 1;
+        let command = match self.keyword(&["departments", "employees", "reports"])? {
+            "reports" => {
+                self.keyword(&["of"])?;
+                Command::ListReports(self.name("employee name")?)
+            }
+            "departments" if with_all => {
+                self.keyword(&["with"])?;
+                self.keyword(&["employees"])?;
+                Command::ListDepartmentsWithEmployees
+            }
+            "departments" => Command::ListDepartments,
+            "employees" if with_all => {
+                self.keyword(&["with"])?;
+                self.keyword(&["departments"])?;
+                Command::ListEmployeesWithDepartments
+            }
+            _ => Command::ListEmployees,
+        };
+        Ok(command)
+    }
+
+    fn format(&mut self) -> Result<Format, ParseError> {
+        match self.keyword(&["csv", "json", "ini"])? {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Ok(Format::Ini),
+        }
+    }
+
+    fn date(&mut self) -> Result<NaiveDate, ParseError> {
+        let date = self.name("date")?;
+        NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_| ParseError::InvalidDate(date))
+    }
+
+    /// Optional, 1 by default.
+    fn count(&mut self) -> Result<usize, ParseError> {
+        match self.tokens.pop_front() {
+            Some(token) => token.text.parse().map_err(|_| ParseError::InvalidCount(token.text)),
+            None => Ok(1),
+        }
+    }
+
+    fn end(&mut self) -> Result<(), ParseError> {
+        match self.tokens.pop_front() {
+            Some(token) => Err(ParseError::Unexpected {
+                expected: END.into(),
+                found: Some(token.text),
+                suggestion: None,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Levenshtein distance, where swapped neighbours are one edit too.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d: Vec<Vec<usize>> = (0..=a.len())
+        .map(|i| {
+            (0..=b.len())
+                .map(|j| {
+                    if i == 0 {
+                        j
+                    } else if j == 0 {
+                        i
+                    } else {
+                        0
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = d[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = substitution.min(d[i - 1][j] + 1).min(d[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Command, String> {
+        input.parse().map_err(|err: ParseError| err.to_string())
+    }
+
+    #[test]
+    fn parses_quoted_names_and_any_case() {
+        assert_eq!(
+            parse(r#"add "Sally Ann" TO "Research and Development""#),
+            Ok(Command::Add {
+                employee: "Sally Ann".into(),
+                department: "Research and Development".into(),
+                title: None,
+                since: None,
+            })
+        );
+        assert_eq!(
+            parse(r#"Add "Dwayne \"The Rock\" Johnson" to Movies"#),
+            Ok(Command::Add {
+                employee: r#"Dwayne "The Rock" Johnson"#.into(),
+                department: "Movies".into(),
+                title: None,
+                since: None,
+            })
+        );
+        assert_eq!(parse("remove Sales DEPARTMENT"), Ok(Command::RemoveDepartment("Sales".into())));
+        assert_eq!(parse("Remove Sally employee"), Ok(Command::RemoveEmployee("Sally".into())));
+        assert_eq!(parse("quit"), Ok(Command::Quit));
+        assert_eq!(parse("Undo"), Ok(Command::Undo(1)));
+        assert_eq!(parse("redo 3"), Ok(Command::Redo(3)));
+    }
+
+    #[test]
+    fn parses_employee_details() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15);
+        assert_eq!(
+            parse(r#"Add Sally to Engineering since 2024-01-15 as "Data Engineer""#),
+            Ok(Command::Add {
+                employee: "Sally".into(),
+                department: "Engineering".into(),
+                title: Some("Data Engineer".into()),
+                since: date,
+            })
+        );
+        assert_eq!(
+            parse("move Sally to Research on 2024-01-15"),
+            Ok(Command::Move { employee: "Sally".into(), department: "Research".into(), on: date })
+        );
+        assert_eq!(
+            parse("Set manager of Sally to Alice"),
+            Ok(Command::SetManager { employee: "Sally".into(), manager: Some("Alice".into()) })
+        );
+        assert_eq!(
+            parse("clear title of Sally"),
+            Ok(Command::SetTitle { employee: "Sally".into(), title: None })
+        );
+        assert_eq!(parse("List reports of Alice"), Ok(Command::ListReports("Alice".into())));
+        assert_eq!(parse("Who manages Sally"), Ok(Command::WhoManages("Sally".into())));
+        assert_eq!(parse("Show org chart"), Ok(Command::ShowOrgChart));
+        assert_eq!(parse("Show history of Sally"), Ok(Command::ShowHistory("Sally".into())));
+        assert_eq!(
+            parse(r#"Export INI "backups/company.ini""#),
+            Ok(Command::Export { format: Format::Ini, path: "backups/company.ini".into() })
+        );
+        assert_eq!(
+            parse("import csv people.csv"),
+            Ok(Command::Import { format: Format::Csv, path: "people.csv".into() })
+        );
+        assert_eq!(
+            parse("Export xml out.xml"),
+            Err(r#"Expected "csv", "json" or "ini", found "xml""#.into())
+        );
+        assert_eq!(
+            parse("Add Sally to Engineering since yesterday"),
+            Err(r#""yesterday" is not a date like 2024-01-31"#.into())
+        );
+    }
+
+    #[test]
+    fn parses_lists() {
+        assert_eq!(parse("List departments"), Ok(Command::ListDepartments));
+        assert_eq!(parse("list Employees"), Ok(Command::ListEmployees));
+        assert_eq!(
+            parse("List departments with employees"),
+            Ok(Command::ListDepartmentsWithEmployees)
+        );
+        assert_eq!(
+            parse("List employees with departments"),
+            Ok(Command::ListEmployeesWithDepartments)
+        );
+        assert_eq!(parse("List Sales department"), Ok(Command::ListDepartment("Sales".into())));
+        assert_eq!(parse("List Sally employee"), Ok(Command::ListEmployee("Sally".into())));
+        // Quoted keywords are names.
+        assert_eq!(
+            parse(r#"List "departments" department"#),
+            Ok(Command::ListDepartment("departments".into()))
+        );
+    }
+
+    #[test]
+    fn suggests_keywords() {
+        assert_eq!(
+            parse("Ad Sally to Sales"),
+            Err(r#"Expected "Add", "Move", "Set", "Clear", "Remove", "List", "Who", "Show", "Export", "Import", "Undo", "Redo" or "Quit", found "Ad", did you mean "Add"?"#.into())
+        );
+        assert_eq!(
+            parse("Add Sally ot Sales"),
+            Err(r#"Expected "to", found "ot", did you mean "to"?"#.into())
+        );
+        assert_eq!(
+            parse("List departmnts"),
+            Err(r#"Expected "departments", "employees" or "reports", found "departmnts", did you mean "departments"?"#.into())
+        );
+        assert_eq!(
+            parse("Remove Sales dept"),
+            Err(r#"Expected "department" or "employee", found "dept""#.into())
+        );
+        assert_eq!(parse("Add Sally to"), Err("Expected department name, found nothing".into()));
+    }
+
+    #[test]
+    fn reports_malformed_input() {
+        assert_eq!(parse(r#"Add "Sally to Sales"#), Err("Missing closing quote".into()));
+        assert_eq!(parse("Add Sally Ann to Sales"), Err(r#"Expected "to", found "Ann""#.into()));
+        assert_eq!(
+            parse("Add Sally to Research and Development"),
+            Err(r#"Expected "as", "since" or end of command (quote names with spaces), found "and""#.into())
+        );
+        assert_eq!(parse("Undo all"), Err(r#""all" is not a number of changes"#.into()));
+    }
+}
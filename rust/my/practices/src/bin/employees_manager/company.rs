@@ -0,0 +1,382 @@
+//! Employees with their departments and managers, changed only by [`Change`]s, so that every change
+//! can be journaled and reverted.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+// Each `Employee` name and `Department` are unique.
+// So, one `Employee` can be only in one `Department` at a time.
+
+pub type EmployeeId = u32;
+pub type Department = String;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Employee {
+    pub id: EmployeeId,
+    pub name: String,
+    pub title: Option<String>,
+    pub start_date: NaiveDate,
+    pub manager: Option<EmployeeId>,
+    /// Departments from the first one, the last one is the current.
+    pub history: Vec<Assignment>,
+}
+
+impl Employee {
+    pub fn department(&self) -> &Department {
+        &self.history.last().expect("Employee is always in some department").department
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Assignment {
+    pub department: Department,
+    pub since: NaiveDate,
+}
+
+/// Change of the company, which knows enough to be reverted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Change {
+    Add {
+        employee: Employee,
+        new_department: bool,
+    },
+    Move {
+        id: EmployeeId,
+        assignment: Assignment,
+        new_department: bool,
+    },
+    SetManager {
+        id: EmployeeId,
+        manager: Option<EmployeeId>,
+        previous: Option<EmployeeId>,
+    },
+    SetTitle {
+        id: EmployeeId,
+        title: Option<String>,
+        previous: Option<String>,
+    },
+    /// `reports` lose their manager.
+    RemoveEmployee {
+        employee: Employee,
+        reports: Vec<EmployeeId>,
+    },
+    /// Employees of other departments in `reports` lose their managers.
+    RemoveDepartment {
+        department: Department,
+        employees: Vec<Employee>,
+        reports: Vec<(EmployeeId, EmployeeId)>,
+    },
+    /// `new_departments` are the ones the company has no employees in yet.
+    Import {
+        employees: Vec<Employee>,
+        new_departments: Vec<Department>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Reporting chain has a cycle: {}", .0.join(" → "))]
+pub struct CycleError(pub Vec<String>);
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Company {
+    pub departments: BTreeSet<Department>,
+    pub employees: BTreeMap<EmployeeId, Employee>,
+    /// Never decreases, so that IDs are not reused.
+    pub next_id: EmployeeId,
+}
+
+impl Company {
+    pub fn find(&self, name: &str) -> Option<&Employee> {
+        self.employees.values().find(|employee| employee.name == name)
+    }
+
+    pub fn employees_of<'a>(&'a self, department: &str) -> impl Iterator<Item = &'a Employee> {
+        self.employees.values().filter(move |employee| employee.department() == department)
+    }
+
+    /// Direct reports of the employee.
+    pub fn reports(&self, id: EmployeeId) -> impl Iterator<Item = &Employee> {
+        self.employees.values().filter(move |employee| employee.manager == Some(id))
+    }
+
+    /// Managers of the employee from the closest one.
+    pub fn managers(&self, id: EmployeeId) -> Result<Vec<&Employee>, CycleError> {
+        let mut chain = vec![&self.employees[&id]];
+        while let Some(manager) = chain.last().and_then(|employee| employee.manager) {
+            let Some(manager) = self.employees.get(&manager) else {
+                break;
+            };
+            if let Some(start) = chain.iter().position(|employee| employee.id == manager.id) {
+                let mut names: Vec<_> =
+                    chain[start..].iter().map(|employee| employee.name.clone()).collect();
+                names.push(manager.name.clone());
+                return Err(CycleError(names));
+            }
+            chain.push(manager);
+        }
+        chain.remove(0);
+        Ok(chain)
+    }
+
+    /// Whether `manager` managing `id` would make a cycle.
+    pub fn check_manager(&self, id: EmployeeId, manager: EmployeeId) -> Result<(), CycleError> {
+        let managers = self.managers(manager)?;
+        let mut chain = vec![&self.employees[&manager]];
+        chain.extend(managers);
+        match chain.iter().position(|employee| employee.id == id) {
+            Some(end) => {
+                let mut names = vec![self.employees[&id].name.clone()];
+                names.extend(chain[..=end].iter().map(|employee| employee.name.clone()));
+                Err(CycleError(names))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Indented tree from employees without managers:
+    ///
+    /// ```text
+    /// Alice (CEO, Management)
+    /// ├─ Bob (Engineering)
+    /// │  └─ Carol (Engineer, Engineering)
+    /// └─ Dave (Sales)
+    /// ```
+    pub fn org_chart(&self) -> Result<String, CycleError> {
+        let mut chart = String::new();
+        let mut shown = BTreeSet::new();
+        let roots = self.employees.values().filter(|employee| {
+            employee.manager.is_none_or(|manager| !self.employees.contains_key(&manager))
+        });
+        for root in roots {
+            self.write_subtree(&mut chart, root, "", None, &mut shown);
+        }
+        // Employees in cycles can't be reached from the ones without managers.
+        if let Some(employee) =
+            self.employees.values().find(|employee| !shown.contains(&employee.id))
+        {
+            self.managers(employee.id)?;
+        }
+        Ok(chart.trim_end().to_owned())
+    }
+
+    fn write_subtree(
+        &self,
+        chart: &mut String,
+        employee: &Employee,
+        indent: &str,
+        last: Option<bool>,
+        shown: &mut BTreeSet<EmployeeId>,
+    ) {
+        shown.insert(employee.id);
+        let (branch, nested) = match last {
+            None => ("", String::new()),
+            Some(false) => ("├─ ", format!("{indent}│  ")),
+            Some(true) => ("└─ ", format!("{indent}   ")),
+        };
+        let details = match &employee.title {
+            Some(title) => format!("{title}, {}", employee.department()),
+            None => employee.department().clone(),
+        };
+        writeln!(chart, "{indent}{branch}{} ({details})", employee.name).unwrap();
+
+        let reports: Vec<_> = self.reports(employee.id).collect();
+        for (i, report) in reports.iter().enumerate() {
+            self.write_subtree(chart, report, &nested, Some(i + 1 == reports.len()), shown);
+        }
+    }
+
+    pub fn apply(&mut self, change: &Change) {
+        match change {
+            Change::Add { employee, .. } => {
+                self.departments.insert(employee.department().clone());
+                self.employees.insert(employee.id, employee.clone());
+                self.next_id = self.next_id.max(employee.id + 1);
+            }
+            Change::Move { id, assignment, .. } => {
+                self.departments.insert(assignment.department.clone());
+                self.employee_mut(*id).history.push(assignment.clone());
+            }
+            Change::SetManager { id, manager, .. } => self.employee_mut(*id).manager = *manager,
+            Change::SetTitle { id, title, .. } => self.employee_mut(*id).title = title.clone(),
+            Change::RemoveEmployee { employee, reports } => {
+                self.employees.remove(&employee.id);
+                for report in reports {
+                    self.employee_mut(*report).manager = None;
+                }
+            }
+            Change::RemoveDepartment { department, employees, reports } => {
+                self.departments.remove(department);
+                for employee in employees {
+                    self.employees.remove(&employee.id);
+                }
+                for (report, _) in reports {
+                    self.employee_mut(*report).manager = None;
+                }
+            }
+            Change::Import { employees, new_departments } => {
+                self.departments.extend(new_departments.iter().cloned());
+                for employee in employees {
+                    self.employees.insert(employee.id, employee.clone());
+                    self.next_id = self.next_id.max(employee.id + 1);
+                }
+            }
+        }
+    }
+
+    pub fn revert(&mut self, change: &Change) {
+        match change {
+            Change::Add { employee, new_department } => {
+                self.employees.remove(&employee.id);
+                if *new_department {
+                    self.departments.remove(employee.department());
+                }
+            }
+            Change::Move { id, assignment, new_department } => {
+                self.employee_mut(*id).history.pop();
+                if *new_department {
+                    self.departments.remove(&assignment.department);
+                }
+            }
+            Change::SetManager { id, previous, .. } => self.employee_mut(*id).manager = *previous,
+            Change::SetTitle { id, previous, .. } => {
+                self.employee_mut(*id).title = previous.clone()
+            }
+            Change::RemoveEmployee { employee, reports } => {
+                self.employees.insert(employee.id, employee.clone());
+                for report in reports {
+                    self.employee_mut(*report).manager = Some(employee.id);
+                }
+            }
+            Change::RemoveDepartment { department, employees, reports } => {
+                self.departments.insert(department.clone());
+                for employee in employees {
+                    self.employees.insert(employee.id, employee.clone());
+                }
+                for (report, manager) in reports {
+                    self.employee_mut(*report).manager = Some(*manager);
+                }
+            }
+            Change::Import { employees, new_departments } => {
+                for employee in employees {
+                    self.employees.remove(&employee.id);
+                }
+                for department in new_departments {
+                    self.departments.remove(department);
+                }
+            }
+        }
+    }
+
+    /// Changes refer only to existing employees, as they are applied and reverted in order.
+    fn employee_mut(&mut self, id: EmployeeId) -> &mut Employee {
+        self.employees.get_mut(&id).expect("Change refers to an existing employee")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn company(employees: &[(&str, &str, Option<&str>)]) -> Company {
+        let mut company = Company::default();
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        for (name, department, title) in employees {
+            let employee = Employee {
+                id: company.next_id,
+                name: name.to_string(),
+                title: title.map(Into::into),
+                start_date: date,
+                manager: None,
+                history: vec![Assignment { department: department.to_string(), since: date }],
+            };
+            company.apply(&Change::Add { employee, new_department: true });
+        }
+        company
+    }
+
+    fn set_manager(company: &mut Company, id: EmployeeId, manager: EmployeeId) {
+        let previous = company.employees[&id].manager;
+        company.apply(&Change::SetManager { id, manager: Some(manager), previous });
+    }
+
+    #[test]
+    fn draws_org_chart() {
+        let mut company = company(&[
+            ("Alice", "Management", Some("CEO")),
+            ("Bob", "Engineering", None),
+            ("Carol", "Engineering", Some("Engineer")),
+            ("Dave", "Sales", None),
+            ("Erin", "Sales", None),
+        ]);
+        set_manager(&mut company, 1, 0);
+        set_manager(&mut company, 2, 1);
+        set_manager(&mut company, 3, 0);
+
+        assert_eq!(
+            company.org_chart().unwrap(),
+            "Alice (CEO, Management)\n\
+             ├─ Bob (Engineering)\n\
+             │  └─ Carol (Engineer, Engineering)\n\
+             └─ Dave (Sales)\n\
+             Erin (Sales)"
+        );
+        let managers: Vec<_> = company.managers(2).unwrap().iter().map(|e| &e.name).collect();
+        assert_eq!(managers, ["Bob", "Alice"]);
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let mut company =
+            company(&[("Alice", "A", None), ("Bob", "A", None), ("Carol", "A", None)]);
+        set_manager(&mut company, 1, 0);
+        set_manager(&mut company, 2, 1);
+
+        let err = company.check_manager(0, 2).unwrap_err();
+        assert_eq!(err.to_string(), "Reporting chain has a cycle: Alice → Carol → Bob → Alice");
+        assert!(company.check_manager(2, 0).is_ok());
+        assert_eq!(company.check_manager(0, 0).unwrap_err().0, ["Alice", "Alice"]);
+
+        // Like after an import of broken data.
+        set_manager(&mut company, 0, 2);
+        assert_eq!(company.managers(1).unwrap_err().0, ["Bob", "Alice", "Carol", "Bob"]);
+        assert!(company.org_chart().is_err());
+    }
+
+    #[test]
+    fn reverts_changes() {
+        let mut company = company(&[("Alice", "Management", None), ("Bob", "Engineering", None)]);
+        set_manager(&mut company, 1, 0);
+        let before = company.clone();
+
+        let date = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        let changes = [
+            Change::Move {
+                id: 1,
+                assignment: Assignment { department: "Research".into(), since: date },
+                new_department: true,
+            },
+            Change::RemoveDepartment {
+                department: "Management".into(),
+                employees: vec![company.employees[&0].clone()],
+                reports: vec![(1, 0)],
+            },
+        ];
+        for change in &changes {
+            company.apply(change);
+        }
+        assert_eq!(company.departments, BTreeSet::from(["Engineering".into(), "Research".into()]));
+        let bob = &company.employees[&1];
+        assert_eq!(
+            (bob.department().as_str(), bob.manager, bob.history.len()),
+            ("Research", None, 2)
+        );
+
+        for change in changes.iter().rev() {
+            company.revert(change);
+        }
+        assert_eq!(company, before);
+    }
+}
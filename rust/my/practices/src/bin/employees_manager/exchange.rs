@@ -0,0 +1,377 @@
+//! Export and import of employees as records, one per employee with the current department.
+//! Histories are not exchanged, imported employees are in their department since `start_date`.
+//!
+//! The INI layout has one section per department, `start_date; title; manager` per employee,
+//! where only the date is required:
+//!
+//! ```ini
+//! [Engineering]
+//! Bob = 2021-03-01; ; Alice
+//! Carol = 2022-05-10; Engineer; Bob
+//! ```
+//!
+//! It is written with `rust-ini`, as `serde_ini` needs a struct field per section, while departments
+//! are known only at runtime. `rust-ini` also keeps repeated sections and keys, so that an employee
+//! listed twice is reported instead of silently overwritten.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::company::{Assignment, Change, Company, CycleError, Department, Employee};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+    Ini,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    pub name: String,
+    pub department: Department,
+    #[serde(default)]
+    pub title: Option<String>,
+    pub start_date: NaiveDate,
+    /// Name of the manager, who is either imported too or already in the company.
+    #[serde(default)]
+    pub manager: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("Invalid CSV")]
+    Csv(#[from] csv::Error),
+
+    #[error("Invalid JSON")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid INI")]
+    Ini(#[from] ini::ParseError),
+
+    #[error("{0} is outside of department sections")]
+    IniWithoutDepartment(String),
+
+    #[error("{value:?} of {employee} is not like `2024-01-31; title; manager`")]
+    IniValue { employee: String, value: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Conflict {
+    #[error("{employee} is listed {count} times in {department} department")]
+    Repeated { employee: String, department: Department, count: usize },
+
+    #[error("{employee} is listed in {} departments", Departments(.departments))]
+    InManyDepartments { employee: String, departments: Vec<Department> },
+
+    #[error("{employee} is already added to another {department} department")]
+    AddedToAnother { employee: String, department: Department },
+
+    #[error("Cannot find {manager}, the manager of {employee}")]
+    ManagerNotFound { employee: String, manager: String },
+
+    #[error(transparent)]
+    Cycle(#[from] CycleError),
+}
+
+struct Departments<'a>(&'a [Department]);
+
+impl fmt::Display for Departments<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.split_last() {
+            Some((last, init)) if !init.is_empty() => write!(f, "{} and {last}", init.join(", ")),
+            _ => f.write_str(&self.0.join("")),
+        }
+    }
+}
+
+/// All conflicts of an import, nothing is imported if there are any.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Found {} conflicts:{}", .0.len(), List(.0))]
+pub struct Conflicts(pub Vec<Conflict>);
+
+struct List<'a>(&'a [Conflict]);
+
+impl fmt::Display for List<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|conflict| write!(f, "\n - {conflict}"))
+    }
+}
+
+pub fn records(company: &Company) -> Vec<Record> {
+    company
+        .employees
+        .values()
+        .map(|employee| Record {
+            name: employee.name.clone(),
+            department: employee.department().clone(),
+            title: employee.title.clone(),
+            start_date: employee.start_date,
+            manager: employee
+                .manager
+                .and_then(|manager| company.employees.get(&manager))
+                .map(|manager| manager.name.clone()),
+        })
+        .collect()
+}
+
+impl Format {
+    pub fn encode(self, records: &[Record]) -> String {
+        match self {
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(vec![]);
+                for record in records {
+                    writer.serialize(record).expect("Record is always serializable");
+                }
+                let csv = writer.into_inner().expect("Writing to `Vec` never fails");
+                String::from_utf8(csv).expect("CSV of strings is UTF-8")
+            }
+            Format::Json => {
+                serde_json::to_string_pretty(records).expect("Record is always serializable")
+            }
+            Format::Ini => {
+                let mut ini = ini::Ini::new();
+                for record in records {
+                    let mut fields = vec![record.start_date.to_string()];
+                    fields.push(record.title.clone().unwrap_or_default());
+                    fields.extend(record.manager.clone());
+                    let value = fields.join("; ");
+                    let value = value.trim_end_matches("; ");
+                    ini.with_section(Some(&record.department)).add(&record.name, value);
+                }
+                let mut buf = vec![];
+                ini.write_to(&mut buf).expect("Writing to `Vec` never fails");
+                String::from_utf8(buf).expect("INI of strings is UTF-8")
+            }
+        }
+    }
+
+    pub fn decode(self, text: &str) -> Result<Vec<Record>, DecodeError> {
+        match self {
+            Format::Csv => Ok(csv::Reader::from_reader(text.as_bytes())
+                .deserialize()
+                .collect::<Result<_, _>>()?),
+            Format::Json => Ok(serde_json::from_str(text)?),
+            Format::Ini => {
+                let mut records = vec![];
+                for (department, properties) in ini::Ini::load_from_str(text)?.iter() {
+                    for (name, value) in properties.iter() {
+                        let Some(department) = department else {
+                            return Err(DecodeError::IniWithoutDepartment(name.into()));
+                        };
+                        records.push(ini_record(name, department, value)?);
+                    }
+                }
+                Ok(records)
+            }
+        }
+    }
+}
+
+fn ini_record(name: &str, department: &str, value: &str) -> Result<Record, DecodeError> {
+    let invalid = || DecodeError::IniValue { employee: name.into(), value: value.into() };
+    let mut fields = value.split(';').map(str::trim);
+    let start_date = fields.next().and_then(|date| date.parse().ok()).ok_or_else(invalid)?;
+    let mut optional = || fields.next().filter(|field| !field.is_empty()).map(Into::into);
+    let (title, manager) = (optional(), optional());
+    if fields.next().is_some() {
+        return Err(invalid());
+    }
+    Ok(Record { name: name.into(), department: department.into(), title, start_date, manager })
+}
+
+/// Change adding the employees of `records`, which are not in the company yet.
+/// Employees already in the same department are skipped, as after importing the same file again.
+pub fn import(company: &Company, records: Vec<Record>) -> Result<Change, Conflicts> {
+    let mut conflicts = vec![];
+
+    let mut by_name: BTreeMap<&str, Vec<&Record>> = BTreeMap::new();
+    for record in &records {
+        by_name.entry(&record.name).or_default().push(record);
+    }
+    let mut unique = vec![];
+    for record in &records {
+        let listed = &by_name[record.name.as_str()];
+        if !std::ptr::eq(listed[0], record) {
+            continue;
+        }
+        let mut departments: Vec<&Department> = vec![];
+        for listed in listed {
+            if !departments.contains(&&listed.department) {
+                departments.push(&listed.department);
+            }
+        }
+        if departments.len() > 1 {
+            conflicts.push(Conflict::InManyDepartments {
+                employee: record.name.clone(),
+                departments: departments.into_iter().cloned().collect(),
+            });
+        } else if listed.len() > 1 {
+            conflicts.push(Conflict::Repeated {
+                employee: record.name.clone(),
+                department: record.department.clone(),
+                count: listed.len(),
+            });
+        }
+        match company.find(&record.name) {
+            Some(found) if found.department() != &record.department => {
+                conflicts.push(Conflict::AddedToAnother {
+                    employee: record.name.clone(),
+                    department: found.department().clone(),
+                })
+            }
+            Some(_) => {}
+            None => unique.push(record),
+        }
+    }
+
+    let ids: BTreeMap<&str, _> = unique
+        .iter()
+        .zip(company.next_id..)
+        .map(|(record, id)| (record.name.as_str(), id))
+        .collect();
+    let mut employees = vec![];
+    for record in &unique {
+        let manager = record.manager.as_ref().and_then(|manager| {
+            let id = ids.get(manager.as_str()).copied().or_else(|| Some(company.find(manager)?.id));
+            if id.is_none() {
+                conflicts.push(Conflict::ManagerNotFound {
+                    employee: record.name.clone(),
+                    manager: manager.clone(),
+                });
+            }
+            id
+        });
+        employees.push(Employee {
+            id: ids[record.name.as_str()],
+            name: record.name.clone(),
+            title: record.title.clone(),
+            start_date: record.start_date,
+            manager,
+            history: vec![Assignment {
+                department: record.department.clone(),
+                since: record.start_date,
+            }],
+        });
+    }
+    let new_departments: BTreeSet<_> = unique
+        .iter()
+        .map(|record| &record.department)
+        .filter(|department| !company.departments.contains(*department))
+        .cloned()
+        .collect();
+    let change =
+        Change::Import { employees, new_departments: new_departments.into_iter().collect() };
+
+    // Only imported employees get managers, so only they can make cycles.
+    let mut imported = company.clone();
+    imported.apply(&change);
+    let mut cycles: Vec<CycleError> = vec![];
+    if let Change::Import { employees, .. } = &change {
+        for employee in employees {
+            if let Err(cycle) = imported.managers(employee.id) {
+                let members: BTreeSet<_> = cycle.0.iter().collect();
+                if !cycles.iter().any(|known| known.0.iter().collect::<BTreeSet<_>>() == members) {
+                    cycles.push(cycle);
+                }
+            }
+        }
+    }
+    conflicts.extend(cycles.into_iter().map(Conflict::Cycle));
+
+    match conflicts.is_empty() {
+        true => Ok(change),
+        false => Err(Conflicts(conflicts)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, department: &str, title: Option<&str>, manager: Option<&str>) -> Record {
+        Record {
+            name: name.into(),
+            department: department.into(),
+            title: title.map(Into::into),
+            start_date: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            manager: manager.map(Into::into),
+        }
+    }
+
+    fn sample() -> Vec<Record> {
+        vec![
+            record("Alice", "Management", Some("CEO"), None),
+            record("Bob", "Engineering", None, Some("Alice")),
+            record("Carol", "Engineering", Some("Data Engineer"), Some("Bob")),
+        ]
+    }
+
+    #[test]
+    fn round_trips_all_formats() {
+        for format in [Format::Csv, Format::Json, Format::Ini] {
+            let text = format.encode(&sample());
+            let mut decoded = format.decode(&text).unwrap();
+            decoded.sort_by(|a, b| a.name.cmp(&b.name));
+            assert_eq!(decoded, sample(), "{format:?}:\n{text}");
+        }
+
+        assert_eq!(
+            Format::Ini.encode(&sample()[..2]),
+            "[Management]\nAlice=2024-01-15; CEO\n\n[Engineering]\nBob=2024-01-15; ; Alice\n"
+        );
+        assert_eq!(
+            Format::Csv.encode(&sample()[1..2]),
+            "name,department,title,start_date,manager\nBob,Engineering,,2024-01-15,Alice\n"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_ini() {
+        let err = Format::Ini.decode("Bob = 2024-01-15").unwrap_err();
+        assert_eq!(err.to_string(), "Bob is outside of department sections");
+        let err = Format::Ini.decode("[Sales]\nBob = yesterday").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#""yesterday" of Bob is not like `2024-01-31; title; manager`"#
+        );
+    }
+
+    #[test]
+    fn reports_all_conflicts() {
+        let mut company = Company::default();
+        company.apply(&import(&company, vec![record("Dave", "Sales", None, None)]).unwrap());
+
+        let text = "\
+            [Engineering]\n\
+            Bob = 2024-01-15; ; Carol\n\
+            Carol = 2024-01-15; ; Bob\n\
+            Erin = 2024-01-15\n\
+            Frank = 2024-01-15; ; Grace\n\
+            Dave = 2024-01-15\n\
+            [Sales]\n\
+            Erin = 2024-01-15\n\
+            Dave = 2024-01-15\n\
+            [Engineering]\n\
+            Erin = 2024-01-15\n\
+            Heidi = 2024-01-15\n\
+            Heidi = 2024-01-15\n";
+        let err = import(&company, Format::Ini.decode(text).unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Found 6 conflicts:\n \
+             - Erin is listed in Engineering and Sales departments\n \
+             - Dave is listed in Engineering and Sales departments\n \
+             - Dave is already added to another Sales department\n \
+             - Heidi is listed 2 times in Engineering department\n \
+             - Cannot find Grace, the manager of Frank\n \
+             - Reporting chain has a cycle: Bob → Carol → Bob"
+        );
+        // Importing the same employees again skips them.
+        let change = import(&company, vec![record("Dave", "Sales", None, None)]).unwrap();
+        assert_eq!(change, Change::Import { employees: vec![], new_departments: vec![] });
+    }
+}
@@ -0,0 +1,59 @@
+use std::{fs, io};
+
+use manager::EmployeesManager;
+
+mod command;
+mod company;
+mod exchange;
+mod manager;
+mod storage;
+
+fn main() {
+    // Departments are saved to `DATA_FILE`.
+    // With `--script` commands are read from the file or stdin, the first failed one exits with 1.
+    let mut path = None;
+    let mut script = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--script" if script.is_none() => script = Some(args.next().unwrap_or_else(|| usage())),
+            _ if arg.starts_with('-') || path.is_some() => usage(),
+            _ => path = Some(arg),
+        }
+    }
+
+    let path = path.unwrap_or_else(|| "employees.json".into());
+    let mut manager = match EmployeesManager::open(path) {
+        Ok(manager) => manager,
+        Err(err) => exit_with(err),
+    };
+
+    let result = match script.as_deref() {
+        None => {
+            manager.run();
+            return;
+        }
+        Some("-") => manager.run_script(io::stdin().lock()),
+        Some(script) => fs::File::open(script)
+            .map_err(manager::ScriptError::Read)
+            .and_then(|script| manager.run_script(io::BufReader::new(script))),
+    };
+    if let Err(err) = result {
+        exit_with(err);
+    }
+}
+
+fn exit_with(err: impl std::error::Error) -> ! {
+    eprintln!("Error: {err}");
+    let mut source = err.source();
+    while let Some(err) = source {
+        eprintln!("Caused by: {err}");
+        source = err.source();
+    }
+    std::process::exit(1);
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: employees_manager [DATA_FILE] [--script SCRIPT_FILE|-]");
+    std::process::exit(2);
+}
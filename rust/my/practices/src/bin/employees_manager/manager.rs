@@ -0,0 +1,782 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use chrono::NaiveDate;
+use my_practices::print_err;
+
+use super::command::{Command, ParseError};
+use super::company::{Assignment, Change, Company, CycleError, Department, Employee};
+use super::exchange::{self, Conflicts, DecodeError};
+use super::storage::{Entry, Snapshot, Storage, StorageError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+
+    #[error("{employee} is already added to {department} department!")]
+    AlreadyAdded { employee: String, department: Department },
+
+    #[error(
+        "{employee} is already added to another {department} department! Consider moving {employee} to another department instead!"
+    )]
+    AddedToAnother { employee: String, department: Department },
+
+    #[error(
+        "{employee} can't be moved on {on}, before joining {department} department on {since}!"
+    )]
+    MovedTooEarly { employee: String, department: Department, since: NaiveDate, on: NaiveDate },
+
+    #[error("Cannot find {0} department!")]
+    DepartmentNotFound(Department),
+
+    #[error("Cannot find {0}!")]
+    EmployeeNotFound(String),
+
+    #[error(transparent)]
+    Cycle(#[from] CycleError),
+
+    #[error("Failed to read {}", .0.display())]
+    Read(PathBuf, #[source] io::Error),
+
+    #[error("Failed to write {}", .0.display())]
+    Write(PathBuf, #[source] io::Error),
+
+    #[error("Failed to import {}", .0.display())]
+    Decode(PathBuf, #[source] DecodeError),
+
+    #[error(transparent)]
+    Conflicts(#[from] Conflicts),
+
+    #[error("Nothing to {0}!")]
+    NothingTo(&'static str),
+
+    #[error("Failed to save the change")]
+    Storage(#[from] StorageError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptError {
+    #[error("Failed to read the script")]
+    Read(#[source] io::Error),
+
+    #[error("Command at line {line} failed")]
+    Command {
+        line: usize,
+        #[source]
+        source: CommandError,
+    },
+
+    #[error("Failed to save the company")]
+    Save(#[source] StorageError),
+}
+
+#[derive(Default)]
+pub struct EmployeesManager {
+    company: Company,
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+    /// In memory only if `None`.
+    storage: Option<Storage>,
+}
+
+impl EmployeesManager {
+    /// Loads the company saved at `path` and replays the changes made after the last `Quit`.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        let (storage, snapshot, entries) = Storage::open(path)?;
+        let mut manager = EmployeesManager {
+            company: snapshot.company,
+            undo: snapshot.undo,
+            redo: snapshot.redo,
+            storage: Some(storage),
+        };
+        for entry in entries {
+            manager.perform(entry);
+        }
+        Ok(manager)
+    }
+
+    pub fn run(&mut self) {
+        EmployeesManager::print_intro();
+        loop {
+            print!("Please input your command: ");
+            io::stdout().flush().expect("Unable to flush stdout");
+
+            let mut input = String::new();
+            // The end of input quits too.
+            if io::stdin().read_line(&mut input).expect("Failed to read line.") == 0 {
+                println!();
+                self.process_command("Quit");
+                break;
+            }
+
+            let command = input.trim();
+            if command.is_empty() {
+                continue;
+            }
+
+            if !self.process_command(command) {
+                break;
+            }
+        }
+    }
+
+    /// Executes `script` line by line, skipping empty lines and `#` comments, and saves
+    /// the company at the end. Stops at the first command which fails.
+    pub fn run_script(&mut self, script: impl BufRead) -> Result<(), ScriptError> {
+        for (i, line) in script.lines().enumerate() {
+            let line = line.map_err(ScriptError::Read)?;
+            let command = line.trim();
+            if command.is_empty() || command.starts_with('#') {
+                continue;
+            }
+
+            let at_line = |source| ScriptError::Command { line: i + 1, source };
+            let command = command.parse().map_err(|err: ParseError| at_line(err.into()))?;
+            if !self.execute_command(command).map_err(at_line)? {
+                return Ok(());
+            }
+        }
+        self.save().map_err(ScriptError::Save)
+    }
+
+    fn print_intro() {
+        println!("This is a basic employees management tool.");
+        println!("You can add or remove employees and their departments in a company.");
+        println!("Use this list of commands:");
+        println!(
+            " - \"Add `employee_name` to `department_name` [as `title`] [since `YYYY-MM-DD`]\" to add employee to department."
+        );
+        println!(
+            " - \"Move `employee_name` to `department_name` [on `YYYY-MM-DD`]\" to move employee to another department."
+        );
+        println!(
+            " - \"Set manager|title of `employee_name` to `value`\" to set manager or title of employee."
+        );
+        println!(
+            " - \"Clear manager|title of `employee_name`\" to clear manager or title of employee."
+        );
+        println!(" - \"Remove `department_name` department\" to remove department.");
+        println!(" - \"Remove `employee_name` employee\" to remove employee from department.");
+        println!(" - \"List `department_name` department\" to list department with its employees.");
+        println!(" - \"List `employee_name` employee\" to list employee with its department.");
+        println!(" - \"List departments\" to list all departments.");
+        println!(" - \"List employees\" to list all employees.");
+        println!(" - \"List departments with employees\" to list all departments with employees.");
+        println!(" - \"List employees with departments\" to list all employees with departments.");
+        println!(" - \"List reports of `employee_name`\" to list employees managed by employee.");
+        println!(" - \"Who manages `employee_name`\" to list managers of employee up to the top.");
+        println!(" - \"Show org chart\" to show managers with their reports as a tree.");
+        println!(" - \"Show history of `employee_name`\" to list departments of employee.");
+        println!(
+            " - \"Export csv|json|ini `file_path`\" to save all employees to file, or print them with `-`."
+        );
+        println!(
+            " - \"Import csv|json|ini `file_path`\" to add employees from file, if there are no conflicts."
+        );
+        println!(" - \"Undo\" or \"Undo `count`\" to revert last additions and removals.");
+        println!(" - \"Redo\" or \"Redo `count`\" to repeat reverted additions and removals.");
+        println!(" - \"Quit\" to save and quit.");
+        println!("Commands are case-insensitive, quote names with spaces: \"Sally Ann\".");
+        println!();
+    }
+
+    fn process_command(&mut self, command: &str) -> bool {
+        let result = command
+            .parse()
+            .map_err(CommandError::from)
+            .and_then(|command| self.execute_command(command));
+        match result {
+            Ok(proceed) => proceed,
+            Err(err @ CommandError::Storage(_)) => {
+                print_err!(err);
+                true
+            }
+            Err(err) => {
+                println!("{err}");
+                let mut source = std::error::Error::source(&err);
+                while let Some(err) = source {
+                    println!("Caused by: {err}");
+                    source = err.source();
+                }
+                true
+            }
+        }
+    }
+
+    fn execute_command(&mut self, command: Command) -> Result<bool, CommandError> {
+        match command {
+            Command::Add { employee, department, title, since } => {
+                match self.add_employee_to_department(&employee, &department, title, since)? {
+                    None => {
+                        println!("{employee} was successfully added to {department} department.")
+                    }
+                    Some(old_department) => {
+                        if old_department == department {
+                            return Err(CommandError::AlreadyAdded { employee, department });
+                        } else {
+                            return Err(CommandError::AddedToAnother {
+                                employee,
+                                department: old_department,
+                            });
+                        }
+                    }
+                }
+            }
+            Command::Move { employee, department, on } => {
+                self.move_employee(&employee, &department, on)?;
+                println!("{employee} was successfully moved to {department} department.");
+            }
+            Command::SetManager { employee, manager } => {
+                self.set_manager(&employee, manager.as_deref())?;
+                match manager {
+                    Some(manager) => println!("{employee} is now managed by {manager}."),
+                    None => println!("{employee} has no manager now."),
+                }
+            }
+            Command::SetTitle { employee, title } => {
+                let id = self.find(&employee)?.id;
+                let previous = self.company.employees[&id].title.clone();
+                self.record(Entry::Change(Change::SetTitle {
+                    id,
+                    title: title.clone(),
+                    previous,
+                }))?;
+                match title {
+                    Some(title) => println!("{employee}'s title is now {title}."),
+                    None => println!("{employee} has no title now."),
+                }
+            }
+            Command::RemoveDepartment(department) => match self.remove_department(&department)? {
+                true => println!("{department} department was successfully removed."),
+                false => return Err(CommandError::DepartmentNotFound(department)),
+            },
+            Command::RemoveEmployee(employee) => match self.remove_employee(&employee)? {
+                true => println!("{employee} was successfully removed."),
+                false => return Err(CommandError::EmployeeNotFound(employee)),
+            },
+            Command::ListDepartment(department) => match self.list_department(&department) {
+                Some(employees) => {
+                    println!("List of employees in {department} department is {:?}.", employees)
+                }
+                None => return Err(CommandError::DepartmentNotFound(department)),
+            },
+            Command::ListEmployee(employee) => {
+                let found = self.find(&employee)?;
+                print!("{employee}'s department is {}", found.department());
+                if let Some(title) = &found.title {
+                    print!(", title is {title}");
+                }
+                println!(", started on {}.", found.start_date);
+            }
+            Command::ListDepartments => {
+                let departments = self.list_departments();
+                println!("List of departments is {:?}.", departments);
+            }
+            Command::ListEmployees => {
+                let employees = self.list_employees();
+                println!("List of all employees is {:?}.", employees);
+            }
+            Command::ListDepartmentsWithEmployees => {
+                let departments = self.list_departments_with_employees();
+                println!("List of departments with employees is {:?}.", departments);
+            }
+            Command::ListEmployeesWithDepartments => {
+                let employees = self.list_employees_with_departments();
+                println!("List of employees with departments is {:?}.", employees);
+            }
+            Command::ListReports(employee) => {
+                let id = self.find(&employee)?.id;
+                let reports: Vec<_> = self.company.reports(id).map(|e| e.name.as_str()).collect();
+                println!("List of reports of {employee} is {:?}.", reports);
+            }
+            Command::WhoManages(employee) => {
+                let id = self.find(&employee)?.id;
+                let managers: Vec<_> =
+                    self.company.managers(id)?.iter().map(|e| e.name.as_str()).collect();
+                match managers[..] {
+                    [] => println!("{employee} has no manager."),
+                    _ => println!("{employee} is managed by {}.", managers.join(" → ")),
+                }
+            }
+            Command::ShowOrgChart => println!("{}", self.company.org_chart()?),
+            Command::ShowHistory(employee) => {
+                for assignment in &self.find(&employee)?.history {
+                    println!("{} since {}", assignment.department, assignment.since);
+                }
+            }
+            Command::Export { format, path } => {
+                let records = exchange::records(&self.company);
+                let text = format.encode(&records);
+                if path == Path::new("-") {
+                    print!("{text}");
+                } else {
+                    fs::write(&path, text).map_err(|err| CommandError::Write(path.clone(), err))?;
+                    let count = records.len();
+                    println!("{count} employees were successfully exported to {}.", path.display());
+                }
+            }
+            Command::Import { format, path } => {
+                let text = fs::read_to_string(&path)
+                    .map_err(|err| CommandError::Read(path.clone(), err))?;
+                let records =
+                    format.decode(&text).map_err(|err| CommandError::Decode(path.clone(), err))?;
+                match exchange::import(&self.company, records)? {
+                    Change::Import { employees, .. } if employees.is_empty() => {
+                        println!("All employees of {} are already added.", path.display())
+                    }
+                    change => {
+                        self.record(Entry::Change(change))?;
+                        println!("Employees were successfully imported from {}.", path.display());
+                    }
+                }
+            }
+            Command::Undo(count) => self.undo_or_redo(Entry::Undo, count)?,
+            Command::Redo(count) => self.undo_or_redo(Entry::Redo, count)?,
+            Command::Quit => {
+                self.save()?;
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn undo_or_redo(&mut self, entry: Entry, count: usize) -> Result<(), CommandError> {
+        let (done, action) = match entry {
+            Entry::Undo => (self.undo(count)?, "undo"),
+            _ => (self.redo(count)?, "redo"),
+        };
+        match done {
+            0 => return Err(CommandError::NothingTo(action)),
+            1 => println!("1 change was successfully {action}ne."),
+            _ => println!("{done} changes were successfully {action}ne."),
+        }
+        Ok(())
+    }
+
+    fn find(&self, employee: &str) -> Result<&Employee, CommandError> {
+        self.company.find(employee).ok_or_else(|| CommandError::EmployeeNotFound(employee.into()))
+    }
+
+    fn add_employee_to_department(
+        &mut self,
+        employee: &str,
+        department: &str,
+        title: Option<String>,
+        since: Option<NaiveDate>,
+    ) -> Result<Option<Department>, StorageError> {
+        // Check whether this employee is added to some department.
+        if let Some(old) = self.company.find(employee) {
+            return Ok(Some(old.department().clone()));
+        }
+        // Employee is not added to any department, so let's add it.
+        let since = since.unwrap_or_else(today);
+        self.record(Entry::Change(Change::Add {
+            employee: Employee {
+                id: self.company.next_id,
+                name: employee.to_owned(),
+                title,
+                start_date: since,
+                manager: None,
+                history: vec![Assignment { department: department.to_owned(), since }],
+            },
+            new_department: !self.company.departments.contains(department),
+        }))?;
+        Ok(None)
+    }
+
+    /// Keeps the previous departments in the history.
+    fn move_employee(
+        &mut self,
+        employee: &str,
+        department: &str,
+        on: Option<NaiveDate>,
+    ) -> Result<(), CommandError> {
+        let found = self.find(employee)?;
+        let current = found.history.last().expect("Employee is always in some department");
+        let on = on.unwrap_or_else(today);
+        if current.department == department {
+            return Err(CommandError::AlreadyAdded {
+                employee: employee.into(),
+                department: department.into(),
+            });
+        }
+        if on < current.since {
+            return Err(CommandError::MovedTooEarly {
+                employee: employee.into(),
+                department: current.department.clone(),
+                since: current.since,
+                on,
+            });
+        }
+        self.record(Entry::Change(Change::Move {
+            id: found.id,
+            assignment: Assignment { department: department.to_owned(), since: on },
+            new_department: !self.company.departments.contains(department),
+        }))?;
+        Ok(())
+    }
+
+    fn set_manager(&mut self, employee: &str, manager: Option<&str>) -> Result<(), CommandError> {
+        let found = self.find(employee)?;
+        let (id, previous) = (found.id, found.manager);
+        let manager = match manager {
+            Some(manager) => {
+                let manager = self.find(manager)?.id;
+                self.company.check_manager(id, manager)?;
+                Some(manager)
+            }
+            None => None,
+        };
+        self.record(Entry::Change(Change::SetManager { id, manager, previous }))?;
+        Ok(())
+    }
+
+    fn remove_department(&mut self, department: &str) -> Result<bool, StorageError> {
+        if !self.company.departments.contains(department) {
+            return Ok(false);
+        }
+        let employees: Vec<Employee> = self.company.employees_of(department).cloned().collect();
+        let reports = self
+            .company
+            .employees
+            .values()
+            .filter(|employee| employee.department() != department)
+            .filter_map(|employee| Some((employee.id, employee.manager?)))
+            .filter(|(_, manager)| employees.iter().any(|employee| employee.id == *manager))
+            .collect();
+        self.record(Entry::Change(Change::RemoveDepartment {
+            department: department.to_owned(),
+            employees,
+            reports,
+        }))?;
+        Ok(true)
+    }
+
+    fn remove_employee(&mut self, employee: &str) -> Result<bool, StorageError> {
+        let Some(found) = self.company.find(employee) else {
+            return Ok(false);
+        };
+        let reports = self.company.reports(found.id).map(|report| report.id).collect();
+        self.record(Entry::Change(Change::RemoveEmployee { employee: found.clone(), reports }))?;
+        Ok(true)
+    }
+
+    /// Reverts up to `count` last changes, returns how many were reverted.
+    fn undo(&mut self, count: usize) -> Result<usize, StorageError> {
+        let count = count.min(self.undo.len());
+        for _ in 0..count {
+            self.record(Entry::Undo)?;
+        }
+        Ok(count)
+    }
+
+    /// Repeats up to `count` last reverted changes, returns how many were repeated.
+    fn redo(&mut self, count: usize) -> Result<usize, StorageError> {
+        let count = count.min(self.redo.len());
+        for _ in 0..count {
+            self.record(Entry::Redo)?;
+        }
+        Ok(count)
+    }
+
+    fn save(&mut self) -> Result<(), StorageError> {
+        let Some(storage) = &mut self.storage else {
+            return Ok(());
+        };
+        let mut snapshot = Snapshot {
+            company: self.company.clone(),
+            undo: self.undo.clone(),
+            redo: self.redo.clone(),
+            generation: 0,
+        };
+        storage.save(&mut snapshot)
+    }
+
+    /// Journals `entry` first, so that it is replayed after a crash.
+    fn record(&mut self, entry: Entry) -> Result<(), StorageError> {
+        if let Some(storage) = &mut self.storage {
+            storage.append(&entry)?;
+        }
+        self.perform(entry);
+        Ok(())
+    }
+
+    /// Applies `entry` and updates the history, for new entries and replayed ones.
+    fn perform(&mut self, entry: Entry) {
+        match entry {
+            Entry::Change(change) => {
+                self.company.apply(&change);
+                self.undo.push(change);
+                self.redo.clear();
+            }
+            Entry::Undo => {
+                if let Some(change) = self.undo.pop() {
+                    self.company.revert(&change);
+                    self.redo.push(change);
+                }
+            }
+            Entry::Redo => {
+                if let Some(change) = self.redo.pop() {
+                    self.company.apply(&change);
+                    self.undo.push(change);
+                }
+            }
+        }
+    }
+
+    fn list_department(&self, department: &str) -> Option<BTreeSet<&str>> {
+        self.company.departments.contains(department).then(|| {
+            self.company.employees_of(department).map(|employee| employee.name.as_str()).collect()
+        })
+    }
+
+    fn list_departments(&self) -> Vec<&Department> {
+        self.company.departments.iter().collect()
+    }
+
+    fn list_employees(&self) -> Vec<&str> {
+        self.company.employees.values().map(|employee| employee.name.as_str()).collect()
+    }
+
+    fn list_departments_with_employees(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut departments: BTreeMap<&str, BTreeSet<&str>> = self
+            .company
+            .departments
+            .iter()
+            .map(|department| (department.as_str(), BTreeSet::new()))
+            .collect();
+        for employee in self.company.employees.values() {
+            departments.entry(employee.department()).or_default().insert(&employee.name);
+        }
+        departments
+    }
+
+    fn list_employees_with_departments(&self) -> BTreeMap<&str, &Department> {
+        let mut employees_with_departments = BTreeMap::new();
+        for employee in self.company.employees.values() {
+            employees_with_departments.insert(employee.name.as_str(), employee.department());
+        }
+        employees_with_departments
+    }
+}
+
+fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn departments<'a>(entries: &[(&'a str, &[&'a str])]) -> BTreeMap<&'a str, BTreeSet<&'a str>> {
+        entries
+            .iter()
+            .map(|(department, employees)| (*department, employees.iter().copied().collect()))
+            .collect()
+    }
+
+    #[test]
+    fn undoes_and_redoes_changes() {
+        let mut manager = EmployeesManager::default();
+        for command in ["Add Sally to Engineering", "Add Amir to Sales", "Add Bob to Sales"] {
+            assert!(manager.process_command(command));
+        }
+        manager.process_command("Remove Sales department");
+        manager.process_command("Remove Sally employee");
+        assert_eq!(manager.list_departments_with_employees(), departments(&[("Engineering", &[])]));
+
+        manager.process_command("Undo 2");
+        assert_eq!(
+            manager.list_departments_with_employees(),
+            departments(&[("Engineering", &["Sally"]), ("Sales", &["Amir", "Bob"])])
+        );
+        manager.process_command("Undo 2");
+        assert_eq!(
+            manager.list_departments_with_employees(),
+            departments(&[("Engineering", &["Sally"])])
+        );
+
+        manager.process_command("Redo");
+        assert_eq!(
+            manager.list_departments_with_employees(),
+            departments(&[("Engineering", &["Sally"]), ("Sales", &["Amir"])])
+        );
+        // A new change drops the rest of redo.
+        manager.process_command("Add Bob to Engineering");
+        assert_eq!(manager.redo(3).unwrap(), 0);
+        assert_eq!(manager.undo(10).unwrap(), 3);
+        assert!(manager.company.departments.is_empty());
+    }
+
+    #[test]
+    fn runs_script_until_first_error() {
+        let script = r#"
+            # Seed data.
+            add "Sally Ann" to "Research and Development"
+            ADD Amir TO Sales
+
+            add Bob to Sales
+            remove Carol employee
+            add Carol to Sales
+        "#;
+        let mut manager = EmployeesManager::default();
+        let err = manager.run_script(script.as_bytes()).unwrap_err();
+
+        assert!(
+            matches!(
+                &err,
+                ScriptError::Command { line: 7, source: CommandError::EmployeeNotFound(name) }
+                    if name == "Carol"
+            ),
+            "{err:?}"
+        );
+        assert_eq!(
+            manager.list_departments_with_employees(),
+            departments(&[
+                ("Research and Development", &["Sally Ann"]),
+                ("Sales", &["Amir", "Bob"])
+            ])
+        );
+
+        let err = manager.run_script("List departmens".as_bytes()).unwrap_err();
+        assert_eq!(
+            std::error::Error::source(&err).unwrap().to_string(),
+            r#"Expected "departments", "employees" or "reports", found "departmens", did you mean "departments"?"#
+        );
+    }
+
+    #[test]
+    fn saves_after_script() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.child("employees.json");
+
+        let mut manager = EmployeesManager::open(&path).unwrap();
+        manager
+            .run_script("Add Sally to Engineering\nQuit\nAdd Amir to Sales\n".as_bytes())
+            .unwrap();
+        assert!(path.exists());
+        manager.run_script("Add Amir to Sales".as_bytes()).unwrap();
+        std::fs::remove_file(path.with_extension("journal")).unwrap();
+
+        let manager = EmployeesManager::open(&path).unwrap();
+        assert_eq!(
+            manager.list_departments_with_employees(),
+            departments(&[("Engineering", &["Sally"]), ("Sales", &["Amir"])])
+        );
+    }
+
+    #[test]
+    fn recovers_after_crash() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.child("employees.json");
+
+        let mut manager = EmployeesManager::open(&path).unwrap();
+        manager.process_command("Add Sally to Engineering");
+        manager.process_command("Add Amir to Sales");
+        assert!(!manager.process_command("Quit"));
+        manager.process_command("Remove Sally employee");
+        manager.process_command("Add Bob to Sales");
+        manager.process_command("Undo");
+        // Crash without `Quit`.
+        drop(manager);
+
+        let expected = departments(&[("Engineering", &[]), ("Sales", &["Amir"])]);
+        let mut manager = EmployeesManager::open(&path).unwrap();
+        assert_eq!(manager.list_departments_with_employees(), expected);
+        // History is recovered too.
+        manager.process_command("Redo");
+        manager.process_command("Undo 2");
+        assert_eq!(
+            manager.list_departments_with_employees(),
+            departments(&[("Engineering", &["Sally"]), ("Sales", &["Amir"])])
+        );
+        drop(manager);
+
+        // The journal only has the changes after the snapshot, so it isn't replayed without it.
+        std::fs::remove_file(&path).unwrap();
+        let err = EmployeesManager::open(&path).err().unwrap();
+        assert!(matches!(err, StorageError::NewerJournal { .. }), "{err:?}");
+    }
+
+    #[test]
+    fn keeps_history_and_rejects_cycles() {
+        let script = r#"
+            Add Alice to Management as CEO since 2020-01-06
+            Add Bob to Engineering since 2021-03-01
+            Set manager of Bob to Alice
+            Move Bob to Research on 2023-09-01
+            Move Bob to Sales on 2022-01-01
+        "#;
+        let mut manager = EmployeesManager::default();
+        let err = manager.run_script(script.as_bytes()).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                ScriptError::Command { line: 6, source: CommandError::MovedTooEarly { .. } }
+            ),
+            "{err:?}"
+        );
+
+        let bob = manager.find("Bob").unwrap();
+        let history: Vec<_> =
+            bob.history.iter().map(|a| (a.department.as_str(), a.since.to_string())).collect();
+        assert_eq!(
+            history,
+            [("Engineering", "2021-03-01".into()), ("Research", "2023-09-01".into())]
+        );
+        assert_eq!(bob.start_date.to_string(), "2021-03-01");
+        // Moving out of the only employee's department keeps it.
+        assert_eq!(manager.list_departments(), ["Engineering", "Management", "Research"]);
+
+        let err = manager.run_script("Set manager of Alice to Bob".as_bytes()).unwrap_err();
+        assert_eq!(
+            std::error::Error::source(&err).unwrap().to_string(),
+            "Reporting chain has a cycle: Alice → Bob → Alice"
+        );
+        assert_eq!(
+            manager.company.org_chart().unwrap(),
+            "Alice (CEO, Management)\n└─ Bob (Research)"
+        );
+
+        manager.run_script("Undo 2".as_bytes()).unwrap();
+        assert_eq!(manager.find("Bob").unwrap().department(), "Engineering");
+        assert_eq!(
+            manager.company.org_chart().unwrap(),
+            "Alice (CEO, Management)\nBob (Engineering)"
+        );
+    }
+
+    #[test]
+    fn exports_and_imports_in_one_change() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.child("company.csv");
+        let script = format!(
+            "Add Alice to Management as CEO since 2020-01-06\n\
+             Add Bob to Engineering since 2021-03-01\n\
+             Set manager of Bob to Alice\n\
+             Export csv {:?}",
+            path.display()
+        );
+        let mut exported = EmployeesManager::default();
+        exported.run_script(script.as_bytes()).unwrap();
+
+        let mut manager = EmployeesManager::default();
+        manager.run_script("Add Bob to Sales".as_bytes()).unwrap();
+        let import = format!("Import CSV {:?}", path.display());
+        let err = manager.run_script(import.as_bytes()).unwrap_err();
+        assert!(
+            matches!(&err, ScriptError::Command { source: CommandError::Conflicts(conflicts), .. }
+                if conflicts.0.len() == 1),
+            "{err:?}"
+        );
+
+        manager.run_script(format!("Remove Bob employee\n{import}").as_bytes()).unwrap();
+        assert_eq!(manager.company.org_chart(), exported.company.org_chart());
+        assert_eq!(manager.find("Bob").unwrap().start_date.to_string(), "2021-03-01");
+        manager.run_script("Undo".as_bytes()).unwrap();
+        assert!(manager.company.employees.is_empty());
+    }
+}
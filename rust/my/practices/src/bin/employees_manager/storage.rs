@@ -0,0 +1,278 @@
+//! The company is saved as a JSON snapshot, and every change in between is appended to a journal,
+//! one JSON entry per line, so that nothing is lost if the manager crashes before `Quit`.
+//!
+//! The journal starts with the generation of the snapshot it continues, and its entries are replayed
+//! on load. Saving a snapshot starts the next generation with an empty journal, and the journal of
+//! an older generation, left by a crash in between, is already included in the snapshot.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::company::{Change, Company};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Entry {
+    Change(Change),
+    Undo,
+    Redo,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub company: Company,
+    pub undo: Vec<Change>,
+    pub redo: Vec<Change>,
+    /// Generation of the journal which continues this snapshot.
+    pub generation: u64,
+}
+
+/// First line of the journal.
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalHeader {
+    generation: u64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("Failed to access {}", .0.display())]
+    Io(PathBuf, #[source] io::Error),
+
+    #[error("Failed to parse {}", .0.display())]
+    Parse(PathBuf, #[source] serde_json::Error),
+
+    #[error("Failed to parse {} at line {line}", .path.display())]
+    ParseJournal {
+        path: PathBuf,
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("Journal {} continues a newer snapshot than {}", .journal.display(), .snapshot.display())]
+    NewerJournal { journal: PathBuf, snapshot: PathBuf },
+}
+
+pub struct Storage {
+    snapshot_path: PathBuf,
+    journal_path: PathBuf,
+    journal: File,
+    generation: u64,
+}
+
+impl Storage {
+    /// Opens the snapshot at `path` and the journal next to it, creating them if there are none.
+    /// Returns the snapshot with the journal entries it doesn't include yet.
+    pub fn open(path: impl Into<PathBuf>) -> Result<(Self, Snapshot, Vec<Entry>), StorageError> {
+        let snapshot_path = path.into();
+        let journal_path = snapshot_path.with_extension("journal");
+
+        let snapshot = match fs::read(&snapshot_path) {
+            Ok(json) => serde_json::from_slice(&json)
+                .map_err(|err| StorageError::Parse(snapshot_path.clone(), err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Snapshot::default(),
+            Err(err) => return Err(StorageError::Io(snapshot_path, err)),
+        };
+
+        let io_err = |err| StorageError::Io(journal_path.clone(), err);
+        let journal = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&journal_path)
+            .map_err(io_err)?;
+        let content = fs::read_to_string(&journal_path).map_err(io_err)?;
+        // A crash in the middle of an append leaves the last line without `\n`, it was never applied.
+        let complete = content.rfind('\n').map_or(0, |i| i + 1);
+        if complete < content.len() {
+            journal.set_len(complete as u64).map_err(io_err)?;
+        }
+
+        let parse_err = |i: usize| {
+            let path = journal_path.clone();
+            move |source| StorageError::ParseJournal { path, line: i + 1, source }
+        };
+        let mut lines = content[..complete].lines().enumerate();
+        let header: Option<JournalHeader> = match lines.next() {
+            Some((i, line)) => Some(serde_json::from_str(line).map_err(parse_err(i))?),
+            None => None,
+        };
+        let generation = snapshot.generation;
+        let entries = match header {
+            Some(header) if header.generation == generation => lines
+                .map(|(i, line)| serde_json::from_str(line).map_err(parse_err(i)))
+                .collect::<Result<_, _>>()?,
+            Some(header) if header.generation > generation => {
+                return Err(StorageError::NewerJournal {
+                    journal: journal_path,
+                    snapshot: snapshot_path,
+                });
+            }
+            // Lost, or emptied by a crash before the header is written, or left by a crash after
+            // the snapshot is saved, which already includes its entries.
+            _ => {
+                start_journal(&journal, generation).map_err(io_err)?;
+                vec![]
+            }
+        };
+
+        Ok((Self { snapshot_path, journal_path, journal, generation }, snapshot, entries))
+    }
+
+    /// Writes the entry before it is applied, so that a crash after it is recovered by a replay.
+    pub fn append(&mut self, entry: &Entry) -> Result<(), StorageError> {
+        let mut line = serde_json::to_string(entry).expect("Entry is always serializable");
+        line.push('\n');
+        self.journal
+            .write_all(line.as_bytes())
+            .and_then(|()| self.journal.sync_data())
+            .map_err(|err| StorageError::Io(self.journal_path.clone(), err))?;
+        Ok(())
+    }
+
+    /// Replaces the snapshot through a temporary file, so that it is either the old or the new one,
+    /// and then empties the journal, as the snapshot includes its entries.
+    pub fn save(&mut self, snapshot: &mut Snapshot) -> Result<(), StorageError> {
+        snapshot.generation = self.generation + 1;
+        let tmp = self.snapshot_path.with_extension("json.tmp");
+        let json = serde_json::to_vec_pretty(snapshot).expect("Snapshot is always serializable");
+        write_synced(&tmp, &json)
+            .and_then(|()| fs::rename(&tmp, &self.snapshot_path))
+            .map_err(|err| StorageError::Io(self.snapshot_path.clone(), err))?;
+        self.generation = snapshot.generation;
+        start_journal(&self.journal, self.generation)
+            .map_err(|err| StorageError::Io(self.journal_path.clone(), err))
+    }
+}
+
+/// Truncates the journal to the header of `generation`.
+fn start_journal(mut journal: &File, generation: u64) -> io::Result<()> {
+    let mut header = serde_json::to_string(&JournalHeader { generation })
+        .expect("JournalHeader is always serializable");
+    header.push('\n');
+    journal.set_len(0)?;
+    journal.write_all(header.as_bytes())?;
+    journal.sync_data()
+}
+
+fn write_synced(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::company::{Assignment, Employee};
+
+    fn add(name: &str, department: &str) -> Entry {
+        let since = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        Entry::Change(Change::Add {
+            employee: Employee {
+                id: 0,
+                name: name.into(),
+                title: None,
+                start_date: since,
+                manager: None,
+                history: vec![Assignment { department: department.into(), since }],
+            },
+            new_department: true,
+        })
+    }
+
+    #[test]
+    fn replays_entries_after_snapshot() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.child("employees.json");
+
+        let (mut storage, mut snapshot, entries) = Storage::open(&path).unwrap();
+        assert_eq!(snapshot.generation, 0);
+        assert!(entries.is_empty());
+        storage.append(&add("Sally", "Engineering")).unwrap();
+        storage.save(&mut snapshot).unwrap();
+        storage.append(&add("Amir", "Sales")).unwrap();
+        storage.append(&Entry::Undo).unwrap();
+        drop(storage);
+
+        let (mut storage, mut snapshot, entries) = Storage::open(&path).unwrap();
+        assert_eq!(snapshot.generation, 1);
+        assert_eq!(entries, [add("Amir", "Sales"), Entry::Undo]);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        // The journal doesn't grow with saved entries.
+        storage.save(&mut snapshot).unwrap();
+        let journal = fs::read_to_string(path.with_extension("journal")).unwrap();
+        assert_eq!(journal, "{\"generation\":2}\n");
+    }
+
+    #[test]
+    fn skips_journal_of_saved_snapshot() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.child("employees.json");
+        let journal = path.with_extension("journal");
+
+        let (mut storage, mut snapshot, _) = Storage::open(&path).unwrap();
+        storage.append(&add("Sally", "Engineering")).unwrap();
+        let saved = fs::read(&journal).unwrap();
+        storage.save(&mut snapshot).unwrap();
+        drop(storage);
+        // Like a crash after the snapshot is saved, but before the journal is emptied.
+        fs::write(&journal, saved).unwrap();
+
+        let (mut storage, _, entries) = Storage::open(&path).unwrap();
+        assert!(entries.is_empty());
+        storage.append(&Entry::Undo).unwrap();
+        drop(storage);
+
+        // And a lost journal is an empty one.
+        let (_, _, entries) = Storage::open(&path).unwrap();
+        assert_eq!(entries, [Entry::Undo]);
+        fs::remove_file(&journal).unwrap();
+        let (mut storage, _, entries) = Storage::open(&path).unwrap();
+        assert!(entries.is_empty());
+        storage.append(&Entry::Redo).unwrap();
+        drop(storage);
+        let (_, _, entries) = Storage::open(&path).unwrap();
+        assert_eq!(entries, [Entry::Redo]);
+    }
+
+    #[test]
+    fn drops_torn_last_entry() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.child("employees.json");
+        let (mut storage, _, _) = Storage::open(&path).unwrap();
+        storage.append(&add("Sally", "Engineering")).unwrap();
+        drop(storage);
+        let journal = path.with_extension("journal");
+        let mut file = OpenOptions::new().append(true).open(&journal).unwrap();
+        file.write_all(br#"{"Change":{"Add":{"employee":{"id":1,"name":"Am"#).unwrap();
+
+        let (mut storage, _, entries) = Storage::open(&path).unwrap();
+        assert_eq!(entries, [add("Sally", "Engineering")]);
+        storage.append(&Entry::Undo).unwrap();
+
+        let (_, _, entries) = Storage::open(&path).unwrap();
+        assert_eq!(entries, [add("Sally", "Engineering"), Entry::Undo]);
+    }
+
+    #[test]
+    fn reports_corrupted_journal() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.child("employees.json");
+        let journal = "{\"generation\":0}\n\"Undo\"\nnot json\n\"Redo\"\n";
+        fs::write(path.with_extension("journal"), journal).unwrap();
+
+        let err = Storage::open(&path).err().unwrap();
+        assert!(matches!(err, StorageError::ParseJournal { line: 3, .. }), "{err:?}");
+        assert!(err.to_string().ends_with("employees.journal at line 3"), "{err}");
+
+        fs::write(path.with_extension("journal"), "{\"generation\":1}\n").unwrap();
+        let err = Storage::open(&path).err().unwrap();
+        assert!(matches!(err, StorageError::NewerJournal { .. }), "{err:?}");
+    }
+}
@@ -1,10 +1,19 @@
 use std::{fs, path::Path, sync::Arc};
 
+use v4::{CleanupGuard, CleanupTarget};
+use v5::{AsyncCleanupGuard, PanicPolicy};
+
+mod registry;
 mod v1;
 mod v2;
 mod v3;
+mod v4;
+mod v5;
 
 fn main() {
+    // `Drop` is skipped on signals, so registered targets are cleaned up by the handler.
+    registry::handle_signals().expect("Failed to handle signals");
+
     pretty_print("Basic case");
     {
         let file_path = Path::new("example.txt");
@@ -84,6 +93,95 @@ fn main() {
         let _file_deleter = v3::FileDeleter::new(file_path.into(), true);
     }
     println!();
+
+    pretty_print("Clean up a directory with everything in it");
+    {
+        let dir_path = Path::new("example_dir");
+        fs::create_dir_all(dir_path.join("nested")).expect("Failed to create dir");
+        fs::File::create(dir_path.join("nested/example.txt")).expect("Failed to create file");
+        println!("Dir '{}' created", dir_path.display());
+        let _guard = CleanupGuard::new(CleanupTarget::DirRecursive(dir_path.into()));
+    }
+    println!();
+
+    pretty_print("Observe failures with `close`");
+    {
+        let link_path = Path::new("example_link");
+        fs::create_dir_all(link_path).expect("Failed to create dir");
+        println!("Dir '{}' created in place of the symlink", link_path.display());
+        let guard = CleanupGuard::new(CleanupTarget::SymlinkOnly(link_path.into()));
+        if let Err(err) = guard.close() {
+            println!("Failed to clean up: {err}");
+        }
+        fs::remove_dir(link_path).expect("Failed to remove dir");
+    }
+    println!();
+
+    pretty_print("Retain for all clones");
+    {
+        let file_path = Path::new("example.txt");
+        let guard = CleanupGuard::new(CleanupTarget::File(file_path.into()));
+        let clone = guard.clone();
+        clone.retain();
+        assert_eq!(guard.target(), None);
+    }
+    println!();
+
+    pretty_print("Persist files matching a glob instead of deleting them");
+    {
+        fs::File::create("example_1.tmp").expect("Failed to create file");
+        fs::File::create("example_2.tmp").expect("Failed to create file");
+        fs::create_dir_all("example_dir").expect("Failed to create dir");
+        let guard = CleanupGuard::new(CleanupTarget::Glob("example_*.tmp".into()));
+        guard.persist_to("example_dir").expect("Failed to persist");
+        let _dir_guard = CleanupGuard::new(CleanupTarget::DirRecursive("example_dir".into()));
+    }
+    println!();
+
+    pretty_print("Clean up on the blocking pool of a runtime");
+    {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to build runtime");
+        runtime.block_on(async {
+            let file_path = Path::new("example_async.txt");
+            fs::File::create(file_path).expect("Failed to create file");
+            println!("File '{}' created", file_path.display());
+            let guard = AsyncCleanupGuard::new(CleanupTarget::File(file_path.into()));
+            guard.close().await.expect("Failed to clean up");
+            println!("File '{}' exists: {}", file_path.display(), file_path.exists());
+        });
+    }
+    println!();
+
+    pretty_print("Keep file after a panic");
+    {
+        let file_path = Path::new("example_panic.txt");
+        fs::File::create(file_path).expect("Failed to create file");
+        println!("File '{}' created", file_path.display());
+        let guard = AsyncCleanupGuard::with_policy(
+            CleanupTarget::File(file_path.into()),
+            PanicPolicy::Keep,
+        );
+        println!("Guard with {:?} policy for {:?}", guard.on_panic, guard.target());
+        let result = std::panic::catch_unwind(move || {
+            let _guard = guard;
+            panic!("Something went wrong");
+        });
+        assert!(result.is_err());
+        let _guard = CleanupGuard::new(CleanupTarget::File(file_path.into()));
+    }
+    println!();
+
+    pretty_print("Clean up on exit, which skips `Drop`");
+    {
+        let file_path = Path::new("example_exit.txt");
+        fs::File::create(file_path).expect("Failed to create file");
+        println!("File '{}' created", file_path.display());
+        let _guard = CleanupGuard::new(CleanupTarget::File(file_path.into()));
+        registry::cleanup_and_exit(0);
+    }
 }
 
 fn pretty_print(str: &str) {
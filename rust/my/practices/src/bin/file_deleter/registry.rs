@@ -0,0 +1,203 @@
+//! Global registry of targets to clean up, as `std::process::exit` and signals skip `Drop`,
+//! like in `experiments/src/bin/exit_with_drop.rs`.
+//!
+//! Guards and `FileDeleter`s keep a [`Registration`] in their shared inner, so a target is registered once for all
+//! clones. Whoever takes it from the registry first, the last drop, [`cleanup_and_exit`]
+//! or a signal, cleans it up, and nobody else does.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    sync::{
+        Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use signal_hook::consts::signal::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+use super::v4::{CleanupError, CleanupTarget};
+
+static TARGETS: Mutex<BTreeMap<u64, CleanupTarget>> = Mutex::new(BTreeMap::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn targets() -> MutexGuard<'static, BTreeMap<u64, CleanupTarget>> {
+    TARGETS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Unregisters its target on drop, without cleaning it up.
+#[derive(Debug)]
+pub struct Registration(u64);
+
+impl Registration {
+    pub fn new(target: CleanupTarget) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        targets().insert(id, target);
+        Self(id)
+    }
+
+    /// `None` once taken, here or by [`cleanup_all`].
+    pub fn target(&self) -> Option<CleanupTarget> {
+        targets().get(&self.0).cloned()
+    }
+
+    /// Unregisters the target, so that the caller is the only one to clean it up.
+    pub fn take(&self) -> Option<CleanupTarget> {
+        targets().remove(&self.0)
+    }
+
+    /// Moves the target to a new registration, which cleans it up elsewhere while it stays registered.
+    /// `None` once taken.
+    pub fn transfer(&self) -> Option<Registration> {
+        let mut targets = targets();
+        let target = targets.remove(&self.0)?;
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        targets.insert(id, target);
+        Some(Self(id))
+    }
+
+    /// Registers the target again, like after it failed to move.
+    pub fn restore(&self, target: CleanupTarget) {
+        targets().insert(self.0, target);
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+/// Cleans up all registered targets, in the order of registration, and returns the failures.
+pub fn cleanup_all() -> Vec<CleanupError> {
+    let targets = std::mem::take(&mut *targets());
+    targets.into_values().filter_map(|target| target.clean_up().err()).collect()
+}
+
+/// Cleans up all registered targets, which `std::process::exit` would leak, and exits.
+pub fn cleanup_and_exit(code: i32) -> ! {
+    for err in cleanup_all() {
+        eprintln!("Failed to clean up before exit: {err}");
+    }
+    std::process::exit(code);
+}
+
+/// Cleans up all registered targets on SIGINT or SIGTERM, and then terminates as if
+/// there were no handler. Call once at the start of `main`.
+pub fn handle_signals() -> io::Result<()> {
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    std::thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            for err in cleanup_all() {
+                eprintln!("Failed to clean up on signal {signal}: {err}");
+            }
+            // Terminates with the signal, like the default handler.
+            let _ = signal_hook::low_level::emulate_default_handler(signal);
+            std::process::exit(128 + signal);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, process::Command};
+
+    use super::*;
+    use crate::v4::CleanupGuard;
+    use crate::{v2, v3};
+
+    /// Tests of exits and signals run in a child process, which is this test itself.
+    const CHILD: &str = "FILE_DELETER_CHILD";
+
+    fn registered(path: &std::path::Path) -> usize {
+        let target = CleanupTarget::File(path.to_path_buf());
+        targets().values().filter(|registered| **registered == target).count()
+    }
+
+    #[test]
+    fn registers_once_for_all_clones() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.child("shared.txt");
+        fs::write(&path, "temp").unwrap();
+
+        let guard = CleanupGuard::new(CleanupTarget::File(path.clone()));
+        let clones = [guard.clone(), guard.clone()];
+        assert_eq!(guard.target(), Some(CleanupTarget::File(path.clone())));
+        assert_eq!(registered(&path), 1);
+
+        drop(clones);
+        assert!(path.exists());
+        drop(guard);
+        assert!(!path.exists());
+        assert_eq!(registered(&path), 0);
+    }
+
+    #[test]
+    fn registers_file_deleters() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let (deleted, retained) = (dir.child("deleted.txt"), dir.child("retained.txt"));
+        fs::write(&deleted, "temp").unwrap();
+        fs::write(&retained, "temp").unwrap();
+
+        let deleter = v3::FileDeleter::new(deleted.clone(), false);
+        let clone = deleter.clone();
+        assert_eq!(registered(&deleted), 1);
+        drop(deleter);
+        assert!(deleted.exists());
+        assert_eq!(registered(&deleted), 1);
+        drop(clone);
+        assert!(!deleted.exists());
+        assert_eq!(registered(&deleted), 0);
+
+        let deleter = v3::FileDeleter::new(retained.clone(), true);
+        assert_eq!(registered(&retained), 0);
+        drop(deleter);
+        assert!(retained.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cleans_up_on_exit_and_signals() {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Ok(mode) = std::env::var(CHILD) {
+            let dir = PathBuf::from(std::env::var("FILE_DELETER_DIR").unwrap());
+            let guard = CleanupGuard::new(CleanupTarget::File(dir.join("v4")));
+            let _clones = (guard.clone(), v2::FileDeleter::new(dir.join("v2")).clone());
+            let deleter = v3::FileDeleter::new(dir.join("v3"), false);
+            let _clones = (deleter.clone(), v3::FileDeleter::new(dir.join("kept"), true));
+            handle_signals().unwrap();
+            match mode.as_str() {
+                "exit" => cleanup_and_exit(3),
+                _ => signal_hook::low_level::raise(SIGTERM).unwrap(),
+            }
+            // Waits for the signal thread to terminate the process.
+            std::thread::sleep(std::time::Duration::from_secs(10));
+            unreachable!("Process is not terminated by the signal");
+        }
+
+        for mode in ["exit", "signal"] {
+            let dir = temp_dir::TempDir::new().unwrap();
+            for name in ["v2", "v3", "v4", "kept"] {
+                fs::write(dir.child(name), "temp").unwrap();
+            }
+            let status = Command::new(std::env::current_exe().unwrap())
+                .args(["registry::tests::cleans_up_on_exit_and_signals", "--exact"])
+                .env(CHILD, mode)
+                .env("FILE_DELETER_DIR", dir.path())
+                .output()
+                .unwrap()
+                .status;
+            match mode {
+                "exit" => assert_eq!(status.code(), Some(3)),
+                _ => assert_eq!(status.signal(), Some(SIGTERM)),
+            }
+            for name in ["v2", "v3", "v4"] {
+                assert!(!dir.child(name).exists(), "{mode}: {name}");
+            }
+            assert!(dir.child("kept").exists(), "{mode}");
+        }
+    }
+}
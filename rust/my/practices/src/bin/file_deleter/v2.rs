@@ -1,5 +1,8 @@
 use std::{ops::Deref, path::PathBuf, sync::Arc};
 
+use super::registry::Registration;
+use super::v4::CleanupTarget;
+
 #[derive(Debug, Clone)]
 pub struct FileDeleter {
     file_deleter_inner: Arc<FileDeleterInner>,
@@ -13,19 +16,26 @@ impl Deref for FileDeleter {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct FileDeleterInner {
     pub file_path: PathBuf,
+    registration: Registration,
 }
 
 impl FileDeleter {
     pub fn new(file_path: impl Into<PathBuf>) -> Self {
-        Self { file_deleter_inner: Arc::new(FileDeleterInner { file_path: file_path.into() }) }
+        let file_path = file_path.into();
+        let registration = Registration::new(CleanupTarget::File(file_path.clone()));
+        Self { file_deleter_inner: Arc::new(FileDeleterInner { file_path, registration }) }
     }
 }
 
 impl Drop for FileDeleterInner {
     fn drop(&mut self) {
+        // Already deleted on exit or a signal.
+        if self.registration.take().is_none() {
+            return;
+        }
         if let Err(err) = std::fs::remove_file(&self.file_path) {
             println!("Failed to delete '{}' file: {}", self.file_path.display(), err);
         } else {
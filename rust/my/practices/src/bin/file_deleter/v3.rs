@@ -2,6 +2,9 @@
 
 use std::{ops::Deref, path::PathBuf, sync::Arc};
 
+use super::registry::Registration;
+use super::v4::CleanupTarget;
+
 /// Helps to delete a file by its path on drop.
 /// [`FileDeleter`] is a wrapped [`FileDeleterInner`] in [`Arc`] for easier cloning,
 /// and more importantly for deleting *only* when the last [`FileDeleter`] will drop.
@@ -12,7 +15,12 @@ pub struct FileDeleter {
 
 impl FileDeleter {
     pub fn new(path: PathBuf, retain: bool) -> Self {
-        Self { file_deleter_inner: Arc::new(FileDeleterInner { path, retain }) }
+        let registration = Registration::new(CleanupTarget::File(path.clone()));
+        // Retained files are not deleted on exit either.
+        if retain {
+            registration.take();
+        }
+        Self { file_deleter_inner: Arc::new(FileDeleterInner { path, retain, registration }) }
     }
 }
 
@@ -26,10 +34,12 @@ impl Deref for FileDeleter {
 
 /// Deletes a file by its path on drop.
 /// Can specify whether to retain a file so it won't be deleted.
-#[derive(Debug, Clone)]
+/// The file is in the [`registry`](super::registry) too, to be deleted on exit or a signal.
+#[derive(Debug)]
 pub struct FileDeleterInner {
     pub path: PathBuf,
     pub retain: bool,
+    registration: Registration,
 }
 
 impl Drop for FileDeleterInner {
@@ -37,6 +47,10 @@ impl Drop for FileDeleterInner {
         if self.retain {
             println!("File '{}' will be retained", self.path.display());
         } else {
+            // Already deleted on exit or a signal.
+            if self.registration.take().is_none() {
+                return;
+            }
             if self.path.exists() {
                 if let Err(err) = std::fs::remove_file(&self.path) {
                     println!("Failed to delete '{}' file: {}", self.path.display(), err);
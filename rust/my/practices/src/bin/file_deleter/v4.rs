@@ -0,0 +1,387 @@
+//! This module generalises [`FileDeleter`](super::v3::FileDeleter) into [`CleanupGuard`],
+//! which cleans up any [`CleanupTarget`] on drop, unless it is retained or persisted.
+//!
+//! Symlinks are never followed, so a guard can't remove anything outside of its target.
+//! Targets are kept in the [`registry`](super::registry), so that they are cleaned up on exit too.
+
+use std::{
+    fs, io,
+    ops::Deref,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use super::registry::Registration;
+
+/// What to clean up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CleanupTarget {
+    /// A file, or a symlink itself.
+    File(PathBuf),
+    /// A directory with everything in it, symlinks inside are removed but not followed.
+    DirRecursive(PathBuf),
+    /// Files and directories matching the pattern when cleaning up, like `target/*.tmp`.
+    Glob(String),
+    /// A symlink, but not a file or directory in its place.
+    SymlinkOnly(PathBuf),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CleanupError {
+    #[error("Failed to remove '{}'", .0.display())]
+    Remove(PathBuf, #[source] io::Error),
+
+    #[error("'{}' is not a symlink, so it is not removed", .0.display())]
+    NotSymlink(PathBuf),
+
+    #[error("Invalid glob pattern '{0}'")]
+    Pattern(String, #[source] glob::PatternError),
+
+    #[error("Failed to clean up {} paths matching '{pattern}'", .failures.len())]
+    Glob { pattern: String, failures: Vec<CleanupError> },
+
+    #[error("Failed to move '{}' to '{}'", .from.display(), .to.display())]
+    Persist {
+        from: PathBuf,
+        to: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// Cleans up its target when the last clone drops, like [`FileDeleter`](super::v3::FileDeleter).
+/// Use [`CleanupGuard::close`] to observe failures, which `Drop` can only print.
+#[derive(Debug, Clone)]
+pub struct CleanupGuard {
+    inner: Arc<CleanupGuardInner>,
+}
+
+impl CleanupGuard {
+    pub fn new(target: CleanupTarget) -> Self {
+        Self { inner: Arc::new(CleanupGuardInner { registration: Registration::new(target) }) }
+    }
+
+    /// Keeps the target, for all clones.
+    pub fn retain(&self) {
+        self.inner.take();
+    }
+
+    /// Moves the target to `path` and keeps it there. Matches of a glob are moved into
+    /// the `path` directory. The target is still cleaned up if moving fails.
+    pub fn persist_to(&self, path: impl AsRef<Path>) -> Result<(), CleanupError> {
+        match self.inner.take() {
+            Some(target) => persist(&target, path.as_ref())
+                .inspect_err(|_| self.inner.registration.restore(target)),
+            None => Ok(()),
+        }
+    }
+
+    /// Cleans up now, even if there are other clones, which won't clean up again.
+    pub fn close(self) -> Result<(), CleanupError> {
+        self.inner.take().map_or(Ok(()), |target| target.clean_up())
+    }
+}
+
+impl Deref for CleanupGuard {
+    type Target = CleanupGuardInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[derive(Debug)]
+pub struct CleanupGuardInner {
+    registration: Registration,
+}
+
+impl CleanupGuardInner {
+    /// `None` once retained, persisted or closed.
+    pub fn target(&self) -> Option<CleanupTarget> {
+        self.registration.target()
+    }
+
+    fn take(&self) -> Option<CleanupTarget> {
+        self.registration.take()
+    }
+}
+
+impl Drop for CleanupGuardInner {
+    fn drop(&mut self) {
+        // Nothing to do once retained, persisted or closed.
+        if let Some(target) = self.take() {
+            match target.clean_up() {
+                Ok(()) => println!("Cleaned up {target:?}"),
+                Err(err) => println!("Failed to clean up {target:?}: {err}"),
+            }
+        }
+    }
+}
+
+impl CleanupTarget {
+    /// Missing targets are already cleaned up.
+    pub fn clean_up(&self) -> Result<(), CleanupError> {
+        match self {
+            CleanupTarget::File(path) => remove(path, fs::remove_file(path)),
+            CleanupTarget::DirRecursive(path) => remove_any(path),
+            CleanupTarget::Glob(pattern) => {
+                let failures: Vec<_> = matches(pattern)?
+                    .into_iter()
+                    .filter_map(|path| path.and_then(|path| remove_any(&path)).err())
+                    .collect();
+                match failures.is_empty() {
+                    true => Ok(()),
+                    false => Err(CleanupError::Glob { pattern: pattern.clone(), failures }),
+                }
+            }
+            CleanupTarget::SymlinkOnly(path) => match fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.is_symlink() => remove(path, fs::remove_file(path)),
+                Ok(_) => Err(CleanupError::NotSymlink(path.clone())),
+                Err(err) => remove(path, Err(err)),
+            },
+        }
+    }
+}
+
+/// Walks from the directory before the first wildcard, because `glob::glob` follows symlinks
+/// to directories. Only directories which can have matches in them are walked, and matched ones
+/// aren't, as they are cleaned up with everything in them.
+fn matches(pattern: &str) -> Result<Vec<Result<PathBuf, CleanupError>>, CleanupError> {
+    let compiled =
+        glob::Pattern::new(pattern).map_err(|err| CleanupError::Pattern(pattern.into(), err))?;
+    let options = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+    let is_literal =
+        |component: &Component| !component.as_os_str().to_string_lossy().contains(['*', '?', '[']);
+    let base: PathBuf = Path::new(pattern).components().take_while(is_literal).collect();
+    let root = match base.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => &base,
+    };
+    // Patterns of the components after `base`, `None` for `**`.
+    let wildcards: Vec<Option<glob::Pattern>> = Path::new(pattern)
+        .components()
+        .skip(base.components().count())
+        .map(|component| match component.as_os_str().to_string_lossy().as_ref() {
+            "**" => None,
+            // Like `**` if a component is not a pattern on its own, which only widens the walk.
+            component => glob::Pattern::new(component).ok(),
+        })
+        .collect();
+    // Whether `path` under `root` is a match, or a directory with matches in it.
+    let can_match = |path: &Path| {
+        let mut components = path.strip_prefix(root).unwrap_or(path).components();
+        for wildcard in &wildcards {
+            let Some(wildcard) = wildcard else {
+                return true;
+            };
+            match components.next() {
+                Some(component) if wildcard.matches(&component.as_os_str().to_string_lossy()) => {}
+                Some(_) => return false,
+                None => return true,
+            }
+        }
+        components.next().is_none()
+    };
+
+    let mut walk = walkdir::WalkDir::new(root).follow_links(false).sort_by_file_name();
+    if wildcards.iter().all(Option::is_some) {
+        walk = walk.max_depth(wildcards.len());
+    }
+    let mut paths = vec![];
+    let mut entries = walk.into_iter();
+    while let Some(entry) = entries.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            // Nothing to match, or removed in the meantime.
+            Err(err) if err.io_error().is_some_and(|err| err.kind() == io::ErrorKind::NotFound) => {
+                continue;
+            }
+            Err(err) => {
+                let path = err.path().unwrap_or(root).to_path_buf();
+                if can_match(&path) {
+                    paths.push(Err(CleanupError::Remove(path, err.into())));
+                }
+                continue;
+            }
+        };
+        // Relative patterns match paths without the `./` of the walk.
+        let path = match base.as_os_str().is_empty() {
+            true => entry.path().strip_prefix(".").unwrap_or(entry.path()),
+            false => entry.path(),
+        };
+        if compiled.matches_path_with(path, options) {
+            paths.push(Ok(path.to_path_buf()));
+            if entry.file_type().is_dir() {
+                entries.skip_current_dir();
+            }
+        } else if entry.file_type().is_dir() && !can_match(entry.path()) {
+            entries.skip_current_dir();
+        }
+    }
+    Ok(paths)
+}
+
+/// Removes a directory recursively, or anything else as a file, without following symlinks.
+fn remove_any(path: &Path) -> Result<(), CleanupError> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(err) => Err(err),
+    };
+    remove(path, result)
+}
+
+fn remove(path: &Path, result: io::Result<()>) -> Result<(), CleanupError> {
+    match result {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            Err(CleanupError::Remove(path.to_path_buf(), err))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn persist(target: &CleanupTarget, to: &Path) -> Result<(), CleanupError> {
+    let rename = |from: &Path, to: PathBuf| {
+        fs::rename(from, &to).map_err(|source| CleanupError::Persist {
+            from: from.to_path_buf(),
+            to,
+            source,
+        })
+    };
+    match target {
+        CleanupTarget::File(path)
+        | CleanupTarget::DirRecursive(path)
+        | CleanupTarget::SymlinkOnly(path) => rename(path, to.to_path_buf()),
+        CleanupTarget::Glob(pattern) => {
+            for path in matches(pattern)? {
+                let path = path?;
+                let name = path.file_name().expect("Glob matches have file names");
+                rename(&path, to.join(name))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create(paths: &[&Path]) {
+        for path in paths {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "temp").unwrap();
+        }
+    }
+
+    #[test]
+    fn cleans_up_on_drop_of_last_clone() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let nested = dir.child("build/out/data.bin");
+        create(&[&nested]);
+
+        let guard = CleanupGuard::new(CleanupTarget::DirRecursive(dir.child("build")));
+        let clone = guard.clone();
+        drop(guard);
+        assert!(nested.exists());
+        drop(clone);
+        assert!(!dir.child("build").exists());
+    }
+
+    #[test]
+    fn cleans_up_glob_matches() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let (a, b, kept) = (dir.child("a.tmp"), dir.child("cache.tmp/b"), dir.child("c.txt"));
+        create(&[&a, &b, &kept]);
+
+        let pattern = format!("{}/*.tmp", dir.path().display());
+        CleanupGuard::new(CleanupTarget::Glob(pattern)).close().unwrap();
+        assert!(!a.exists() && !dir.child("cache.tmp").exists());
+        assert!(kept.exists());
+
+        let err = CleanupGuard::new(CleanupTarget::Glob("[".into())).close().unwrap_err();
+        assert!(matches!(err, CleanupError::Pattern(..)), "{err:?}");
+    }
+
+    #[test]
+    fn retains_and_persists() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let (file, report) = (dir.child("file.txt"), dir.child("report.txt"));
+        create(&[&file, &report]);
+
+        let guard = CleanupGuard::new(CleanupTarget::File(file.clone()));
+        guard.clone().retain();
+        assert_eq!(guard.target(), None);
+        drop(guard);
+        assert!(file.exists());
+
+        let guard = CleanupGuard::new(CleanupTarget::File(report.clone()));
+        let err = guard.persist_to(dir.child("missing/report.txt")).unwrap_err();
+        assert!(matches!(err, CleanupError::Persist { .. }), "{err:?}");
+        guard.persist_to(dir.child("kept.txt")).unwrap();
+        guard.close().unwrap();
+        assert!(!report.exists() && dir.child("kept.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn never_follows_symlinks() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let (target, link) = (dir.child("real/data.txt"), dir.child("link"));
+        create(&[&target]);
+        std::os::unix::fs::symlink(dir.child("real"), &link).unwrap();
+
+        let err =
+            CleanupGuard::new(CleanupTarget::SymlinkOnly(target.clone())).close().unwrap_err();
+        assert!(matches!(err, CleanupError::NotSymlink(_)), "{err:?}");
+        CleanupGuard::new(CleanupTarget::DirRecursive(link.clone())).close().unwrap();
+        assert!(!link.exists() && target.exists());
+
+        std::os::unix::fs::symlink(dir.child("real"), &link).unwrap();
+        CleanupGuard::new(CleanupTarget::SymlinkOnly(link.clone())).close().unwrap();
+        assert!(fs::symlink_metadata(&link).is_err() && target.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_walk_errors_only_where_matches_can_be() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir::TempDir::new().unwrap();
+        let (tmp, locked) = (dir.child("a.tmp"), dir.child("locked"));
+        create(&[&tmp, &locked.join("b.tmp")]);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // Root reads it anyway, then there are no errors to report.
+        let readable = fs::read_dir(&locked).is_ok();
+
+        let pattern = format!("{}/*.tmp", dir.path().display());
+        CleanupGuard::new(CleanupTarget::Glob(pattern)).close().unwrap();
+        assert!(!tmp.exists());
+
+        let pattern = format!("{}/*/b.tmp", dir.path().display());
+        let result = CleanupGuard::new(CleanupTarget::Glob(pattern)).close();
+        assert_eq!(result.is_ok(), readable, "{result:?}");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn glob_does_not_follow_symlinked_dirs() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let outside = temp_dir::TempDir::new().unwrap();
+        let (kept, removed) = (outside.child("keep.tmp"), dir.child("build/out/a.tmp"));
+        create(&[&kept, &removed]);
+        let link = dir.child("build/link");
+        std::os::unix::fs::symlink(outside.path(), &link).unwrap();
+
+        let pattern = format!("{}/**/*.tmp", dir.path().display());
+        CleanupGuard::new(CleanupTarget::Glob(pattern)).close().unwrap();
+        assert!(!removed.exists());
+        assert!(kept.exists() && fs::symlink_metadata(&link).is_ok());
+
+        // A matching symlink is removed itself.
+        let pattern = format!("{}/build/li*", dir.path().display());
+        CleanupGuard::new(CleanupTarget::Glob(pattern)).close().unwrap();
+        assert!(fs::symlink_metadata(&link).is_err() && kept.exists());
+    }
+}
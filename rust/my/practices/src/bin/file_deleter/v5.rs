@@ -0,0 +1,225 @@
+//! This module makes [`CleanupGuard`](super::v4::CleanupGuard) async-aware as [`AsyncCleanupGuard`],
+//! so that it doesn't block the executor, and lets it keep its target when dropped during a panic.
+//!
+//! Removing files is blocking I/O, like in `my/async/src/bin/blocking_task.rs`. Inside a tokio
+//! runtime, including `tokio_uring::start`, it runs on the blocking pool. The target stays registered
+//! until then, and if the runtime shuts down before the blocking task starts, dropping the task
+//! cleans up on the thread which shuts down the runtime.
+
+use std::{ops::Deref, sync::Arc};
+
+use super::registry::Registration;
+use super::v4::{CleanupError, CleanupTarget};
+
+/// What to do with the target when the guard drops during a panic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    #[default]
+    CleanUp,
+    /// Keeps the target for post-mortem debugging.
+    Keep,
+}
+
+/// Cleans up its target when the last clone drops, on the blocking pool if there is a runtime.
+/// Use [`AsyncCleanupGuard::close`] to wait for the clean up and observe failures.
+#[derive(Debug, Clone)]
+pub struct AsyncCleanupGuard {
+    inner: Arc<AsyncCleanupGuardInner>,
+}
+
+impl AsyncCleanupGuard {
+    pub fn new(target: CleanupTarget) -> Self {
+        Self::with_policy(target, PanicPolicy::default())
+    }
+
+    /// Clones share the policy, so it is only set here.
+    pub fn with_policy(target: CleanupTarget, on_panic: PanicPolicy) -> Self {
+        Self {
+            inner: Arc::new(AsyncCleanupGuardInner {
+                registration: Some(Registration::new(target)),
+                on_panic,
+            }),
+        }
+    }
+
+    /// Cleans up on the blocking pool, even if there are other clones, which won't clean up again.
+    pub async fn close(self) -> Result<(), CleanupError> {
+        let Some(registration) = self.inner.registration.as_ref().and_then(Registration::transfer)
+        else {
+            return Ok(());
+        };
+        let clean_up = CleanUpOnDrop(registration);
+        match tokio::task::spawn_blocking(move || clean_up.run()).await {
+            Ok(result) => result,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            // Cancelled by a runtime shutdown, dropping the task cleaned up and printed failures.
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+impl Deref for AsyncCleanupGuard {
+    type Target = AsyncCleanupGuardInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[derive(Debug)]
+pub struct AsyncCleanupGuardInner {
+    /// Only `None` once moved to the clean up on drop.
+    registration: Option<Registration>,
+    pub on_panic: PanicPolicy,
+}
+
+impl AsyncCleanupGuardInner {
+    /// `None` once closed.
+    pub fn target(&self) -> Option<CleanupTarget> {
+        self.registration.as_ref()?.target()
+    }
+}
+
+impl Drop for AsyncCleanupGuardInner {
+    fn drop(&mut self) {
+        let Some(registration) = self.registration.take() else {
+            return;
+        };
+        if std::thread::panicking() && self.on_panic == PanicPolicy::Keep {
+            if let Some(target) = registration.take() {
+                println!("Kept {target:?} after a panic");
+            }
+            return;
+        }
+        let clean_up = CleanUpOnDrop(registration);
+        // Without a runtime blocking is fine.
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => drop(runtime.spawn_blocking(move || drop(clean_up))),
+            Err(_) => drop(clean_up),
+        }
+    }
+}
+
+/// Cleans up the registered target on drop, also when the runtime drops its blocking task
+/// without running it, which it does on shutdown.
+struct CleanUpOnDrop(Registration);
+
+impl CleanUpOnDrop {
+    fn run(self) -> Result<(), CleanupError> {
+        self.0.take().map_or(Ok(()), |target| target.clean_up())
+    }
+}
+
+impl Drop for CleanUpOnDrop {
+    fn drop(&mut self) {
+        // Nothing to do once closed, or cleaned up on exit.
+        if let Some(target) = self.0.take() {
+            match target.clean_up() {
+                Ok(()) => println!("Cleaned up {target:?}"),
+                Err(err) => println!("Failed to clean up {target:?}: {err}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, time::Duration};
+
+    use super::*;
+
+    fn file(dir: &temp_dir::TempDir, name: &str) -> AsyncCleanupGuard {
+        file_with(dir, name, PanicPolicy::default())
+    }
+
+    fn file_with(dir: &temp_dir::TempDir, name: &str, on_panic: PanicPolicy) -> AsyncCleanupGuard {
+        let path = dir.child(name);
+        fs::write(&path, "temp").unwrap();
+        AsyncCleanupGuard::with_policy(CleanupTarget::File(path), on_panic)
+    }
+
+    /// Dropping doesn't wait for the blocking pool.
+    async fn wait_removed(path: &Path) {
+        for _ in 0..100 {
+            if !path.exists() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("'{}' is not removed", path.display());
+    }
+
+    #[tokio::test]
+    async fn cleans_up_on_tokio() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        file(&dir, "closed.txt").close().await.unwrap();
+        assert!(!dir.child("closed.txt").exists());
+
+        let guard = file(&dir, "dropped.txt");
+        let clone = guard.clone();
+        tokio::spawn(async move { drop(clone) }).await.unwrap();
+        assert!(dir.child("dropped.txt").exists());
+        drop(guard);
+        wait_removed(&dir.child("dropped.txt")).await;
+    }
+
+    #[test]
+    fn cleans_up_when_runtime_shuts_down() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        // The runtime skips queued blocking tasks when it shuts down before an idle thread
+        // picks them up, which is a race, so it is tried a few times.
+        for i in 0..40 {
+            let (guard, closed) = (file(&dir, &format!("{i}.txt")), i % 2 == 1);
+            // Outlives the runtime, so that the close is the only clean up for it to run.
+            let clone = closed.then(|| guard.clone());
+            let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+            runtime.block_on(async {
+                // Leaves an idle blocking thread, which is woken for the clean up.
+                tokio::task::spawn_blocking(|| ()).await.unwrap();
+                std::thread::sleep(Duration::from_millis(5));
+                match closed {
+                    // Starts the blocking task, which the runtime drops like the close.
+                    true => assert!(futures::poll!(Box::pin(guard.close())).is_pending()),
+                    false => drop(guard),
+                }
+            });
+            drop(runtime);
+            assert!(!dir.child(format!("{i}.txt")).exists(), "{i}");
+            drop(clone);
+        }
+    }
+
+    #[test]
+    fn cleans_up_on_tokio_uring() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        tokio_uring::start(async {
+            file(&dir, "closed.txt").close().await.unwrap();
+            drop(file(&dir, "dropped.txt"));
+            wait_removed(&dir.child("dropped.txt")).await;
+        });
+        assert!(!dir.child("closed.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn keeps_target_on_panic() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        for (name, on_panic) in
+            [("kept.txt", PanicPolicy::Keep), ("removed.txt", PanicPolicy::CleanUp)]
+        {
+            let guard = file_with(&dir, name, on_panic);
+            let err = tokio::spawn(async move {
+                let _guard = guard;
+                panic!("Failed with {name}");
+            })
+            .await
+            .unwrap_err();
+            assert!(err.is_panic());
+        }
+        assert!(dir.child("kept.txt").exists());
+        wait_removed(&dir.child("removed.txt")).await;
+
+        // A normal scope exit cleans up with any policy.
+        drop(file_with(&dir, "kept.txt", PanicPolicy::Keep));
+        wait_removed(&dir.child("kept.txt")).await;
+    }
+}
@@ -4,6 +4,9 @@ pub enum Error {
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
+    #[error("Read callback failed")]
+    Callback(#[source] Box<dyn std::error::Error + Send + Sync>),
+
     #[error("Other")]
     Other,
 }
@@ -11,3 +14,11 @@ pub enum Error {
 pub fn read_file(path: &std::path::Path) -> Result<Vec<u8>, Error> {
     Ok(std::fs::read(path)?)
 }
+
+// Calls our `read`, whose error is only a `dyn Error` here.
+pub fn read_file_with<E: std::error::Error + Send + Sync + 'static>(
+    path: &std::path::Path,
+    read: impl FnOnce(&std::path::Path) -> Result<Vec<u8>, E>,
+) -> Result<Vec<u8>, Error> {
+    read(path).map_err(|err| Error::Callback(Box::new(err)))
+}
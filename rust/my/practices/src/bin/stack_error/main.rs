@@ -1,23 +1,28 @@
 use my_practices::print_err;
-use stack_error::StackError;
+use my_practices::stack_error::{Compact, Json, StackError, registry};
 
 mod external;
 mod internal;
 mod my;
-mod stack_error;
 
 fn main() {
-    let res = my::read_two_files("Cargo.toml".as_ref(), "file2".as_ref());
-    match res {
-        Ok(buf) => println!("We read buf: {buf:?}"),
-        Err(err) => {
-            print_err!(err);
+    registry::register::<internal::Error>();
 
-            // Print err as `StackError`.
-            println!("Stack Error:");
-            let mut buf = vec![];
-            err.format(0, &mut buf);
-            dbg!(buf);
+    let results = [
+        my::read_two_files("Cargo.toml".as_ref(), "file2".as_ref()),
+        // Locations of `internal::Error` are found behind `external::Error` too, as it is registered.
+        my::read_file_via_external("file2".as_ref()),
+    ];
+    for res in results {
+        match res {
+            Ok(buf) => println!("We read buf: {buf:?}"),
+            Err(err) => {
+                print_err!(err);
+
+                // Print err as `StackError`, `STACK_ERROR_LOCATIONS=hash` hides paths of the locations.
+                println!("Stack Error: {}", err.report(Compact));
+                println!("Stack Error as JSON: {}", err.report(Json));
+            }
         }
     }
 }
@@ -1,3 +1,6 @@
+// `#[derive(StackError)]` refers to `::my_practices`, which is this crate inside it.
+extern crate self as my_practices;
+
 #[macro_export]
 macro_rules! print_err {
     ($err:expr) => {
@@ -6,12 +9,17 @@ macro_rules! print_err {
         eprintln!("Display alternate:\n{:#}", $err);
         eprintln!("Debug:\n{:?}", $err);
         eprintln!("Debug alternate:\n{:#?}", $err);
-        my_practices::error_chain(&$err);
+        {
+            #[allow(unused_imports)]
+            use $crate::stack_error::__private::{PrintChain as _, PrintStack as _, Wrap};
+            // `StackError`s are printed as the stack, other errors as the `source()` chain.
+            (&Wrap(&$err)).print_chain();
+        }
         eprintln!("------------------------------------------------------------------");
     };
 }
 
-pub fn error_chain(e: &impl std::error::Error) {
+pub fn error_chain(e: &(impl std::error::Error + ?Sized)) {
     let mut current = e.source();
     while let Some(cause) = current {
         eprintln!("Caused by: {cause}, dbg: {cause:?}");
@@ -19,6 +27,10 @@ pub fn error_chain(e: &impl std::error::Error) {
     }
 }
 
+pub mod api_client;
+pub mod stack_error;
+pub mod test_support;
+
 pub mod fibonacci {
     #[inline]
     pub fn number_recursive(n: u8) -> usize {
@@ -0,0 +1,145 @@
+//! Errors which know where they were created and which error of the stack is the next one.
+//!
+//! Derive [`StackError`] for error types, and display the whole stack with a [`Render`]er:
+//! `err.report(Tree)`. Locations are shown in the [`LocationStyle`] of [`location`].
+
+use std::panic::Location;
+
+pub use bridge::{LayerContext, StackResultExt};
+pub use location::{LocationStyle, set_location_style};
+pub use my_stack_error_derive::StackError;
+pub use render::{Colored, Compact, Json, Render, Report, Tree};
+
+pub mod bridge;
+pub mod location;
+pub mod registry;
+pub mod render;
+
+pub trait StackError: std::error::Error {
+    fn next(&self) -> Option<&dyn StackError>;
+
+    fn location(&self) -> Option<Location<'static>>;
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    fn report<R: Render>(&self, renderer: R) -> Report<'_, R>
+    where
+        Self: Sized,
+    {
+        Report::new(self, renderer)
+    }
+}
+
+/// One error of the stack.
+#[derive(Clone, Copy)]
+pub struct Layer<'a> {
+    pub error: &'a dyn std::error::Error,
+    /// `None` for plain sources between [`StackError`]s.
+    pub type_name: Option<&'static str>,
+    pub location: Option<Location<'static>>,
+}
+
+/// Errors of the stack from `error`. After the last `next()` the `source()` chain follows,
+/// where [`registry::register`]ed errors continue the stack.
+pub fn layers(error: &dyn StackError) -> Vec<Layer<'_>> {
+    let mut layers = vec![];
+    let mut next = Some(error);
+    while let Some(current) = next {
+        layers.push(Layer {
+            error: current,
+            type_name: Some(current.type_name()),
+            location: current.location(),
+        });
+        next = current.next();
+        if next.is_some() {
+            continue;
+        }
+
+        let mut source = current.source();
+        while let Some(error) = source {
+            next = registry::downcast(error);
+            if next.is_some() {
+                break;
+            }
+            layers.push(Layer { error, type_name: None, location: None });
+            source = error.source();
+        }
+    }
+    layers
+}
+
+/// Used by [`crate::print_err`] to print the stack of `StackError`s and the `source()` chain of other errors.
+#[doc(hidden)]
+pub mod __private {
+    use std::io::IsTerminal;
+
+    use super::*;
+
+    pub struct Wrap<'a, T: ?Sized>(pub &'a T);
+
+    pub trait PrintStack {
+        fn print_chain(&self);
+    }
+
+    impl<T: StackError> PrintStack for Wrap<'_, T> {
+        fn print_chain(&self) {
+            if std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none() {
+                eprintln!("Stack:\n{}", self.0.report(Colored));
+            } else {
+                eprintln!("Stack:\n{}", self.0.report(Tree));
+            }
+        }
+    }
+
+    pub trait PrintChain {
+        fn print_chain(&self);
+    }
+
+    impl<T: std::error::Error + ?Sized> PrintChain for &Wrap<'_, T> {
+        fn print_chain(&self) {
+            crate::error_chain(self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::Location;
+
+    use super::*;
+
+    #[derive(Debug, thiserror::Error, StackError)]
+    #[error("Stack error")]
+    struct Error(
+        #[source]
+        #[stack(from)]
+        std::io::Error,
+        #[stack(location)] Location<'static>,
+    );
+
+    /// Only checks that the derive compiles for an enum without variants.
+    #[derive(Debug, thiserror::Error, StackError)]
+    #[allow(dead_code)]
+    enum Never {}
+
+    #[test]
+    fn layers_end_with_sources() {
+        let err = Error::from(std::io::Error::other("io"));
+        let layers = layers(&err);
+
+        let type_names: Vec<_> = layers.iter().map(|layer| layer.type_name).collect();
+        assert_eq!(type_names, [Some("my_practices::stack_error::tests::Error"), None]);
+        assert_eq!(layers[0].location.unwrap().file(), file!());
+        assert_eq!(layers[1].error.to_string(), "io");
+    }
+
+    /// Only checks that `print_err!` compiles for both kinds of errors, it prints to stderr.
+    #[allow(dead_code)]
+    fn print_err_accepts_stack_and_plain_errors() {
+        let err = Error::from(std::io::Error::other("io"));
+        crate::print_err!(err);
+        crate::print_err!(std::io::Error::other("io"));
+    }
+}
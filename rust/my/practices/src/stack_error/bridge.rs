@@ -0,0 +1,295 @@
+//! Conversions of [`StackError`]s into `anyhow::Error` and `eyre::Report`, and an eyre handler
+//! rendering them as the stack.
+//!
+//! Error reports require `'static` errors, so the stack is copied into a chain of [`LayerContext`]s,
+//! which keep the location of every layer.
+//! With the `tracing-error` feature the first one also gets the `SpanTrace` of the conversion.
+
+use std::fmt::{self, Display};
+use std::panic::Location;
+
+use super::{LocationStyle, StackError, layers, location};
+
+/// Owned copy of a [`super::Layer`], displayed with its location. The next layer is its `source()`.
+#[derive(Debug)]
+pub struct LayerContext {
+    pub message: String,
+    pub type_name: Option<&'static str>,
+    pub location: Option<Location<'static>>,
+    /// [`location::current_style`] when the stack is copied.
+    pub location_style: LocationStyle,
+    #[cfg(feature = "tracing-error")]
+    pub span_trace: Option<tracing_error::SpanTrace>,
+    next: Option<Box<LayerContext>>,
+}
+
+impl LayerContext {
+    /// Copies the stack of `error`.
+    pub fn chain(error: &dyn StackError) -> Self {
+        let location_style = location::current_style();
+        let mut next = None;
+        for layer in layers(error).iter().rev() {
+            next = Some(Box::new(Self {
+                message: layer.error.to_string(),
+                type_name: layer.type_name,
+                location: layer.location,
+                location_style: location_style.clone(),
+                #[cfg(feature = "tracing-error")]
+                span_trace: None,
+                next,
+            }));
+        }
+        #[allow(unused_mut, reason = "only changed with the `tracing-error` feature")]
+        let mut context = *next.expect("stack has at least the error itself");
+        #[cfg(feature = "tracing-error")]
+        {
+            context.span_trace = Some(tracing_error::SpanTrace::capture());
+        }
+        context
+    }
+
+    /// Shows locations of this and the next layers in `style`.
+    pub fn with_location_style(mut self, style: LocationStyle) -> Self {
+        let mut context = Some(&mut self);
+        while let Some(layer) = context {
+            layer.location_style = style.clone();
+            context = layer.next.as_deref_mut();
+        }
+        self
+    }
+}
+
+impl Display for LayerContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if let Some(location) =
+            self.location.and_then(|location| self.location_style.show_location(location))
+        {
+            write!(f, ", at {location}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LayerContext {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.next.as_deref().map(|next| next as _)
+    }
+}
+
+impl StackError for LayerContext {
+    fn next(&self) -> Option<&dyn StackError> {
+        self.next.as_deref().map(|next| next as _)
+    }
+
+    fn location(&self) -> Option<Location<'static>> {
+        self.location
+    }
+
+    fn type_name(&self) -> &'static str {
+        self.type_name.unwrap_or(std::any::type_name::<Self>())
+    }
+}
+
+#[track_caller]
+pub fn to_anyhow(error: &dyn StackError) -> anyhow::Error {
+    anyhow::Error::new(LayerContext::chain(error))
+}
+
+/// Track caller, so that the `Location` section of `color-eyre` points to the conversion.
+#[cfg(feature = "eyre")]
+#[track_caller]
+pub fn to_eyre(error: &dyn StackError) -> eyre::Report {
+    eyre::Report::new(LayerContext::chain(error))
+}
+
+pub trait StackResultExt<T> {
+    fn into_anyhow(self) -> anyhow::Result<T>;
+
+    #[cfg(feature = "eyre")]
+    fn into_eyre(self) -> eyre::Result<T>;
+}
+
+impl<T, E: StackError> StackResultExt<T> for Result<T, E> {
+    #[track_caller]
+    fn into_anyhow(self) -> anyhow::Result<T> {
+        match self {
+            Ok(value) => Ok(value),
+            Err(err) => Err(to_anyhow(&err)),
+        }
+    }
+
+    #[cfg(feature = "eyre")]
+    #[track_caller]
+    fn into_eyre(self) -> eyre::Result<T> {
+        match self {
+            Ok(value) => Ok(value),
+            Err(err) => Err(to_eyre(&err)),
+        }
+    }
+}
+
+/// Eyre handler printing the report as [`super::Tree`]: `wrap_err` contexts first,
+/// then the stack of the first [`LayerContext`] with locations, and then the span trace.
+#[cfg(feature = "eyre")]
+#[derive(Debug, Default)]
+pub struct StackHandler {
+    /// Captured when the report is created, used if there is none in a [`LayerContext`].
+    #[cfg(feature = "tracing-error")]
+    span_trace: Option<tracing_error::SpanTrace>,
+}
+
+#[cfg(feature = "eyre")]
+impl StackHandler {
+    pub fn install() -> Result<(), eyre::InstallError> {
+        eyre::set_hook(Box::new(|_| {
+            Box::new(StackHandler {
+                #[cfg(feature = "tracing-error")]
+                span_trace: Some(tracing_error::SpanTrace::capture()),
+            })
+        }))
+    }
+}
+
+#[cfg(feature = "eyre")]
+impl eyre::EyreHandler for StackHandler {
+    fn debug(
+        &self,
+        error: &(dyn std::error::Error + 'static),
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        use super::{Layer, Render};
+
+        if f.alternate() {
+            return fmt::Debug::fmt(error, f);
+        }
+
+        let mut stack = vec![];
+        let mut context = None;
+        for error in eyre::Chain::new(error) {
+            if let Some(layer) = error.downcast_ref::<LayerContext>() {
+                // `LayerContext`s display their locations themselves.
+                stack.extend(
+                    layers(layer).into_iter().map(|layer| Layer { location: None, ..layer }),
+                );
+                context = Some(layer);
+                break;
+            }
+            stack.push(Layer { error, type_name: None, location: None });
+        }
+        // Without locations, so any style.
+        super::Tree.render(&stack, &LocationStyle::Omit, f)?;
+
+        #[cfg(feature = "tracing-error")]
+        {
+            use tracing_error::SpanTraceStatus;
+
+            let span_trace = context
+                .and_then(|context| context.span_trace.as_ref())
+                .or(self.span_trace.as_ref())
+                .filter(|span_trace| span_trace.status() == SpanTraceStatus::CAPTURED);
+            if let Some(span_trace) = span_trace {
+                write!(f, "\n\nSpan trace:\n{span_trace}")?;
+            }
+        }
+        #[cfg(not(feature = "tracing-error"))]
+        let _ = context;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::Location;
+
+    use super::*;
+
+    #[derive(Debug, thiserror::Error, StackError)]
+    enum Inner {
+        #[error("IO error")]
+        Io(
+            #[source]
+            #[stack(from)]
+            std::io::Error,
+            #[stack(location)] Location<'static>,
+        ),
+    }
+
+    #[derive(Debug, thiserror::Error, StackError)]
+    enum Outer {
+        #[error("Inner error")]
+        Inner(
+            #[source]
+            #[stack(next, from)]
+            Inner,
+            #[stack(location)] Location<'static>,
+        ),
+    }
+
+    fn outer() -> Result<(), Outer> {
+        Err(Inner::from(std::io::Error::other("not found")))?
+    }
+
+    fn context_with(style: LocationStyle) -> LayerContext {
+        LayerContext::chain(&outer().unwrap_err()).with_location_style(style)
+    }
+
+    #[test]
+    fn anyhow_keeps_locations() {
+        let err = outer().into_anyhow().unwrap_err();
+        let context = err.downcast_ref::<LayerContext>().unwrap();
+        assert_eq!(context.type_name, Some(std::any::type_name::<Outer>()));
+        assert_eq!(context.location.unwrap().file(), file!());
+        // And it's still a `StackError`.
+        assert_eq!(layers(context).len(), 3);
+
+        // Displayed in the given style, not the one from the env.
+        let err = anyhow::Error::new(context_with(LocationStyle::Full));
+        let chain: Vec<_> = err.chain().map(|err| err.to_string()).collect();
+        assert_eq!(chain.len(), 3);
+        assert!(chain[0].starts_with(&format!("Inner error, at {}:", file!())), "{chain:?}");
+        assert!(chain[1].starts_with(&format!("IO error, at {}:", file!())), "{chain:?}");
+        assert_eq!(chain[2], "not found");
+
+        let hidden = anyhow::Error::new(context_with(LocationStyle::Omit));
+        let chain: Vec<_> = hidden.chain().map(|err| err.to_string()).collect();
+        assert_eq!(chain, ["Inner error", "IO error", "not found"]);
+    }
+
+    #[cfg(feature = "eyre")]
+    #[test]
+    fn eyre_handler_renders_stack_and_span_trace() {
+        use eyre::WrapErr;
+
+        StackHandler::install().unwrap();
+
+        let report = || {
+            let context = context_with(LocationStyle::Full);
+            Err::<(), _>(eyre::Report::new(context)).wrap_err("Failed to read").unwrap_err()
+        };
+        #[cfg(feature = "tracing-error")]
+        let report = || {
+            use tracing_subscriber::layer::SubscriberExt;
+
+            let subscriber =
+                tracing_subscriber::Registry::default().with(tracing_error::ErrorLayer::default());
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info_span!("reading", file = "a.txt").in_scope(report)
+            })
+        };
+        let debug = format!("{:?}", report());
+
+        let lines: Vec<_> = debug.lines().collect();
+        assert_eq!(lines[0], "Failed to read");
+        assert!(lines[1].starts_with(&format!("└─ Inner error, at {}:", file!())), "{debug}");
+        assert_eq!(lines[1].matches(", at ").count(), 1, "{debug}");
+        assert!(lines[2].starts_with(&format!("   └─ IO error, at {}:", file!())), "{debug}");
+        assert_eq!(lines[3], "      └─ not found");
+        #[cfg(feature = "tracing-error")]
+        {
+            assert_eq!(lines[5], "Span trace:");
+            assert!(debug.contains("reading"), "{debug}");
+            assert!(debug.contains("a.txt"), "{debug}");
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! How locations of errors are shown by the renderers, so that release logs don't leak paths
+//! of the build machine.
+//!
+//! `file!()` is relative to the workspace root for workspace crates, but it is absolute for
+//! dependencies, like `/home/me/.cargo/registry/src/index.crates.io-.../serde-1.0.228/src/de.rs`.
+//!
+//! Renderers take the style from [`Report::with_location_style`](super::Report::with_location_style),
+//! otherwise it is [`current_style`], the first one of:
+//! - set by [`set_location_style`],
+//! - `STACK_ERROR_LOCATIONS` env var: `full`, `relative`, `hash` or `omit`, other values are ignored,
+//! - [`LocationStyle::Relative`] with the `relative-locations` feature, [`LocationStyle::Full`] otherwise.
+
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+use std::panic::Location;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{OnceLock, RwLock};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocationStyle {
+    /// As given by `file!()`.
+    Full,
+    /// Relative to the workspace root, like `file!()` of workspace crates, also if the path is absolute.
+    /// Other absolute paths are cut to the directory of the crate, which is the parent of the last `src`,
+    /// like `serde-1.0.228/src/de.rs`, so they are the same in any checkout directory.
+    Relative,
+    /// Replaces the first matching prefix with its replacement, like `my/practices/` with `my_practices/`.
+    /// Prefixes are matched against the file as given, and then against the [`Self::Relative`] one,
+    /// which is also used if nothing matches.
+    Rewrite(Vec<(String, String)>),
+    /// Hash of the [`Self::Relative`] location with `/` separators, like `#5f0e4b1c`, to group the same errors in logs.
+    Hash,
+    Omit,
+}
+
+impl LocationStyle {
+    /// Shows a `#[track_caller]` location, see [`Self::show`].
+    pub fn show_location(&self, location: Location<'static>) -> Option<ShownLocation<'static>> {
+        self.show(location.file(), location.line(), location.column())
+    }
+
+    /// `None` for [`Self::Omit`].
+    pub fn show<'a>(&self, file: &'a str, line: u32, column: u32) -> Option<ShownLocation<'a>> {
+        let file = match self {
+            Self::Full => Cow::Borrowed(file),
+            Self::Relative => Cow::Borrowed(relative(file)),
+            Self::Rewrite(prefixes) => [file, relative(file)]
+                .into_iter()
+                .find_map(|file| {
+                    prefixes.iter().find_map(|(prefix, replacement)| {
+                        let rest = file.strip_prefix(prefix.as_str())?;
+                        Some(Cow::Owned(format!("{replacement}{rest}")))
+                    })
+                })
+                .unwrap_or(Cow::Borrowed(relative(file))),
+            Self::Hash => {
+                let file = relative(file).replace('\\', "/");
+                let hash = fnv1a(format!("{file}:{line}:{column}").as_bytes());
+                let hash = (hash >> 32) as u32 ^ hash as u32;
+                return Some(ShownLocation {
+                    file: Cow::Owned(format!("#{hash:08x}")),
+                    position: None,
+                });
+            }
+            Self::Omit => return None,
+        };
+        Some(ShownLocation { file, position: Some((line, column)) })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown location style: {0:?}, expected `full`, `relative`, `hash` or `omit`")]
+pub struct UnknownLocationStyle(String);
+
+impl FromStr for LocationStyle {
+    type Err = UnknownLocationStyle;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "relative" => Ok(Self::Relative),
+            "hash" => Ok(Self::Hash),
+            "omit" => Ok(Self::Omit),
+            _ => Err(UnknownLocationStyle(s.into())),
+        }
+    }
+}
+
+/// Location as the renderers show it: `file:line:column`, or only the hash as `file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShownLocation<'a> {
+    pub file: Cow<'a, str>,
+    pub position: Option<(u32, u32)>,
+}
+
+impl Display for ShownLocation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.file)?;
+        if let Some((line, column)) = self.position {
+            write!(f, ":{line}:{column}")?;
+        }
+        Ok(())
+    }
+}
+
+static STYLE: RwLock<Option<LocationStyle>> = RwLock::new(None);
+
+pub fn set_location_style(style: LocationStyle) {
+    *STYLE.write().unwrap_or_else(|err| err.into_inner()) = Some(style);
+}
+
+/// Style set by [`set_location_style`], or the default one from the env and features.
+pub fn current_style() -> LocationStyle {
+    static DEFAULT: OnceLock<LocationStyle> = OnceLock::new();
+
+    let style = STYLE.read().unwrap_or_else(|err| err.into_inner());
+    let style = style.as_ref().unwrap_or_else(|| {
+        DEFAULT.get_or_init(|| {
+            std::env::var("STACK_ERROR_LOCATIONS")
+                .ok()
+                .and_then(|style| style.parse().ok())
+                .unwrap_or(if cfg!(feature = "relative-locations") {
+                    LocationStyle::Relative
+                } else {
+                    LocationStyle::Full
+                })
+        })
+    });
+    style.clone()
+}
+
+fn relative(file: &str) -> &str {
+    let absolute = file.starts_with(['/', '\\']) || file.get(1..2) == Some(":");
+    if !absolute {
+        return file;
+    }
+    if let Some(rest) = workspace_root().and_then(|root| file.strip_prefix(root)) {
+        return rest.trim_start_matches(['/', '\\']);
+    }
+    let separators: Vec<usize> = file.match_indices(['/', '\\']).map(|(i, _)| i).collect();
+    let component = |i: usize| {
+        let end = separators.get(i + 1).copied().unwrap_or(file.len());
+        &file[separators[i] + 1..end]
+    };
+    let start = match (0..separators.len()).rev().find(|&i| component(i) == "src") {
+        Some(0) => separators[0] + 1,
+        Some(i) => separators[i - 1] + 1,
+        None => separators.last().map_or(0, |i| i + 1),
+    };
+    &file[start..]
+}
+
+/// Root of the workspace of this crate, `None` if it's built as a dependency with absolute `file!()`s.
+fn workspace_root() -> Option<&'static str> {
+    // `my/practices` of `my/practices/src/stack_error/location.rs`.
+    let crate_dir = Path::new(file!()).ancestors().nth(3)?;
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    if crate_dir.is_absolute() || !manifest_dir.ends_with(crate_dir) {
+        return None;
+    }
+    manifest_dir.ancestors().nth(crate_dir.components().count())?.to_str()
+}
+
+/// Stable across Rust versions, unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECKOUTS: [&str; 3] = [
+        "/home/alice/crate/rust/my/practices/src/stack_error.rs",
+        "/tmp/ci/build-42/my/practices/src/stack_error.rs",
+        r"C:\Users\bob\crate\rust\my\practices\src\stack_error.rs",
+    ];
+
+    fn show(style: &LocationStyle, file: &str) -> String {
+        style.show(file, 12, 5).map(|location| location.to_string()).unwrap_or_default()
+    }
+
+    #[test]
+    fn same_in_any_checkout() {
+        for style in [LocationStyle::Relative, LocationStyle::Hash] {
+            let shown: Vec<_> = CHECKOUTS.iter().map(|file| show(&style, file)).collect();
+            assert!(shown.iter().all(|s| s.replace('\\', "/") == shown[0]), "{shown:?}");
+        }
+        assert_eq!(
+            show(&LocationStyle::Relative, CHECKOUTS[0]),
+            "practices/src/stack_error.rs:12:5"
+        );
+        assert_eq!(show(&LocationStyle::Hash, CHECKOUTS[0]).len(), "#12345678".len());
+        // Another position is another error.
+        assert_ne!(
+            LocationStyle::Hash.show(CHECKOUTS[0], 12, 6).unwrap(),
+            LocationStyle::Hash.show(CHECKOUTS[0], 12, 5).unwrap()
+        );
+    }
+
+    #[test]
+    fn shows_dependencies_and_workspace_files() {
+        let registry = "/home/alice/.cargo/registry/src/index.crates.io-1949cf8c6b5b557f/serde-1.0.228/src/de/mod.rs";
+        assert_eq!(show(&LocationStyle::Relative, registry), "serde-1.0.228/src/de/mod.rs:12:5");
+        assert_eq!(show(&LocationStyle::Relative, "/build/main.rs"), "main.rs:12:5");
+        assert_eq!(show(&LocationStyle::Full, registry), format!("{registry}:12:5"));
+        assert_eq!(show(&LocationStyle::Omit, registry), "");
+
+        let location = Location::caller();
+        let relative = show(&LocationStyle::Relative, location.file());
+        assert!(relative.starts_with(file!()), "{relative}");
+        assert!(!relative.contains(env!("CARGO_MANIFEST_DIR")), "{relative}");
+    }
+
+    #[test]
+    fn relative_to_workspace() {
+        let absolute = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/stack_error/location.rs");
+        let absolute = show(&LocationStyle::Relative, absolute.to_str().unwrap());
+        assert_eq!(absolute.replace('\\', "/"), show(&LocationStyle::Relative, file!()));
+        assert_eq!(absolute.replace('\\', "/"), "my/practices/src/stack_error/location.rs:12:5");
+    }
+
+    #[test]
+    fn rewrites_prefixes() {
+        let style = LocationStyle::Rewrite(vec![
+            ("/tmp/ci/".into(), "ci/".into()),
+            ("my/practices/".into(), "my_practices/".into()),
+            ("practices/".into(), "my_practices/".into()),
+        ]);
+        assert_eq!(show(&style, CHECKOUTS[1]), "ci/build-42/my/practices/src/stack_error.rs:12:5");
+        assert_eq!(show(&style, CHECKOUTS[0]), "my_practices/src/stack_error.rs:12:5");
+        assert_eq!(show(&style, file!()), "my_practices/src/stack_error/location.rs:12:5");
+        assert_eq!(show(&style, "/build/main.rs"), "main.rs:12:5");
+    }
+
+    #[test]
+    fn parses_styles() {
+        assert_eq!("Relative".parse::<LocationStyle>().unwrap(), LocationStyle::Relative);
+        assert_eq!(" omit ".parse::<LocationStyle>().unwrap(), LocationStyle::Omit);
+        assert!("short".parse::<LocationStyle>().is_err());
+    }
+}
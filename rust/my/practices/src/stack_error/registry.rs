@@ -0,0 +1,91 @@
+//! Lookup of [`StackError`]s behind plain `dyn Error` sources, so that [`super::layers`] continues
+//! the stack after a third-party error layer.
+//!
+//! `Error::provide` would do it without registration, but it is unstable,
+//! so every type has to be [`register`]ed once instead.
+
+use std::any::TypeId;
+use std::error::Error;
+use std::sync::RwLock;
+
+use super::{LayerContext, StackError};
+
+type Downcast = for<'a> fn(&'a (dyn Error + 'static)) -> Option<&'a dyn StackError>;
+
+static REGISTRY: RwLock<Vec<(TypeId, Downcast)>> = RwLock::new(Vec::new());
+
+/// Allows to find `E` among sources of other errors, registering the same type again does nothing.
+pub fn register<E: StackError + 'static>() {
+    let mut registry = REGISTRY.write().unwrap_or_else(|err| err.into_inner());
+    if registry.iter().all(|(type_id, _)| *type_id != TypeId::of::<E>()) {
+        registry
+            .push((TypeId::of::<E>(), |error| error.downcast_ref::<E>().map(|error| error as _)));
+    }
+}
+
+/// `error` as a [`StackError`] if its type is registered, [`LayerContext`]s are always found.
+pub fn downcast<'a>(error: &'a (dyn Error + 'static)) -> Option<&'a dyn StackError> {
+    if let Some(context) = error.downcast_ref::<LayerContext>() {
+        return Some(context);
+    }
+    let registry = REGISTRY.read().unwrap_or_else(|err| err.into_inner());
+    registry.iter().find_map(|(_, downcast)| downcast(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::Location;
+
+    use super::*;
+    use crate::stack_error::layers;
+
+    #[derive(Debug, thiserror::Error, StackError)]
+    #[error("Inner error")]
+    struct Inner(#[source] std::io::Error, #[stack(location)] Location<'static>);
+
+    /// Third-party error which knows nothing about `StackError`.
+    #[derive(Debug, thiserror::Error)]
+    #[error("Library error")]
+    struct Library(#[source] Box<dyn Error + Send + Sync>);
+
+    #[derive(Debug, thiserror::Error, StackError)]
+    #[error("Outer error")]
+    struct Outer(
+        #[source]
+        #[stack(from)]
+        Library,
+        #[stack(location)] Location<'static>,
+    );
+
+    #[test]
+    fn continues_stack_after_plain_error() {
+        let inner = Inner(std::io::Error::other("io"), *Location::caller());
+        let err = Outer::from(Library(Box::new(inner)));
+        let located = |err: &Outer| {
+            layers(err).iter().map(|layer| layer.location.is_some()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(located(&err), [true, false, false, false]);
+
+        register::<Inner>();
+        register::<Inner>();
+        let registry = REGISTRY.read().unwrap();
+        assert_eq!(
+            registry.iter().filter(|(type_id, _)| *type_id == TypeId::of::<Inner>()).count(),
+            1
+        );
+        drop(registry);
+
+        assert_eq!(located(&err), [true, false, true, false]);
+        let type_names: Vec<_> = layers(&err).iter().map(|layer| layer.type_name).collect();
+        assert_eq!(
+            type_names,
+            [
+                Some(std::any::type_name::<Outer>()),
+                None,
+                Some(std::any::type_name::<Inner>()),
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Ways to display the stack of errors.
+
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+
+use super::{Layer, LocationStyle, StackError, layers, location};
+
+pub trait Render {
+    /// Shows locations of `layers` in the `locations` style.
+    fn render(
+        &self,
+        layers: &[Layer<'_>],
+        locations: &LocationStyle,
+        f: &mut dyn fmt::Write,
+    ) -> fmt::Result;
+}
+
+/// Displays `error` with the renderer.
+pub struct Report<'a, R> {
+    error: &'a dyn StackError,
+    renderer: R,
+    locations: Option<LocationStyle>,
+}
+
+impl<'a, R: Render> Report<'a, R> {
+    pub fn new(error: &'a dyn StackError, renderer: R) -> Self {
+        Self { error, renderer, locations: None }
+    }
+
+    /// Instead of [`location::current_style`].
+    pub fn with_location_style(mut self, style: LocationStyle) -> Self {
+        self.locations = Some(style);
+        self
+    }
+}
+
+impl<R: Render> Display for Report<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let locations = match &self.locations {
+            Some(style) => Cow::Borrowed(style),
+            None => Cow::Owned(location::current_style()),
+        };
+        self.renderer.render(&layers(self.error), &locations, f)
+    }
+}
+
+/// One line: `Internal error (at src/my.rs:31:16): IO error (at src/internal.rs:22:8): Not found`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Compact;
+
+impl Render for Compact {
+    fn render(
+        &self,
+        layers: &[Layer<'_>],
+        locations: &LocationStyle,
+        f: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        for (i, layer) in layers.iter().enumerate() {
+            if i > 0 {
+                f.write_str(": ")?;
+            }
+            write!(f, "{}", layer.error)?;
+            if let Some(location) =
+                layer.location.and_then(|location| locations.show_location(location))
+            {
+                write!(f, " (at {location})")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every error on its own line, nested under the previous one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tree;
+
+impl Render for Tree {
+    fn render(
+        &self,
+        layers: &[Layer<'_>],
+        locations: &LocationStyle,
+        f: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        render_tree(layers, locations, f, &Style::default())
+    }
+}
+
+/// [`Tree`] with ANSI colours for terminals.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Colored;
+
+impl Render for Colored {
+    fn render(
+        &self,
+        layers: &[Layer<'_>],
+        locations: &LocationStyle,
+        f: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        let style = Style {
+            first: "\x1b[1;31m",
+            message: "\x1b[1m",
+            location: "\x1b[2m",
+            branch: "\x1b[36m",
+            reset: "\x1b[0m",
+        };
+        render_tree(layers, locations, f, &style)
+    }
+}
+
+/// Escape sequences of [`Colored`], empty for [`Tree`].
+#[derive(Default)]
+struct Style {
+    first: &'static str,
+    message: &'static str,
+    location: &'static str,
+    branch: &'static str,
+    reset: &'static str,
+}
+
+fn render_tree(
+    layers: &[Layer<'_>],
+    locations: &LocationStyle,
+    f: &mut dyn fmt::Write,
+    style: &Style,
+) -> fmt::Result {
+    let Style { first, message, location: location_style, branch, reset } = style;
+    for (i, layer) in layers.iter().enumerate() {
+        if i == 0 {
+            write!(f, "{first}{}{reset}", layer.error)?;
+        } else {
+            let indent = "   ".repeat(i - 1);
+            write!(f, "\n{indent}{branch}└─{reset} {message}{}{reset}", layer.error)?;
+        }
+        if let Some(location) =
+            layer.location.and_then(|location| locations.show_location(location))
+        {
+            write!(f, "{location_style}, at {location}{reset}")?;
+        }
+    }
+    Ok(())
+}
+
+/// JSON array for log shippers, with `message`, `type_name`, `file`, `line` and `column` of every error.
+/// The last three are `null` if the location is unknown or omitted, the hash is in `file` without the others.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+#[derive(serde::Serialize)]
+struct JsonLayer<'a> {
+    message: String,
+    type_name: Option<&'a str>,
+    file: Option<Cow<'a, str>>,
+    line: Option<u32>,
+    column: Option<u32>,
+}
+
+impl Render for Json {
+    fn render(
+        &self,
+        layers: &[Layer<'_>],
+        locations: &LocationStyle,
+        f: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        let layers: Vec<_> = layers
+            .iter()
+            .map(|layer| {
+                let location =
+                    layer.location.and_then(|location| locations.show_location(location));
+                let position = location.as_ref().and_then(|location| location.position);
+                JsonLayer {
+                    message: layer.error.to_string(),
+                    type_name: layer.type_name,
+                    file: location.map(|location| location.file),
+                    line: position.map(|(line, _)| line),
+                    column: position.map(|(_, column)| column),
+                }
+            })
+            .collect();
+        let json = serde_json::to_string(&layers).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::Location;
+
+    use expect_test::expect;
+
+    use super::*;
+
+    #[derive(Debug, thiserror::Error, StackError)]
+    enum Inner {
+        #[error("IO error")]
+        Io(
+            #[source]
+            #[stack(from)]
+            std::io::Error,
+            #[stack(location)] Location<'static>,
+        ),
+    }
+
+    #[derive(Debug, thiserror::Error, StackError)]
+    enum Outer {
+        #[error("Inner error")]
+        Inner(
+            #[source]
+            #[stack(next, from)]
+            Inner,
+            #[stack(location)] Location<'static>,
+        ),
+    }
+
+    fn outer() -> Outer {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "not found");
+        Outer::from(Inner::from(io))
+    }
+
+    /// Locations are omitted, so the snapshots don't change with lines of this file.
+    fn render(renderer: impl Render) -> String {
+        outer().report(renderer).with_location_style(LocationStyle::Omit).to_string()
+    }
+
+    #[test]
+    fn renders_compact() {
+        expect!["Inner error: IO error: not found"].assert_eq(&render(Compact));
+    }
+
+    #[test]
+    fn renders_tree() {
+        expect![[r#"
+            Inner error
+            └─ IO error
+               └─ not found"#]]
+        .assert_eq(&render(Tree));
+
+        expect![[r#"
+            \e[1;31mInner error\e[0m
+            \e[36m└─\e[0m \e[1mIO error\e[0m
+               \e[36m└─\e[0m \e[1mnot found\e[0m"#]]
+        .assert_eq(&render(Colored).replace('\x1b', "\\e"));
+    }
+
+    #[test]
+    fn renders_json() {
+        let json: serde_json::Value = serde_json::from_str(&render(Json)).unwrap();
+
+        expect![[r#"
+            [
+              {
+                "column": null,
+                "file": null,
+                "line": null,
+                "message": "Inner error",
+                "type_name": "my_practices::stack_error::render::tests::Outer"
+              },
+              {
+                "column": null,
+                "file": null,
+                "line": null,
+                "message": "IO error",
+                "type_name": "my_practices::stack_error::render::tests::Inner"
+              },
+              {
+                "column": null,
+                "file": null,
+                "line": null,
+                "message": "not found",
+                "type_name": null
+              }
+            ]"#]]
+        .assert_eq(&serde_json::to_string_pretty(&json).unwrap());
+    }
+
+    #[test]
+    fn renders_locations() {
+        let err = outer();
+        let at: Vec<_> = layers(&err).iter().filter_map(|layer| layer.location).collect();
+        fn report(err: &Outer, renderer: impl Render) -> String {
+            err.report(renderer).with_location_style(LocationStyle::Full).to_string()
+        }
+
+        assert_eq!(
+            report(&err, Compact),
+            format!("Inner error (at {}): IO error (at {}): not found", at[0], at[1])
+        );
+        assert_eq!(
+            report(&err, Tree),
+            format!("Inner error, at {}\n└─ IO error, at {}\n   └─ not found", at[0], at[1])
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&report(&err, Json)).unwrap();
+        assert_eq!(json[0]["file"], file!());
+        assert_eq!(json[0]["line"], at[0].line());
+        assert_eq!(json[0]["column"], at[0].column());
+        assert_eq!(json[1]["column"], at[1].column());
+    }
+}
@@ -0,0 +1,394 @@
+//! Assertions on error chains for tests, see [`crate::assert_chain`].
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::panic::Location;
+
+use crate::stack_error::{self, Layer, StackError};
+
+/// Checks `source()` chain of an error, one matcher per error of the chain:
+///
+/// ```
+/// use my_practices::assert_chain;
+///
+/// #[derive(Debug, thiserror::Error)]
+/// #[error("Failed to read config")]
+/// struct ConfigError(#[source] std::io::Error);
+///
+/// let err = ConfigError(std::io::Error::other("IO error"));
+/// assert_chain!(err, [contains("config"), is::<std::io::Error>()]);
+/// ```
+///
+/// Or compares [`Chain`] with a snapshot: `assert_chain!(err, expect![[...]])`.
+///
+/// `StackError`s, `&dyn Error` and `&(dyn Error + Send + Sync)` are accepted too,
+/// the latter is what `anyhow::Error` derefs to.
+#[macro_export]
+macro_rules! assert_chain {
+    ($err:expr, [$($matcher:expr),* $(,)?]) => {
+        // `match` keeps temporaries alive, without moving places.
+        match $err {
+            ref err => {
+                #[allow(unused_imports)]
+                use $crate::test_support::matchers::*;
+                let chain = $crate::test_support::chain!(*err);
+                let matchers: &[&dyn $crate::test_support::Matcher] = &[$(&$matcher),*];
+                $crate::test_support::assert_matches(&chain, matchers);
+            }
+        }
+    };
+    ($err:expr, $expect:expr) => {
+        match $err {
+            ref err => $expect.assert_eq(&$crate::test_support::chain!(*err).to_string()),
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __chain {
+    ($err:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::test_support::__private::{
+            ChainOfDyn as _, ChainOfError as _, ChainOfStack as _,
+        };
+        (&&&$crate::stack_error::__private::Wrap(&$err)).chain()
+    }};
+}
+
+#[doc(hidden)]
+pub use crate::__chain as chain;
+
+/// Error of the chain.
+pub struct ChainLink<'a> {
+    pub error: &'a (dyn Error + 'static),
+    pub display: String,
+    /// Name in the `Debug` output, like `Os` of `Os { code: 2, .. }`.
+    pub debug_name: Option<String>,
+    /// Known for [`StackError`]s, if the error is the one or it is [`stack_error::registry::register`]ed.
+    pub type_name: Option<&'static str>,
+    pub location: Option<Location<'static>>,
+}
+
+/// `source()` chain of an error, displayed one error per line for snapshots:
+/// `0: IO error [my::Error] at src/my.rs:12:5`.
+pub struct Chain<'a> {
+    pub links: Vec<ChainLink<'a>>,
+}
+
+impl<'a> Chain<'a> {
+    pub fn of(error: &'a (dyn Error + 'static)) -> Self {
+        Self::collect(error, VecDeque::new())
+    }
+
+    /// Like [`Chain::of`], with locations of all the stack of `error`.
+    pub fn of_stack(error: &'a (dyn StackError + 'static)) -> Self {
+        Self::collect(error, stack_error::layers(error).into())
+    }
+
+    /// Takes type names and locations from `stack`, which goes along the `source()` chain.
+    /// Addresses are compared too, but they are not enough, as the first field has the same one.
+    fn collect(error: &'a (dyn Error + 'static), mut stack: VecDeque<Layer<'a>>) -> Self {
+        let mut links = vec![];
+        let mut next = Some(error);
+        while let Some(error) = next {
+            if stack.front().is_none_or(|layer| address(layer.error) != address(error)) {
+                stack = stack_error::registry::downcast(error)
+                    .map(|error| stack_error::layers(error).into())
+                    .unwrap_or_default();
+            }
+            let layer = stack.pop_front();
+
+            let debug = format!("{error:?}");
+            let debug_name: String =
+                debug.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+            links.push(ChainLink {
+                error,
+                display: error.to_string(),
+                debug_name: (!debug_name.is_empty()).then_some(debug_name),
+                type_name: layer.and_then(|layer| layer.type_name),
+                location: layer.and_then(|layer| layer.location),
+            });
+            next = error.source();
+        }
+        Self { links }
+    }
+}
+
+fn address(error: &dyn Error) -> *const () {
+    (error as *const dyn Error).cast()
+}
+
+impl Display for Chain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, link) in self.links.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{i}: {}", link.display)?;
+            if let Some(name) = link.type_name.or(link.debug_name.as_deref()) {
+                write!(f, " [{name}]")?;
+            }
+            if let Some(location) = link.location {
+                write!(f, " at {location}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub trait Matcher {
+    fn matches(&self, link: &ChainLink<'_>) -> bool;
+
+    /// How the matcher was created, for failure messages.
+    fn describe(&self) -> String;
+}
+
+#[track_caller]
+pub fn assert_matches(chain: &Chain<'_>, matchers: &[&dyn Matcher]) {
+    let mut failed = chain.links.len() != matchers.len();
+    let mut report = String::new();
+    for i in 0..chain.links.len().max(matchers.len()) {
+        let matcher = matchers.get(i).map(|matcher| matcher.describe());
+        let matched = match (chain.links.get(i), matchers.get(i)) {
+            (Some(link), Some(matcher)) => matcher.matches(link),
+            _ => false,
+        };
+        failed |= !matched;
+        let mark = if matched { "ok" } else { "FAILED" };
+        report.push_str(&format!("\n{i}: {} -> {mark}", matcher.as_deref().unwrap_or("nothing")));
+    }
+    if failed {
+        panic!("Error chain doesn't match.\nChain:\n{chain}\nMatchers:{report}");
+    }
+}
+
+pub mod matchers {
+    use std::marker::PhantomData;
+
+    use super::{ChainLink, Matcher};
+
+    pub struct Contains(String);
+
+    /// `Display` of the error contains `text`.
+    pub fn contains(text: impl Into<String>) -> Contains {
+        Contains(text.into())
+    }
+
+    impl Matcher for Contains {
+        fn matches(&self, link: &ChainLink<'_>) -> bool {
+            link.display.contains(&self.0)
+        }
+
+        fn describe(&self) -> String {
+            format!("contains({:?})", self.0)
+        }
+    }
+
+    pub struct Displays(String);
+
+    /// `Display` of the error is `text`.
+    pub fn displays(text: impl Into<String>) -> Displays {
+        Displays(text.into())
+    }
+
+    impl Matcher for Displays {
+        fn matches(&self, link: &ChainLink<'_>) -> bool {
+            link.display == self.0
+        }
+
+        fn describe(&self) -> String {
+            format!("displays({:?})", self.0)
+        }
+    }
+
+    pub struct Is<T>(PhantomData<fn() -> T>);
+
+    /// The error is `T`.
+    pub fn is<T: std::error::Error + 'static>() -> Is<T> {
+        Is(PhantomData)
+    }
+
+    impl<T: std::error::Error + 'static> Matcher for Is<T> {
+        fn matches(&self, link: &ChainLink<'_>) -> bool {
+            link.error.is::<T>()
+        }
+
+        fn describe(&self) -> String {
+            format!("is::<{}>()", std::any::type_name::<T>())
+        }
+    }
+
+    pub struct Located;
+
+    /// The error is a `StackError` with a location.
+    pub fn located() -> Located {
+        Located
+    }
+
+    impl Matcher for Located {
+        fn matches(&self, link: &ChainLink<'_>) -> bool {
+            link.location.is_some()
+        }
+
+        fn describe(&self) -> String {
+            "located()".into()
+        }
+    }
+
+    pub struct Any;
+
+    /// Any error.
+    pub fn any() -> Any {
+        Any
+    }
+
+    impl Matcher for Any {
+        fn matches(&self, _: &ChainLink<'_>) -> bool {
+            true
+        }
+
+        fn describe(&self) -> String {
+            "any()".into()
+        }
+    }
+}
+
+/// Chooses [`Chain::of_stack`] for `StackError`s and [`Chain::of`] for other errors in [`chain!`].
+#[doc(hidden)]
+pub mod __private {
+    use std::error::Error;
+
+    use super::Chain;
+    use crate::stack_error::__private::Wrap;
+    use crate::stack_error::StackError;
+
+    pub trait ChainOfStack<'a> {
+        fn chain(&self) -> Chain<'a>;
+    }
+
+    impl<'a, T: StackError + 'static> ChainOfStack<'a> for &&Wrap<'a, T> {
+        fn chain(&self) -> Chain<'a> {
+            Chain::of_stack(self.0)
+        }
+    }
+
+    pub trait ChainOfDyn<'a> {
+        fn chain(&self) -> Chain<'a>;
+    }
+
+    impl<'a> ChainOfDyn<'a> for &Wrap<'_, &'a (dyn Error + 'static)> {
+        fn chain(&self) -> Chain<'a> {
+            Chain::of(*self.0)
+        }
+    }
+
+    impl<'a> ChainOfDyn<'a> for &Wrap<'_, &'a (dyn Error + Send + Sync + 'static)> {
+        fn chain(&self) -> Chain<'a> {
+            Chain::of(*self.0)
+        }
+    }
+
+    pub trait ChainOfError<'a> {
+        fn chain(&self) -> Chain<'a>;
+    }
+
+    impl<'a, T: Error + 'static> ChainOfError<'a> for Wrap<'a, T> {
+        fn chain(&self) -> Chain<'a> {
+            Chain::of(self.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+
+    #[derive(Debug, thiserror::Error, StackError)]
+    enum Inner {
+        #[error("IO error")]
+        Io(
+            #[source]
+            #[stack(from)]
+            std::io::Error,
+            #[stack(location)] Location<'static>,
+        ),
+    }
+
+    #[derive(Debug, thiserror::Error, StackError)]
+    enum Outer {
+        #[error("Failed to read config")]
+        Read(
+            #[source]
+            #[stack(next, from)]
+            Inner,
+            #[stack(location)] Location<'static>,
+        ),
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Plain error")]
+    struct Plain(#[source] Outer);
+
+    fn outer() -> Outer {
+        Inner::from(std::io::Error::from(std::io::ErrorKind::NotFound)).into()
+    }
+
+    #[test]
+    fn matches_stack_and_plain_errors() {
+        let err = Plain(outer());
+
+        assert_chain!(
+            err,
+            [displays("Plain error"), contains("config"), contains("IO"), is::<std::io::Error>()]
+        );
+        // Locations are lost after the plain error, as `Outer` isn't registered.
+        assert_chain!(err, [any(), is::<Outer>(), any(), any()]);
+        let chain = chain!(err);
+        assert!(chain.links.iter().all(|link| link.location.is_none()));
+
+        assert_chain!(err.0, [is::<Outer>(), located(), any()]);
+        let chain = chain!(err.0);
+        assert_eq!(chain.links[2].debug_name.as_deref(), Some("Kind"));
+
+        let err: Box<dyn Error + Send + Sync> = Box::new(err);
+        assert_chain!(&*err, [is::<Plain>(), any(), any(), any()]);
+    }
+
+    #[test]
+    fn reports_mismatches() {
+        let panic = std::panic::catch_unwind(|| {
+            let err = std::io::Error::other(Plain(outer()));
+            assert_chain!(err, [contains("config"), is::<Plain>()]);
+        })
+        .unwrap_err();
+
+        expect![[r#"
+            Error chain doesn't match.
+            Chain:
+            0: Plain error [Custom]
+            1: Failed to read config [Read]
+            2: IO error [Io]
+            3: entity not found [Kind]
+            Matchers:
+            0: contains("config") -> FAILED
+            1: is::<my_practices::test_support::tests::Plain>() -> FAILED
+            2: nothing -> FAILED
+            3: nothing -> FAILED"#]]
+        .assert_eq(panic.downcast_ref::<String>().unwrap());
+    }
+
+    #[test]
+    fn snapshots() {
+        assert_chain!(
+            outer(),
+            expect![[r#"
+            0: Failed to read config [my_practices::test_support::tests::Outer] at my/practices/src/test_support.rs:337:73
+            1: IO error [my_practices::test_support::tests::Inner] at my/practices/src/test_support.rs:337:9
+            2: entity not found [Kind]"#]]
+        );
+    }
+}
@@ -0,0 +1,388 @@
+//! `#[derive(StackError)]` for error enums and structs of `my_practices`.
+//!
+//! Fields are marked with `#[stack(...)]`:
+//! - `next`: the source error which is a `StackError` too, returned from `next()`;
+//! - `location`: `std::panic::Location<'static>` returned from `location()`;
+//! - `from`: generates `From<FieldType>`, which captures the caller location into
+//!   the `location` field if any. Other fields are not allowed in such variant.
+//!
+//! The generated code refers to `::my_practices::stack_error::StackError`, so `my_practices`
+//! itself has `extern crate self as my_practices`.
+//!
+//! ```ignore
+//! #[derive(Debug, thiserror::Error, StackError)]
+//! pub enum Error {
+//!     #[error("Internal error")]
+//!     Internal(#[source] #[stack(next, from)] internal::Error, #[stack(location)] Location<'static>),
+//!
+//!     #[error("Other")]
+//!     Other,
+//! }
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Fields, Member};
+
+#[proc_macro_derive(StackError, attributes(stack))]
+pub fn derive_stack_error(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Marked field of a variant.
+struct Marked<'a> {
+    member: Member,
+    field: &'a syn::Field,
+    /// Span of the attribute, to point errors at.
+    span: Span,
+}
+
+/// Struct itself or a variant of enum, with `path` to construct and match it.
+struct Variant<'a> {
+    path: TokenStream2,
+    next: Option<Marked<'a>>,
+    location: Option<Marked<'a>>,
+    from: Option<Marked<'a>>,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let mut errors = Errors::default();
+    errors.check(reject_attrs(&input.attrs, "the type"));
+
+    let variants = match &input.data {
+        Data::Struct(data) => vec![(quote!(Self), &data.fields)],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                errors.check(reject_attrs(&variant.attrs, "a variant"));
+                let ident = &variant.ident;
+                (quote!(Self::#ident), &variant.fields)
+            })
+            .collect(),
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "`StackError` can't be derived for unions",
+            ));
+        }
+    };
+    let variants: Vec<_> = variants
+        .into_iter()
+        .filter_map(|(path, fields)| errors.check(parse_variant(path, fields)))
+        .collect();
+    errors.finish()?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let trait_path = quote!(::my_practices::stack_error::StackError);
+    // `match self {}` doesn't compile for a reference to an empty enum.
+    let scrutinee = match variants.is_empty() {
+        true => quote!(*self),
+        false => quote!(self),
+    };
+    let next_arms = variants.iter().map(|variant| {
+        let path = &variant.path;
+        match &variant.next {
+            Some(Marked { member, field, .. }) => quote_spanned! {field.ty.span()=>
+                #path { #member: next, .. } => Some(next as &dyn #trait_path),
+            },
+            None => quote!(#path { .. } => None,),
+        }
+    });
+    let location_arms = variants.iter().map(|variant| {
+        let path = &variant.path;
+        match &variant.location {
+            Some(Marked { member, .. }) => {
+                quote!(#path { #member: location, .. } => Some(*location),)
+            }
+            None => quote!(#path { .. } => None,),
+        }
+    });
+    let from_impls = variants.iter().filter_map(|variant| {
+        let Marked { member, field, .. } = variant.from.as_ref()?;
+        let path = &variant.path;
+        let ty = &field.ty;
+        Some(match &variant.location {
+            Some(location) => {
+                let location = &location.member;
+                quote! {
+                    impl #impl_generics ::core::convert::From<#ty> for #name #ty_generics #where_clause {
+                        #[track_caller]
+                        fn from(source: #ty) -> Self {
+                            #path { #member: source, #location: *::core::panic::Location::caller() }
+                        }
+                    }
+                }
+            }
+            None => quote! {
+                impl #impl_generics ::core::convert::From<#ty> for #name #ty_generics #where_clause {
+                    fn from(source: #ty) -> Self {
+                        #path { #member: source }
+                    }
+                }
+            },
+        })
+    });
+
+    Ok(quote! {
+        impl #impl_generics #trait_path for #name #ty_generics #where_clause {
+            fn next(&self) -> ::core::option::Option<&dyn #trait_path> {
+                match #scrutinee {
+                    #(#next_arms)*
+                }
+            }
+
+            fn location(&self) -> ::core::option::Option<::core::panic::Location<'static>> {
+                match #scrutinee {
+                    #(#location_arms)*
+                }
+            }
+        }
+
+        #(#from_impls)*
+    })
+}
+
+fn parse_variant(path: TokenStream2, fields: &Fields) -> syn::Result<Variant<'_>> {
+    let mut variant = Variant { path, next: None, location: None, from: None };
+    let mut errors = Errors::default();
+
+    for (index, field) in fields.iter().enumerate() {
+        let member = field.ident.clone().map_or_else(|| Member::from(index), Member::Named);
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("stack")) {
+            let result = attr.parse_nested_meta(|meta| {
+                let (slot, name) = if meta.path.is_ident("next") {
+                    (&mut variant.next, "next")
+                } else if meta.path.is_ident("location") {
+                    (&mut variant.location, "location")
+                } else if meta.path.is_ident("from") {
+                    (&mut variant.from, "from")
+                } else {
+                    return Err(meta.error("expected `next`, `location` or `from`"));
+                };
+                if slot.is_some() {
+                    return Err(meta.error(format!("only one field can be `#[stack({name})]`")));
+                }
+                let span = meta.path.span();
+                *slot = Some(Marked { member: member.clone(), field, span });
+                Ok(())
+            });
+            errors.check(result);
+        }
+    }
+
+    if let Some(from) = &variant.from {
+        let location = variant.location.as_ref().map(|location| &location.member);
+        let extra = fields.len()
+            - 1
+            - usize::from(location.is_some_and(|location| *location != from.member));
+        if extra > 0 {
+            errors.push(syn::Error::new(
+                from.span,
+                "`#[stack(from)]` requires other fields to be only the `#[stack(location)]` one",
+            ));
+        }
+        if location == Some(&from.member) {
+            errors.push(syn::Error::new(from.span, "`#[stack(from)]` field can't be the location"));
+        }
+    }
+
+    errors.finish()?;
+    Ok(variant)
+}
+
+fn reject_attrs(attrs: &[syn::Attribute], target: &str) -> syn::Result<()> {
+    match attrs.iter().find(|attr| attr.path().is_ident("stack")) {
+        Some(attr) => Err(syn::Error::new_spanned(
+            attr,
+            format!("`#[stack(...)]` is for fields, not {target}"),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Collects all errors to report them at once.
+#[derive(Default)]
+struct Errors(Option<syn::Error>);
+
+impl Errors {
+    fn push(&mut self, error: syn::Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(error),
+            None => self.0 = Some(error),
+        }
+    }
+
+    fn check<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        result.map_err(|error| self.push(error)).ok()
+    }
+
+    fn finish(self) -> syn::Result<()> {
+        self.0.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+
+    fn expand_to_string(input: DeriveInput) -> String {
+        match expand(&input) {
+            Ok(tokens) => prettyplease::unparse(&syn::parse2(tokens).unwrap()),
+            Err(errors) => errors.into_iter().map(|error| format!("{error}\n")).collect(),
+        }
+    }
+
+    #[test]
+    fn expands_enum() {
+        let input = syn::parse_quote! {
+            pub enum Error {
+                External(#[stack(from)] external::Error, #[stack(location)] Location<'static>),
+                Internal(#[stack(next, from)] internal::Error, #[stack(location)] Location<'static>),
+                Parse { #[stack(next)] source: ParseError, line: usize },
+                Other,
+            }
+        };
+
+        expect![[r#"
+            impl ::my_practices::stack_error::StackError for Error {
+                fn next(
+                    &self,
+                ) -> ::core::option::Option<&dyn ::my_practices::stack_error::StackError> {
+                    match self {
+                        Self::External { .. } => None,
+                        Self::Internal { 0: next, .. } => {
+                            Some(next as &dyn ::my_practices::stack_error::StackError)
+                        }
+                        Self::Parse { source: next, .. } => {
+                            Some(next as &dyn ::my_practices::stack_error::StackError)
+                        }
+                        Self::Other { .. } => None,
+                    }
+                }
+                fn location(&self) -> ::core::option::Option<::core::panic::Location<'static>> {
+                    match self {
+                        Self::External { 1: location, .. } => Some(*location),
+                        Self::Internal { 1: location, .. } => Some(*location),
+                        Self::Parse { .. } => None,
+                        Self::Other { .. } => None,
+                    }
+                }
+            }
+            impl ::core::convert::From<external::Error> for Error {
+                #[track_caller]
+                fn from(source: external::Error) -> Self {
+                    Self::External {
+                        0: source,
+                        1: *::core::panic::Location::caller(),
+                    }
+                }
+            }
+            impl ::core::convert::From<internal::Error> for Error {
+                #[track_caller]
+                fn from(source: internal::Error) -> Self {
+                    Self::Internal {
+                        0: source,
+                        1: *::core::panic::Location::caller(),
+                    }
+                }
+            }
+        "#]]
+        .assert_eq(&expand_to_string(input));
+    }
+
+    #[test]
+    fn expands_generic_struct() {
+        let input = syn::parse_quote! {
+            pub struct Context<E: StackError> {
+                #[stack(next, from)]
+                source: E,
+            }
+        };
+
+        expect![[r#"
+            impl<E: StackError> ::my_practices::stack_error::StackError for Context<E> {
+                fn next(
+                    &self,
+                ) -> ::core::option::Option<&dyn ::my_practices::stack_error::StackError> {
+                    match self {
+                        Self { source: next, .. } => {
+                            Some(next as &dyn ::my_practices::stack_error::StackError)
+                        }
+                    }
+                }
+                fn location(&self) -> ::core::option::Option<::core::panic::Location<'static>> {
+                    match self {
+                        Self { .. } => None,
+                    }
+                }
+            }
+            impl<E: StackError> ::core::convert::From<E> for Context<E> {
+                fn from(source: E) -> Self {
+                    Self { source: source }
+                }
+            }
+        "#]]
+        .assert_eq(&expand_to_string(input));
+    }
+
+    #[test]
+    fn expands_empty_enum() {
+        let input = syn::parse_quote! {
+            pub enum Never {}
+        };
+
+        expect![[r#"
+            impl ::my_practices::stack_error::StackError for Never {
+                fn next(
+                    &self,
+                ) -> ::core::option::Option<&dyn ::my_practices::stack_error::StackError> {
+                    match *self {}
+                }
+                fn location(&self) -> ::core::option::Option<::core::panic::Location<'static>> {
+                    match *self {}
+                }
+            }
+        "#]]
+        .assert_eq(&expand_to_string(input));
+    }
+
+    #[test]
+    fn reports_all_errors() {
+        let input = syn::parse_quote! {
+            #[stack(next)]
+            enum Error {
+                A(#[stack(next)] A, #[stack(next)] B),
+                B(#[stack(from)] A, usize),
+                C(#[stack(from, location)] A),
+                #[stack(location)]
+                D(#[stack(source)] A),
+                E(#[stack = "next"] A),
+            }
+        };
+
+        expect![[r#"
+            `#[stack(...)]` is for fields, not the type
+            `#[stack(...)]` is for fields, not a variant
+            only one field can be `#[stack(next)]`
+            `#[stack(from)]` requires other fields to be only the `#[stack(location)]` one
+            `#[stack(from)]` field can't be the location
+            expected `next`, `location` or `from`
+            expected parentheses: #[stack(...)]
+        "#]]
+        .assert_eq(&expand_to_string(input));
+
+        let input = syn::parse_quote!(union Error { a: u32 });
+        expect![[r#"
+            `StackError` can't be derived for unions
+        "#]]
+        .assert_eq(&expand_to_string(input));
+    }
+}
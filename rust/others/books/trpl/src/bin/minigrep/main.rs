@@ -5,14 +5,13 @@ use std::process;
 
 use minigrep::Config;
 
+mod matcher;
 // Should be defined as lib, but for simplicity, use module.
 mod minigrep;
 
 fn main() {
-    let config = Config::build(env::args()).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {err}");
-        process::exit(1);
-    });
+    // Prints usage for errors and `--help`, and exits.
+    let config = Config::parse_args(env::args()).unwrap_or_else(|err| err.exit());
 
     if let Err(e) = minigrep::run(config) {
         eprintln!("Application error: {e}");
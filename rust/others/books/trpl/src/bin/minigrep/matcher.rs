@@ -0,0 +1,182 @@
+use std::error::Error;
+use std::ops::Range;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use regex::{Regex, RegexBuilder};
+
+/// Finds matches in a line, which are highlighted in coloured output.
+pub trait Matcher {
+    /// Byte ranges of non-overlapping matches, from left to right.
+    fn find_iter(&self, line: &str) -> Vec<Range<usize>>;
+
+    /// Whether the line is selected, which may be without any ranges, like for [`Inverted`].
+    fn is_match(&self, line: &str) -> bool {
+        !self.find_iter(line).is_empty()
+    }
+}
+
+/// Many literals are searched at once, instead of a `contains` per literal.
+impl Matcher for AhoCorasick {
+    fn find_iter(&self, line: &str) -> Vec<Range<usize>> {
+        AhoCorasick::find_iter(self, line).map(|found| found.range()).collect()
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        AhoCorasick::is_match(self, line)
+    }
+}
+
+impl Matcher for Regex {
+    fn find_iter(&self, line: &str) -> Vec<Range<usize>> {
+        Regex::find_iter(self, line).map(|found| found.range()).collect()
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        Regex::is_match(self, line)
+    }
+}
+
+/// Only matches which are not parts of longer words, like `-w` of `grep`.
+pub struct WholeWord(pub Box<dyn Matcher>);
+
+impl Matcher for WholeWord {
+    fn find_iter(&self, line: &str) -> Vec<Range<usize>> {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        self.0
+            .find_iter(line)
+            .into_iter()
+            .filter(|range| {
+                !line[..range.start].chars().next_back().is_some_and(is_word)
+                    && !line[range.end..].chars().next().is_some_and(is_word)
+            })
+            .collect()
+    }
+}
+
+/// Selects lines without matches, so there is nothing to highlight.
+pub struct Inverted(pub Box<dyn Matcher>);
+
+impl Matcher for Inverted {
+    fn find_iter(&self, _line: &str) -> Vec<Range<usize>> {
+        vec![]
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        !self.0.is_match(line)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Sensitive,
+    Insensitive,
+    /// Insensitive unless patterns have uppercase letters, like in `ripgrep`.
+    Smart,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    pub regex: bool,
+    pub whole_word: bool,
+    pub invert: bool,
+    pub case: Case,
+}
+
+/// Matches any of `patterns`, which are literals unless `options.regex`.
+pub fn build(patterns: &[String], options: Options) -> Result<Box<dyn Matcher>, Box<dyn Error>> {
+    let ignore_case = match options.case {
+        Case::Sensitive => false,
+        Case::Insensitive => true,
+        Case::Smart => !patterns.iter().any(|pattern| pattern.chars().any(char::is_uppercase)),
+    };
+
+    // Folding case of non-ASCII literals is left to `regex`, as `aho-corasick` folds only ASCII.
+    let ascii = patterns.iter().all(|pattern| pattern.is_ascii());
+    let mut matcher: Box<dyn Matcher> = if !options.regex && (!ignore_case || ascii) {
+        let literals = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .ascii_case_insensitive(ignore_case)
+            .build(patterns)?;
+        Box::new(literals)
+    } else {
+        let alternatives: Vec<_> = patterns
+            .iter()
+            .map(|pattern| match options.regex {
+                true => format!("(?:{pattern})"),
+                false => regex::escape(pattern),
+            })
+            .collect();
+        let regex = match alternatives.is_empty() {
+            // Like `aho-corasick` without patterns, matches nothing.
+            true => Regex::new("[^\\s\\S]")?,
+            false => {
+                RegexBuilder::new(&alternatives.join("|")).case_insensitive(ignore_case).build()?
+            }
+        };
+        Box::new(regex)
+    };
+    if options.whole_word {
+        matcher = Box::new(WholeWord(matcher));
+    }
+    if options.invert {
+        matcher = Box::new(Inverted(matcher));
+    }
+    Ok(matcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LITERAL: Options =
+        Options { regex: false, whole_word: false, invert: false, case: Case::Sensitive };
+
+    fn find<'a>(patterns: &[&str], options: Options, line: &'a str) -> Vec<&'a str> {
+        let patterns: Vec<String> = patterns.iter().map(|pattern| pattern.to_string()).collect();
+        let matcher = build(&patterns, options).unwrap();
+        matcher.find_iter(line).into_iter().map(|range| &line[range]).collect()
+    }
+
+    #[test]
+    fn finds_many_literals_at_once() {
+        let line = "use std::{fs, io}; // fs::read";
+        assert_eq!(find(&["fs", "io", "std::"], LITERAL, line), ["std::", "fs", "io", "fs"]);
+        // The longest one of literals at the same position.
+        assert_eq!(find(&["fs", "fs::read"], LITERAL, line), ["fs", "fs::read"]);
+        assert_eq!(find(&["a.b"], LITERAL, "a.b axb"), ["a.b"]);
+        assert!(find(&[], LITERAL, line).is_empty());
+    }
+
+    #[test]
+    fn folds_case() {
+        let insensitive = Options { case: Case::Insensitive, ..LITERAL };
+        assert_eq!(find(&["rust"], insensitive, "Rust or RUST"), ["Rust", "RUST"]);
+        assert_eq!(find(&["straße"], insensitive, "STRASSE or STRAßE"), ["STRAßE"]);
+
+        let smart = Options { case: Case::Smart, ..LITERAL };
+        assert_eq!(find(&["rust"], smart, "Rust or rust"), ["Rust", "rust"]);
+        assert_eq!(find(&["Rust"], smart, "Rust or rust"), ["Rust"]);
+    }
+
+    #[test]
+    fn matches_regex_and_whole_words() {
+        let regex = Options { regex: true, ..LITERAL };
+        assert_eq!(find(&[r"\d+", "x|y"], regex, "a1 b22 x"), ["1", "22", "x"]);
+
+        let whole_word = Options { whole_word: true, ..LITERAL };
+        assert_eq!(find(&["cat"], whole_word, "concat cat cats cat_ (cat)"), ["cat", "cat"]);
+        assert_eq!(find(&["fn"], Options { whole_word: true, ..regex }, "fn main"), ["fn"]);
+    }
+
+    #[test]
+    fn inverts_without_highlights() {
+        let patterns = ["cat".to_string()];
+        let matcher = build(&patterns, Options { invert: true, ..LITERAL }).unwrap();
+        assert!(!matcher.is_match("concat"));
+        assert!(matcher.is_match("dog"));
+        assert!(matcher.find_iter("dog").is_empty());
+
+        let invalid = build(&["(".to_string()], Options { regex: true, ..LITERAL });
+        assert!(invalid.is_err());
+    }
+}
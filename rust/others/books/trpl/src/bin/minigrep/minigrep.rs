@@ -1,61 +1,325 @@
 use std::error::Error;
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::{env, fs};
 
+use clap::{Parser, ValueEnum};
+
+use crate::matcher::{self, Case, Matcher};
+
+#[derive(Debug, Parser)]
+#[command(name = "minigrep", about = "Searches files and directories for lines matching patterns")]
 pub struct Config {
-    pub query: String,
-    pub file_path: String,
+    /// Literal to search for, or a regex with `-E`; omitted with `-f`
+    #[arg(required_unless_present = "patterns_file")]
+    pub query: Option<String>,
+    /// Files and directories to search recursively, the current directory if none
+    pub paths: Vec<String>,
+    /// Searches for any of the lines of FILE, like multiple queries
+    #[arg(short = 'f', long = "file", value_name = "FILE")]
+    pub patterns_file: Option<PathBuf>,
+    /// Treats patterns as regular expressions
+    #[arg(short = 'E', long)]
+    pub regex: bool,
+    /// Matches only whole words
+    #[arg(short = 'w', long = "word-regexp")]
+    pub whole_word: bool,
+    /// Selects lines which don't match
+    #[arg(short = 'v', long = "invert-match")]
+    pub invert: bool,
+    /// Ignores case, also if `IGNORE_CASE` is set
+    #[arg(short, long, overrides_with = "smart_case")]
     pub ignore_case: bool,
+    /// Ignores case unless patterns have uppercase letters
+    #[arg(short = 'S', long, overrides_with = "ignore_case")]
+    pub smart_case: bool,
+    /// Prefixes lines with their numbers
+    #[arg(short = 'n', long = "line-number")]
+    pub line_numbers: bool,
+    /// Prints only numbers of selected lines per file
+    #[arg(short, long)]
+    pub count: bool,
+    /// Prints only paths of files with selected lines
+    #[arg(short = 'l', long)]
+    pub files_with_matches: bool,
+    /// Prints NUM lines of context before matches
+    #[arg(short = 'B', long = "before-context", value_name = "NUM")]
+    pub before: Option<usize>,
+    /// Prints NUM lines of context after matches
+    #[arg(short = 'A', long = "after-context", value_name = "NUM")]
+    pub after: Option<usize>,
+    /// Prints NUM lines of context around matches, unless `-A` or `-B` is given
+    #[arg(short = 'C', long, value_name = "NUM")]
+    pub context: Option<usize>,
+    /// Highlights matches, file names and line numbers
+    #[arg(long, value_name = "WHEN", value_enum, default_value_t = Color::Auto)]
+    pub color: Color,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Color {
+    /// Only if printing to a terminal
+    Auto,
+    Always,
+    Never,
 }
 
 impl Config {
-    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
-        args.next();
+    /// Like `grep`, all positional arguments are paths if patterns come from `-f`.
+    pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Config, clap::Error> {
+        let mut config = Config::try_parse_from(args)?;
+        if config.patterns_file.is_some()
+            && let Some(path) = config.query.take()
+        {
+            config.paths.insert(0, path);
+        }
+        if config.paths.is_empty() {
+            config.paths.push(".".into());
+        }
+        // Flags take precedence over the environment.
+        if !config.smart_case && env::var("IGNORE_CASE").is_ok() {
+            config.ignore_case = true;
+        }
+        Ok(config)
+    }
+
+    pub fn before(&self) -> usize {
+        self.before.or(self.context).unwrap_or(0)
+    }
 
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string"),
+    pub fn after(&self) -> usize {
+        self.after.or(self.context).unwrap_or(0)
+    }
+
+    /// Reads patterns from `-f`, where an empty file matches nothing, like in `grep`.
+    pub fn matcher(&self) -> Result<Box<dyn Matcher>, Box<dyn Error>> {
+        let patterns = match &self.patterns_file {
+            Some(path) => fs::read_to_string(path)
+                .map_err(|err| format!("{}: {err}", path.display()))?
+                .lines()
+                .map(String::from)
+                .collect(),
+            None => self.query.iter().cloned().collect::<Vec<_>>(),
+        };
+        let case = match (self.ignore_case, self.smart_case) {
+            (true, _) => Case::Insensitive,
+            (_, true) => Case::Smart,
+            _ => Case::Sensitive,
+        };
+        let options = matcher::Options {
+            regex: self.regex,
+            whole_word: self.whole_word,
+            invert: self.invert,
+            case,
         };
+        matcher::build(&patterns, options)
+    }
+}
+
+/// Searches files and directories, skipping ignored, hidden, binary and non-UTF-8 files.
+/// Unreadable paths are reported and skipped, so that the rest is still searched.
+pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let colored = match config.color {
+        Color::Auto => io::stdout().is_terminal(),
+        Color::Always => true,
+        Color::Never => false,
+    };
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    search_paths(&config, colored, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn search_paths(
+    config: &Config,
+    colored: bool,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let matcher = config.matcher()?;
+    let paint = |color: &str, text: &dyn Display| match colored {
+        true => format!("{color}{text}{RESET}"),
+        false => text.to_string(),
+    };
+    let mut failed = 0;
+    // Like `grep`, groups of lines are separated only if there is context, across files too.
+    let (before, after) = (config.before(), config.after());
+    let separate_groups = before > 0 || after > 0;
+    let mut printed_group = false;
+    // Like `grep`, prefixes lines with file names if there may be more than one file.
+    let with_file_names =
+        config.paths.len() > 1 || config.paths.iter().any(|p| Path::new(p).is_dir());
+
+    let mut walk = ignore::WalkBuilder::new(&config.paths[0]);
+    for path in &config.paths[1..] {
+        walk.add(path);
+    }
+    // `.gitignore` files are respected outside of git repositories too.
+    walk.require_git(false).sort_by_file_path(Path::cmp);
 
-        let file_path = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a file path"),
+    for entry in walk.build() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                eprintln!("minigrep: {err}");
+                failed += 1;
+                continue;
+            }
+        };
+        if entry.file_type().is_none_or(|file_type| file_type.is_dir()) {
+            continue;
+        }
+        let path = entry.path();
+        let contents = match read_text(path) {
+            Ok(Some(contents)) => contents,
+            Ok(None) => {
+                // Only the files asked for explicitly are worth mentioning.
+                if entry.depth() == 0 {
+                    eprintln!("minigrep: {}: binary or not UTF-8, skipped", path.display());
+                }
+                continue;
+            }
+            Err(err) => {
+                eprintln!("minigrep: {}: {err}", path.display());
+                failed += 1;
+                continue;
+            }
         };
 
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
+        let results = search(matcher.as_ref(), &contents);
+        let prefix = |separator: char| match with_file_names {
+            true => paint(PATH, &path.display()) + &paint(SEPARATOR, &separator),
+            false => String::new(),
+        };
 
-        Ok(Config { query, file_path, ignore_case })
+        if config.files_with_matches {
+            if !results.is_empty() {
+                writeln!(out, "{}", paint(PATH, &path.display()))?;
+            }
+        } else if config.count {
+            writeln!(out, "{}{}", prefix(':'), results.len())?;
+        } else {
+            let groups = with_context(&contents, &results, before, after);
+            for group in &groups {
+                if separate_groups && printed_group {
+                    writeln!(out, "{}", paint(SEPARATOR, &"--"))?;
+                }
+                printed_group = true;
+                for line in group {
+                    // Like `grep`, `:` follows matches and `-` follows context lines.
+                    let separator = if line.matched { ':' } else { '-' };
+                    write!(out, "{}", prefix(separator))?;
+                    if config.line_numbers {
+                        write!(
+                            out,
+                            "{}",
+                            paint(NUMBER, &line.number) + &paint(SEPARATOR, &separator)
+                        )?;
+                    }
+                    match colored && line.matched {
+                        true => writeln!(
+                            out,
+                            "{}",
+                            highlight(line.text, &matcher.find_iter(line.text))
+                        )?,
+                        false => writeln!(out, "{}", line.text)?,
+                    }
+                }
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{failed} paths couldn't be searched").into()),
     }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.file_path)?;
+/// `None` for binary files, which have NUL bytes like `grep` assumes, and for non-UTF-8 ones.
+fn read_text(path: &Path) -> io::Result<Option<String>> {
+    let bytes = fs::read(path)?;
+    if bytes.contains(&0) {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(bytes).ok())
+}
 
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
-    } else {
-        search(&config.query, &contents)
-    };
+/// Colours of `grep` by default, as ANSI escapes.
+const MATCH: &str = "\x1b[1;31m";
+const PATH: &str = "\x1b[35m";
+const NUMBER: &str = "\x1b[32m";
+const SEPARATOR: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
 
-    for line in results {
-        println!("{line}");
+/// Wraps `ranges` of `text` in [`MATCH`] colour.
+fn highlight(text: &str, ranges: &[Range<usize>]) -> String {
+    let mut highlighted = String::with_capacity(text.len());
+    let mut end = 0;
+    for range in ranges {
+        highlighted += &text[end..range.start];
+        highlighted += &format!("{MATCH}{}{RESET}", &text[range.clone()]);
+        end = range.end;
     }
+    highlighted + &text[end..]
+}
 
-    Ok(())
+/// Selected lines with their numbers, starting from 1.
+pub fn search<'a>(matcher: &dyn Matcher, contents: &'a str) -> Vec<(usize, &'a str)> {
+    numbered(contents).filter(|(_, line)| matcher.is_match(line)).collect()
 }
 
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    contents.lines().filter(|line| line.contains(query)).collect()
+fn numbered(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents.lines().enumerate().map(|(i, line)| (i + 1, line))
 }
 
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
-    contents.lines().filter(|line| line.to_lowercase().contains(&query)).collect()
+#[derive(Debug, PartialEq)]
+pub struct Line<'a> {
+    pub number: usize,
+    pub text: &'a str,
+    pub matched: bool,
+}
+
+/// Groups of `results` with `before` and `after` lines around them,
+/// where overlapping or adjacent groups are merged.
+pub fn with_context<'a>(
+    contents: &'a str,
+    results: &[(usize, &'a str)],
+    before: usize,
+    after: usize,
+) -> Vec<Vec<Line<'a>>> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut groups: Vec<Vec<Line>> = vec![];
+    for (i, &(number, _)) in results.iter().enumerate() {
+        let start = number.saturating_sub(before).max(1);
+        let end = (number + after).min(lines.len());
+        let next_match = results.get(i + 1).map_or(usize::MAX, |(next, _)| *next);
+        let last = groups.last().and_then(|group| group.last()).map_or(0, |line| line.number);
+        if last + 1 < start || groups.is_empty() {
+            groups.push(vec![]);
+        }
+        let group = groups.last_mut().expect("A group is pushed above");
+        // Lines from `next_match` are added with it.
+        for number in start.max(last + 1)..=end.min(next_match - 1) {
+            group.push(Line { number, text: lines[number - 1], matched: number == results[i].0 });
+        }
+    }
+    groups
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn matcher(query: &str, case: Case) -> Box<dyn Matcher> {
+        let options = matcher::Options { regex: false, whole_word: false, invert: false, case };
+        matcher::build(&[query.to_string()], options).unwrap()
+    }
+
+    fn parse(args: &[&str]) -> Config {
+        let args = ["minigrep"].iter().chain(args).map(|arg| arg.to_string());
+        Config::parse_args(args).unwrap()
+    }
+
     #[test]
     fn case_sensitive() {
         let query = "duct";
@@ -65,7 +329,10 @@ safe, fast, productive.
 Pick three.
 Duct tape.";
 
-        assert_eq!(vec!["safe, fast, productive."], search(query, contents));
+        assert_eq!(
+            vec![(2, "safe, fast, productive.")],
+            search(matcher(query, Case::Sensitive).as_ref(), contents)
+        );
     }
 
     #[test]
@@ -77,6 +344,113 @@ safe, fast, productive.
 Pick three.
 Trust me.";
 
-        assert_eq!(vec!["Rust:", "Trust me."], search_case_insensitive(query, contents));
+        let results = search(matcher(query, Case::Insensitive).as_ref(), contents);
+        assert_eq!(vec![(1, "Rust:"), (4, "Trust me.")], results);
+    }
+
+    #[test]
+    fn context_groups() {
+        let contents = "a\nb\nmatch\nc\nd\ne\nmatch\nmatch\nf\ng\nh\ni\nmatch";
+        let results = search(matcher("match", Case::Sensitive).as_ref(), contents);
+        let groups: Vec<Vec<_>> = with_context(contents, &results, 1, 1)
+            .iter()
+            .map(|group| group.iter().map(|line| (line.number, line.matched)).collect())
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                vec![(2, false), (3, true), (4, false)],
+                vec![(6, false), (7, true), (8, true), (9, false)],
+                vec![(12, false), (13, true)],
+            ]
+        );
+        // Adjacent groups are merged.
+        assert_eq!(with_context(contents, &results, 2, 2).len(), 1);
+        assert_eq!(with_context(contents, &results, 0, 0).iter().flatten().count(), 4);
+    }
+
+    #[test]
+    fn searches_directories() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let files = [
+            (".gitignore", "target/\n"),
+            ("src/main.rs", "fn main() {\n    println!(\"query\");\n}\n"),
+            ("src/lib.rs", "// query\n"),
+            ("target/out.rs", "query\n"),
+            ("image.png", "query\0"),
+            ("latin1.txt", "query \u{fffd}"),
+            ("patterns.txt", "println\n// \n"),
+        ];
+        for (path, contents) in files {
+            let path = dir.child(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let mut latin1 = b"query ".to_vec();
+        latin1.push(0xe9);
+        fs::write(dir.child("latin1.txt"), latin1).unwrap();
+
+        let search = |args: &[&str]| {
+            let mut config = parse(args);
+            config.paths =
+                config.paths.iter().map(|path| dir.child(path).display().to_string()).collect();
+            config.patterns_file =
+                config.patterns_file.map(|path| dir.child(path.to_str().unwrap()));
+            let mut out = vec![];
+            search_paths(&config, config.color == Color::Always, &mut out).unwrap();
+            String::from_utf8(out).unwrap().replace(&format!("{}/", dir.path().display()), "")
+        };
+        assert_eq!(search(&["-l", "query", "."]), "./src/lib.rs\n./src/main.rs\n");
+        assert_eq!(search(&["-c", "query", "src/main.rs"]), "1\n");
+        assert_eq!(
+            search(&["query", "src"]),
+            "src/lib.rs:// query\nsrc/main.rs:    println!(\"query\");\n"
+        );
+        assert_eq!(
+            search(&["-n", "-B", "1", "query", "src"]),
+            "src/lib.rs:1:// query\n--\nsrc/main.rs-1-fn main() {\nsrc/main.rs:2:    println!(\"query\");\n"
+        );
+        assert_eq!(
+            search(&["-f", "patterns.txt", "src"]),
+            "src/lib.rs:// query\nsrc/main.rs:    println!(\"query\");\n"
+        );
+        assert_eq!(search(&["-v", "-c", "query", "src/main.rs"]), "2\n");
+        assert_eq!(
+            search(&["--color", "always", "-n", "-E", "q\\w+", "src/lib.rs"]),
+            "\x1b[32m1\x1b[0m\x1b[36m:\x1b[0m// \x1b[1;31mquery\x1b[0m\n"
+        );
+        assert!(search_paths(&parse(&["query", "missing"]), false, &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn highlights_matches() {
+        let query = Range { start: 2, end: 7 };
+        assert_eq!(highlight("a query", &[query]), "a \x1b[1;31mquery\x1b[0m");
+        assert_eq!(highlight("ab", &[0..1, 1..2]), "\x1b[1;31ma\x1b[0m\x1b[1;31mb\x1b[0m");
+        assert_eq!(highlight("none", &[]), "none");
+    }
+
+    #[test]
+    fn parses_options() {
+        let config = parse(&["-n", "-C", "2", "-A", "3", "query", "src", "README.md"]);
+        assert_eq!(config.query.as_deref(), Some("query"));
+        assert_eq!(config.paths, ["src", "README.md"]);
+        assert!(config.line_numbers && !config.count && !config.files_with_matches);
+        assert_eq!((config.before(), config.after()), (2, 3));
+
+        let config = parse(&["query"]);
+        assert_eq!(config.paths, ["."]);
+        assert_eq!((config.before(), config.after()), (0, 0));
+
+        // All positional arguments are paths with `-f`.
+        let config = parse(&["-f", "patterns.txt", "src"]);
+        assert_eq!((config.query, config.paths), (None, vec!["src".to_string()]));
+        let config = parse(&["-i", "--smart-case", "query"]);
+        assert!(!config.ignore_case && config.smart_case);
+
+        let err = Config::parse_args(["minigrep", "-A", "x", "q"].map(String::from)).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+        let err = Config::parse_args(["minigrep"].map(String::from)).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
     }
 }